@@ -0,0 +1,358 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/// Parses `/etc/group` into one row per member, so `join group() using (...)` finds
+/// every group a user is in. Groups without members get a single row with no member.
+pub fn parse_group(output: &str) -> Vec<Value> {
+    let mut rows = Vec::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 4 {
+            continue;
+        }
+        let gid = fields[2].parse::<i64>().ok();
+        let members: Vec<&str> = fields[3].split(',').filter(|m| !m.is_empty()).collect();
+        if members.is_empty() {
+            rows.push(json!({ "group_name": fields[0], "gid": gid, "member": null }));
+        }
+        for member in members {
+            rows.push(json!({ "group_name": fields[0], "gid": gid, "member": member }));
+        }
+    }
+    rows
+}
+
+/// Parses `/etc/shadow` into `chage -l` style password aging, with the hash itself
+/// replaced by whether there is one.
+pub fn parse_shadow(output: &str) -> Vec<Value> {
+    let date = |days: Option<i64>| {
+        days.and_then(|days| NaiveDate::from_ymd_opt(1970, 1, 1)?.checked_add_signed(Duration::days(days)))
+            .map(|date| date.to_string())
+    };
+    let mut rows = Vec::new();
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 8 {
+            continue;
+        }
+        let number = |i: usize| fields[i].parse::<i64>().ok();
+        let status = match fields[1] {
+            "" => "empty",
+            hash if hash.starts_with('!') || hash.starts_with('*') => "locked",
+            _ => "set",
+        };
+        let (last_changed, max_days) = (number(2), number(4));
+        // A last change of 0 means the password has to be changed at next login.
+        let expires = match (last_changed, max_days) {
+            (Some(0), _) => Some(0),
+            (Some(changed), Some(max)) if max < 99999 => Some(changed + max),
+            _ => None,
+        };
+        rows.push(json!({
+            "username": fields[0],
+            "password_status": status,
+            "last_changed": date(last_changed),
+            "min_days": number(3),
+            "max_days": max_days,
+            "warn_days": number(5),
+            "inactive_days": number(6),
+            "password_expires": date(expires),
+            "account_expires": date(number(7)),
+        }));
+    }
+    rows
+}
+
+/// Parses the user specifications out of `grep -H ^ /etc/sudoers ...`, one row per
+/// rule. Defaults, aliases and includes are left out.
+pub fn parse_sudoers(output: &str) -> Vec<Value> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"^([A-Z_]+):\s*").unwrap());
+
+    let mut rows = Vec::new();
+    let mut pending: Option<(String, String)> = None;
+    for line in output.lines() {
+        let Some((file, text)) = line.split_once(':') else {
+            continue;
+        };
+        // Lines ending in a backslash carry on to the next one.
+        let (file, text) = match pending.take() {
+            Some((file, start)) => (file, format!("{} {}", start, text.trim())),
+            None => (file.to_string(), text.trim().to_string()),
+        };
+        if let Some(start) = text.strip_suffix('\\') {
+            pending = Some((file, start.trim().to_string()));
+            continue;
+        }
+        let first = text.split_whitespace().next().unwrap_or("");
+        // Comments, #include and @include lines, Defaults and aliases.
+        if text.is_empty()
+            || text.starts_with('#')
+            || text.starts_with('@')
+            || first.starts_with("Defaults")
+            || first.ends_with("_Alias")
+        {
+            continue;
+        }
+        let Some((who, spec)) = text.split_once('=') else {
+            continue;
+        };
+        let mut who = who.split_whitespace();
+        let principal = who.next().unwrap_or("");
+        let hosts = who.collect::<Vec<_>>().join(" ");
+
+        let mut spec = spec.trim();
+        let mut runas = None;
+        if let Some(rest) = spec.strip_prefix('(') {
+            if let Some((inside, rest)) = rest.split_once(')') {
+                runas = Some(inside.trim().to_string());
+                spec = rest.trim();
+            }
+        }
+        let mut tags = Vec::new();
+        while let Some(caps) = tag.captures(spec) {
+            tags.push(caps[1].to_string());
+            spec = &spec[caps[0].len()..];
+        }
+        rows.push(json!({
+            "file": file,
+            "principal": principal,
+            "hosts": hosts,
+            "runas": runas,
+            "tags": if tags.is_empty() { None } else { Some(tags.join(",")) },
+            "commands": spec,
+            "line": text,
+        }));
+    }
+    rows
+}
+
+/// Parses `last -F -w` (or `lastb`) run with `TZ=UTC`, so login and logout come out as
+/// real timestamps. Reboots and shutdowns show up as the `reboot` and `shutdown` users.
+pub fn parse_last(output: &str) -> Vec<Value> {
+    static LAST: OnceLock<Regex> = OnceLock::new();
+    let last = LAST.get_or_init(|| {
+        let date = r"\w{3} \w{3} +\d+ \d\d:\d\d:\d\d \d{4}";
+        Regex::new(&format!(
+            r"^(\S+)\s+(system boot|\S+)\s+(?:(\S+)\s+)?({date})(?:\s+-\s+({date})|\s+-\s+(crash|down)|\s+(still logged in|still running|gone - no logout))?"
+        ))
+        .unwrap()
+    });
+    let timestamp = |s: &str| {
+        let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        NaiveDateTime::parse_from_str(&s, "%a %b %d %H:%M:%S %Y")
+            .ok()
+            .map(|t| t.and_utc().timestamp())
+    };
+
+    let mut rows = Vec::new();
+    for line in output.lines() {
+        // The trailing "wtmp begins ..." line looks like a login too.
+        if line.split_whitespace().nth(1) == Some("begins") {
+            continue;
+        }
+        let Some(caps) = last.captures(line) else {
+            continue;
+        };
+        let login = timestamp(&caps[4]);
+        let logout = caps.get(5).and_then(|m| timestamp(m.as_str()));
+        let status = match (caps.get(5), caps.get(6).or(caps.get(7))) {
+            (Some(_), _) => "logged out",
+            (None, Some(status)) => status.as_str(),
+            (None, None) => "unknown",
+        };
+        let duration = match (login, logout) {
+            (Some(login), Some(logout)) => Some(logout - login),
+            _ => None,
+        };
+        rows.push(json!({
+            "user": &caps[1],
+            "tty": &caps[2],
+            "hostname": caps.get(3).map(|m| m.as_str()),
+            "login": login,
+            "logout": logout,
+            "duration_seconds": duration,
+            "status": status,
+        }));
+    }
+    rows
+}
+
+/// Parses `id`, one row for each group the user is in, primary group included.
+pub fn parse_id(output: &str) -> Vec<Value> {
+    static ID: OnceLock<Regex> = OnceLock::new();
+    let id = ID.get_or_init(|| Regex::new(r"(\d+)(?:\(([^)]*)\))?").unwrap());
+
+    let mut rows = Vec::new();
+    for line in output.lines() {
+        let mut user = (None, None);
+        let mut primary_gid = None;
+        let mut groups = Vec::new();
+        for part in line.split_whitespace() {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            let ids: Vec<(Option<i64>, Option<String>)> = id
+                .captures_iter(value)
+                .map(|caps| (caps[1].parse().ok(), caps.get(2).map(|m| m.as_str().to_string())))
+                .collect();
+            match key {
+                "uid" => user = ids.into_iter().next().unwrap_or_default(),
+                "gid" => primary_gid = ids.first().and_then(|(gid, _)| *gid),
+                "groups" => groups = ids,
+                _ => {}
+            }
+        }
+        for (gid, group_name) in groups {
+            rows.push(json!({
+                "user": user.1,
+                "uid": user.0,
+                "primary_gid": primary_gid,
+                "gid": gid,
+                "group_name": group_name,
+            }));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_entries_become_password_aging() {
+        let output = "\
+root:*:19000:0:99999:7:::
+alice:$6$salt$hash:19500:1:90:7:14::
+bob::0:0:99999:7:::
+svc:!:19000:::::19999:
+short:line
+";
+        assert_eq!(
+            parse_shadow(output),
+            vec![
+                json!({
+                    "username": "root", "password_status": "locked", "last_changed": "2022-01-08",
+                    "min_days": 0, "max_days": 99999, "warn_days": 7, "inactive_days": null,
+                    "password_expires": null, "account_expires": null,
+                }),
+                json!({
+                    "username": "alice", "password_status": "set", "last_changed": "2023-05-23",
+                    "min_days": 1, "max_days": 90, "warn_days": 7, "inactive_days": 14,
+                    "password_expires": "2023-08-21", "account_expires": null,
+                }),
+                json!({
+                    "username": "bob", "password_status": "empty", "last_changed": "1970-01-01",
+                    "min_days": 0, "max_days": 99999, "warn_days": 7, "inactive_days": null,
+                    "password_expires": "1970-01-01", "account_expires": null,
+                }),
+                json!({
+                    "username": "svc", "password_status": "locked", "last_changed": "2022-01-08",
+                    "min_days": null, "max_days": null, "warn_days": null, "inactive_days": null,
+                    "password_expires": null, "account_expires": "2024-10-03",
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn sudoers_rules_parse_across_continuations_skipping_aliases() {
+        let output = "\
+/etc/sudoers:# User privilege specification
+/etc/sudoers:Defaults\tenv_reset
+/etc/sudoers:User_Alias ADMINS = alice, \\
+/etc/sudoers:    bob
+/etc/sudoers:root\tALL=(ALL:ALL) ALL
+/etc/sudoers:%sudo   ALL=(ALL:ALL) ALL
+/etc/sudoers:@includedir /etc/sudoers.d
+/etc/sudoers.d/ops:ADMINS web1, web2 = (root) NOPASSWD: SETENV: /usr/bin/systemctl restart nginx, \\
+/etc/sudoers.d/ops:    /usr/bin/journalctl
+/etc/sudoers.d/ops:deploy ALL = /usr/bin/rsync
+";
+        let rows = parse_sudoers(output);
+        let rules: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r["file"].as_str().unwrap(),
+                    r["principal"].as_str().unwrap(),
+                    r["hosts"].as_str().unwrap(),
+                    r["runas"].as_str(),
+                    r["tags"].as_str(),
+                    r["commands"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("/etc/sudoers", "root", "ALL", Some("ALL:ALL"), None, "ALL"),
+                ("/etc/sudoers", "%sudo", "ALL", Some("ALL:ALL"), None, "ALL"),
+                (
+                    "/etc/sudoers.d/ops",
+                    "ADMINS",
+                    "web1, web2",
+                    Some("root"),
+                    Some("NOPASSWD,SETENV"),
+                    "/usr/bin/systemctl restart nginx, /usr/bin/journalctl",
+                ),
+                ("/etc/sudoers.d/ops", "deploy", "ALL", None, None, "/usr/bin/rsync"),
+            ]
+        );
+        assert_eq!(rows[3]["line"], "deploy ALL = /usr/bin/rsync");
+    }
+
+    #[test]
+    fn last_logins_parse_with_their_status() {
+        let output = "\
+alice    pts/0        10.0.0.9         Mon Jan  1 10:00:00 2024 - Mon Jan  1 11:30:00 2024  (01:30)
+bob      pts/1        192.168.1.5      Tue Jan  2 09:00:00 2024   still logged in
+reboot   system boot  6.1.0-18-amd64   Tue Jan  2 08:59:00 2024   still running
+carol    tty1                          Mon Jan  1 07:00:00 2024 - crash                     (01:00)
+root     tty2                          Mon Jan  1 06:00:00 2024 - down                      (00:30)
+
+wtmp begins Mon Jan  1 00:00:00 2024
+";
+        assert_eq!(
+            parse_last(output),
+            vec![
+                json!({
+                    "user": "alice", "tty": "pts/0", "hostname": "10.0.0.9", "login": 1704103200,
+                    "logout": 1704108600, "duration_seconds": 5400, "status": "logged out",
+                }),
+                json!({
+                    "user": "bob", "tty": "pts/1", "hostname": "192.168.1.5", "login": 1704186000,
+                    "logout": null, "duration_seconds": null, "status": "still logged in",
+                }),
+                json!({
+                    "user": "reboot", "tty": "system boot", "hostname": "6.1.0-18-amd64", "login": 1704185940,
+                    "logout": null, "duration_seconds": null, "status": "still running",
+                }),
+                json!({
+                    "user": "carol", "tty": "tty1", "hostname": null, "login": 1704092400,
+                    "logout": null, "duration_seconds": null, "status": "crash",
+                }),
+                json!({
+                    "user": "root", "tty": "tty2", "hostname": null, "login": 1704088800,
+                    "logout": null, "duration_seconds": null, "status": "down",
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn id_has_a_row_per_group() {
+        let output = "uid=1000(alice) gid=1000(alice) groups=1000(alice),27(sudo),1234\n";
+        let row = |gid: i64, name: Option<&str>| {
+            json!({ "user": "alice", "uid": 1000, "primary_gid": 1000, "gid": gid, "group_name": name })
+        };
+        assert_eq!(
+            parse_id(output),
+            vec![row(1000, Some("alice")), row(27, Some("sudo")), row(1234, None)]
+        );
+        assert_eq!(parse_id("id: 'nobody-here': no such user\n"), Vec::<Value>::new());
+    }
+}
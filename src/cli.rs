@@ -0,0 +1,381 @@
+use datafusion::arrow::array::{ArrayRef, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::plan_err;
+use datafusion::datasource::TableType;
+use datafusion::error::Result;
+use datafusion::execution::context::SessionContext;
+use datafusion::physical_plan::{collect, execute_stream, ExecutionPlanProperties};
+use futures::StreamExt;
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::Editor;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::context::{CommandTables, ZqueryContext};
+use crate::engine::sql_to_df;
+use crate::error::ZqueryError;
+use crate::format::{write_batches, Format, Output};
+use crate::helper::ZqueryHelper;
+use crate::remote::Remote;
+use crate::script::{split_script, substitute, ScriptItem};
+
+const HELP: &str = r"Meta-commands:
+  \dt                        list command tables and tables in the session
+  \d [name]                  describe a table, or list them all
+  \hosts                     list the hosts in ~/.ssh/config
+  \format [name] [width]     show or set the output format and max column width
+  \set [name value]          set a variable for :name, or list them
+  \unset name                remove a variable
+  \timing [on|off]           show how long each query takes
+  \help                      show this help
+
+Anything else is run as SQL, e.g. select * from ps() where cpu_percent > 1;";
+
+async fn run_sql(
+    ctx: &SessionContext,
+    sql: &str,
+    output: &Output,
+    out: &mut (dyn Write + Send),
+) -> Result<()> {
+    let df = sql_to_df(ctx, sql).await?;
+    let plan = df.create_physical_plan().await?;
+    let schema = plan.schema();
+    if !plan.execution_mode().is_unbounded() {
+        let batches = collect(plan, ctx.task_ctx()).await?;
+        write_batches(&mut *out, &schema, &batches, output.format, &output.options)?;
+        if output.format == Format::Json {
+            writeln!(out)?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    // Streaming queries never finish, so write each batch as it arrives until ctrl-c.
+    // A JSON array would never be closed, so those come out a row per line instead.
+    let format = match output.format {
+        Format::Json => Format::Ndjson,
+        format if !format.is_streamable() => {
+            return plan_err!("{:?} output needs a query that finishes, not a stream", format)
+        }
+        format => format,
+    };
+    let mut options = output.options.clone();
+    let mut stream = execute_stream(plan, ctx.task_ctx())?;
+    loop {
+        tokio::select! {
+            batch = stream.next() => match batch {
+                Some(batch) => {
+                    let batch = batch?;
+                    if batch.num_rows() > 0 {
+                        write_batches(&mut *out, &schema, &[batch], format, &options)?;
+                        out.flush()?;
+                        options.header = false;
+                    }
+                }
+                None => break,
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+/// What scripts and the REPL share: the session, where results go, and the
+/// variables set with `-v` and `\set`.
+struct Cli {
+    ctx: SessionContext,
+    output: Output,
+    out: Box<dyn Write + Send>,
+    vars: HashMap<String, String>,
+    timing: bool,
+}
+
+impl Cli {
+    /// `--strict` makes a whole `hosts()` fan-out fail when any host fails.
+    async fn new(output: Output, strict: bool, vars: Vec<(String, String)>) -> std::io::Result<Self> {
+        Ok(Self {
+            ctx: ZqueryContext::new().with_strict(strict).build(),
+            out: output.open()?,
+            output,
+            vars: vars.into_iter().collect(),
+            timing: false,
+        })
+    }
+
+    async fn run(&mut self, item: ScriptItem) -> std::result::Result<(), ZqueryError> {
+        match item {
+            ScriptItem::Meta(line) => self.meta_command(&line).await,
+            ScriptItem::Sql(sql) => {
+                let sql = substitute(&sql, &self.vars);
+                let started = Instant::now();
+                let result = run_sql(&self.ctx, &sql, &self.output, &mut self.out).await;
+                if self.timing {
+                    eprintln!("Time: {:.3} ms", started.elapsed().as_secs_f64() * 1000.0);
+                }
+                Ok(result?)
+            }
+        }
+    }
+
+    /// Runs each statement in order, stopping at the first failure unless
+    /// `continue_on_error` is set.
+    async fn run_script(&mut self, script: &str, continue_on_error: bool) -> std::result::Result<(), ZqueryError> {
+        let items = split_script(script);
+        let total = items.len();
+        let mut failures = Vec::new();
+        for item in items {
+            if let Err(e) = self.run(item).await {
+                if !continue_on_error {
+                    return Err(e);
+                }
+                eprintln!("Error: {}", e);
+                failures.push(e);
+            }
+        }
+        match failures.len() {
+            0 => Ok(()),
+            failed => Err(ZqueryError::Statements {
+                failed,
+                total,
+                first: Box::new(failures.swap_remove(0)),
+            }),
+        }
+    }
+
+    /// Handles a `\` command instead of SQL.
+    async fn meta_command(&mut self, line: &str) -> std::result::Result<(), ZqueryError> {
+        let usage = |message: &str| Err(ZqueryError::Usage(message.to_string()));
+        let mut words = line.split_whitespace();
+        match words.next().unwrap_or_default() {
+            "\\dt" => self.list_tables().await?,
+            "\\d" => match words.next() {
+                Some(name) => self.describe(name).await?,
+                None => self.list_tables().await?,
+            },
+            "\\hosts" => {
+                let remote = Remote::of(&self.ctx.state());
+                let hosts = remote.inventory.hosts()?.into_iter().map(|host| vec![host]).collect();
+                self.print_rows(&["host"], hosts)?;
+            }
+            "\\timing" => {
+                self.timing = match words.next() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    Some(_) => return usage("\\timing takes on or off"),
+                    None => !self.timing,
+                };
+                println!("Timing is {}.", if self.timing { "on" } else { "off" });
+            }
+            "\\help" | "\\?" => println!("{}", HELP),
+            "\\format" => match words.next() {
+                None => println!(
+                    "Format is {:?}, max width is {}",
+                    self.output.format,
+                    self.output
+                        .options
+                        .max_width
+                        .map_or("unlimited".to_string(), |w| w.to_string())
+                ),
+                Some(name) => {
+                    let format = name.parse::<Format>().map_err(ZqueryError::Usage)?;
+                    match words.next().map(|w| w.parse::<usize>()) {
+                        Some(Err(_)) => return usage("max width must be a number"),
+                        Some(Ok(width)) => self.output.options.max_width = Some(width).filter(|w| *w > 0),
+                        None => {}
+                    }
+                    self.output.format = format;
+                }
+            },
+            // `\set name value` sets a variable, `\set` lists them.
+            "\\set" => match words.next() {
+                None => {
+                    let mut vars: Vec<_> = self.vars.iter().collect();
+                    vars.sort();
+                    for (name, value) in vars {
+                        println!("{} = '{}'", name, value);
+                    }
+                }
+                Some(name) => {
+                    let value = line.splitn(3, char::is_whitespace).nth(2).unwrap_or_default();
+                    self.vars.insert(name.to_string(), value.trim().to_string());
+                }
+            },
+            "\\unset" => match words.next() {
+                Some(name) => {
+                    self.vars.remove(name);
+                }
+                None => return usage("\\unset needs a variable name"),
+            },
+            command => {
+                return Err(ZqueryError::Usage(format!(
+                    "unknown command {}, try \\help",
+                    command
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// `\dt`: the command tables, then any tables created in the session.
+    async fn list_tables(&mut self) -> std::result::Result<(), ZqueryError> {
+        let mut rows: Vec<Vec<String>> = CommandTables::of(self.ctx.state().config())
+            .0
+            .into_iter()
+            .map(|(name, func)| {
+                vec![
+                    name.clone(),
+                    "command table".to_string(),
+                    func.signature(&name),
+                    func.description.to_string(),
+                ]
+            })
+            .collect();
+        for catalog_name in self.ctx.catalog_names() {
+            let catalog = match self.ctx.catalog(&catalog_name) {
+                Some(catalog) => catalog,
+                None => continue,
+            };
+            for schema_name in catalog.schema_names() {
+                let schema = match catalog.schema(&schema_name) {
+                    Some(schema) => schema,
+                    None => continue,
+                };
+                let mut names = schema.table_names();
+                names.sort();
+                for name in names {
+                    let kind = match schema.table(&name).await?.map(|t| t.table_type()) {
+                        Some(TableType::View) => "view",
+                        Some(TableType::Temporary) => "temporary table",
+                        _ => "table",
+                    };
+                    rows.push(vec![name, kind.to_string(), String::new(), String::new()]);
+                }
+            }
+        }
+        self.print_rows(&["name", "type", "signature", "description"], rows)
+    }
+
+    /// `\d name`: how a command table is run, then the columns of any table.
+    async fn describe(&mut self, name: &str) -> std::result::Result<(), ZqueryError> {
+        if let Some(func) = CommandTables::of(self.ctx.state().config()).get(name) {
+            writeln!(self.out, "Command table {}: {}", name, func.description)?;
+            writeln!(self.out, "  Usage:     {}", func.signature(name))?;
+            writeln!(self.out, "  Command:   {}", func.command.join(" "))?;
+            writeln!(self.out, "  Parser:    {}", func.parser)?;
+            writeln!(self.out, "  Platforms: {}", func.platforms.join(", "))?;
+        }
+        let sql = format!("describe {}", name);
+        Ok(run_sql(&self.ctx, &sql, &self.output, &mut self.out).await?)
+    }
+
+    /// Prints rows of text in the current output format.
+    fn print_rows(&mut self, columns: &[&str], rows: Vec<Vec<String>>) -> std::result::Result<(), ZqueryError> {
+        let schema = Schema::new(
+            columns
+                .iter()
+                .map(|c| Field::new(*c, DataType::Utf8, false))
+                .collect::<Vec<_>>(),
+        );
+        let arrays: Vec<ArrayRef> = (0..columns.len())
+            .map(|i| Arc::new(StringArray::from_iter_values(rows.iter().map(|r| &r[i]))) as ArrayRef)
+            .collect();
+        let batch = RecordBatch::try_new(Arc::new(schema.clone()), arrays)
+            .map_err(|e| ZqueryError::Sql(e.into()))?;
+        write_batches(&mut self.out, &schema, &[batch], self.output.format, &self.output.options)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Runs the statements from `--eval`, `-f` or stdin.
+pub async fn cli_script(
+    script: String,
+    output: Output,
+    strict: bool,
+    vars: Vec<(String, String)>,
+    continue_on_error: bool,
+) -> std::result::Result<(), ZqueryError> {
+    let mut cli = Cli::new(output, strict, vars).await?;
+    cli.run_script(&script, continue_on_error).await
+}
+
+/// `$XDG_DATA_HOME/zquery/history`, which is usually `~/.local/share/zquery/history`.
+fn history_path() -> PathBuf {
+    let data = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".local/share"),
+    };
+    data.join("zquery").join("history")
+}
+
+pub async fn cli_repl(output: Output, strict: bool, vars: Vec<(String, String)>) -> std::io::Result<()> {
+    let mut cli = Cli::new(output, strict, vars).await?;
+
+    let mut rl: Editor<ZqueryHelper, FileHistory> = Editor::new().map_err(std::io::Error::other)?;
+    let helper = ZqueryHelper::new(cli.ctx.clone());
+    helper.refresh().await;
+    rl.set_helper(Some(helper));
+    let history = history_path();
+    if let Some(dir) = history.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if rl.load_history(&history).is_err() {
+        println!("No previous history.");
+    }
+    println!("Type \\help for help.");
+    loop {
+        let readline = rl.readline(">> ");
+        match readline {
+            Ok(line) => {
+                let _ = rl.add_history_entry(line.as_str());
+                for item in split_script(&line) {
+                    if let Err(e) = cli.run(item).await {
+                        println!("Error: {}", e);
+                    }
+                }
+                if let Some(helper) = rl.helper() {
+                    helper.refresh().await;
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                break;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break;
+            }
+        }
+    }
+    rl.save_history(&history).map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::WriteOptions;
+
+    #[tokio::test]
+    async fn describing_a_command_table_writes_everything_to_the_output() {
+        let path = std::env::temp_dir().join(format!("zquery-describe-{}.txt", std::process::id()));
+        let output = Output { format: Format::Csv, options: WriteOptions::default(), path: Some(path.clone()) };
+        let mut cli = Cli::new(output, false, Vec::new()).await.unwrap();
+        cli.run(ScriptItem::Meta("\\d ps".to_string())).await.unwrap();
+        drop(cli);
+        let written = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(written.starts_with("Command table ps: Processes.\n  Usage:     ps("), "{}", written);
+        assert!(written.contains("\n  Parser:    jc --ps\n"), "{}", written);
+        assert!(written.contains("\ncolumn_name,data_type,is_nullable\nuser,Utf8,YES\npid,Int64,YES\n"), "{}", written);
+    }
+}
@@ -1,10 +1,22 @@
+use crate::accounts::{parse_group, parse_id, parse_last, parse_shadow, parse_sudoers};
 use crate::commandtable::*;
-use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use crate::cron::{parse_cron_files, parse_crontab, CRONTAB};
+use crate::docker::*;
+use crate::git::*;
+use crate::hardware::*;
+use crate::k8s::*;
+use crate::net::parse_ss;
+use crate::packages::*;
+use crate::perf::*;
+use crate::procfs::*;
+use crate::systemd::parse_systemctl_show;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
 use std::sync::Arc;
 use datafusion::common::Result;
 
 use std::any::Any;
-use datafusion_expr::{ColumnarValue, Signature, Volatility};
+use datafusion::scalar::ScalarValue;
+use datafusion_expr::{ColumnarValue, Signature, TypeSignature, Volatility};
 use datafusion_expr::ScalarUDFImpl;
 
 #[derive(Debug)]
@@ -20,6 +32,10 @@ impl Host {
     }
 }
 
+impl Default for Host {
+    fn default() -> Self { Self::new() }
+}
+
 /// Implement the ScalarUDFImpl trait for AddOne
 impl ScalarUDFImpl for Host {
     fn as_any(&self) -> &dyn Any { self }
@@ -37,10 +53,227 @@ impl ScalarUDFImpl for Host {
     }
 }
 
+/// `hosts('prod-*')` runs a command table on every host in `~/.ssh/config` whose
+/// name matches the glob, adding a `host` column to tell the rows apart.
+#[derive(Debug)]
+pub struct Hosts {
+    signature: Signature
+}
+
+impl Hosts {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::uniform(1, vec![DataType::Utf8], Volatility::Immutable)
+        }
+    }
+}
+
+impl Default for Hosts {
+    fn default() -> Self { Self::new() }
+}
+
+impl ScalarUDFImpl for Hosts {
+    fn as_any(&self) -> &dyn Any { self }
+    fn name(&self) -> &str { "hosts" }
+    fn signature(&self) -> &Signature { &self.signature }
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+      Ok(DataType::Utf8)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        Ok(args[0].clone())
+    }
+}
+
+/// A scalar stand-in named after a command table. DataFusion plans table function
+/// arguments as scalar expressions, so without this `stream(ps(), interval '5s')`
+/// would fail to plan before `stream` ever sees `ps()`.
+#[derive(Debug)]
+pub struct TableArg {
+    name: String,
+    signature: Signature
+}
+
+impl TableArg {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            signature: Signature::one_of(
+                vec![TypeSignature::Exact(vec![]), TypeSignature::VariadicAny],
+                Volatility::Immutable,
+            )
+        }
+    }
+}
+
+impl ScalarUDFImpl for TableArg {
+    fn as_any(&self) -> &dyn Any { self }
+    fn name(&self) -> &str { &self.name }
+    fn signature(&self) -> &Signature { &self.signature }
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+      Ok(DataType::Utf8)
+    }
+    // Only ever used as an argument to another table function, so evaluating it
+    // just yields the table's name.
+    fn invoke(&self, _args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        Ok(ColumnarValue::Scalar(ScalarValue::Utf8(Some(self.name.clone()))))
+    }
+    fn invoke_no_args(&self, _number_rows: usize) -> Result<ColumnarValue> {
+        Ok(ColumnarValue::Scalar(ScalarValue::Utf8(Some(self.name.clone()))))
+    }
+}
+
+/// Placeholder for `key => value` table function arguments. The engine rewrites
+/// them to `named_arg('key', value)` so table functions can still see them.
+#[derive(Debug)]
+pub struct NamedArg {
+    signature: Signature
+}
+
+impl NamedArg {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::any(2, Volatility::Immutable)
+        }
+    }
+}
+
+impl Default for NamedArg {
+    fn default() -> Self { Self::new() }
+}
+
+impl ScalarUDFImpl for NamedArg {
+    fn as_any(&self) -> &dyn Any { self }
+    fn name(&self) -> &str { "named_arg" }
+    fn signature(&self) -> &Signature { &self.signature }
+    fn return_type(&self, args: &[DataType]) -> Result<DataType> {
+      Ok(args[1].clone())
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        Ok(args[1].clone())
+    }
+}
+
+/// Platforms `jc` parses the output of most commands for.
+const UNIX: &[&str] = &["linux", "darwin", "cygwin", "aix", "freebsd"];
+
+/// Runs the command named after it, for commands whose exit status of 1 only means some
+/// of what they were given couldn't be read or found, like `find` over a directory with
+/// unreadable parts, and whose output is still worth having.
+const PARTIAL_OK: &str = r#""$0" "$@"; status=$?; [ $status -le 1 ] || exit $status"#;
+
+/// A column of string keys and values, like labels, read from a JSON object.
+fn string_map() -> DataType {
+    DataType::Map(
+        Arc::new(Field::new(
+            "entries",
+            DataType::Struct(
+                vec![
+                    Field::new("key", DataType::Utf8, false),
+                    Field::new("value", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+            false,
+        )),
+        false,
+    )
+}
+
+/// A column of objects with these fields, read from a JSON array.
+fn struct_list(fields: Vec<Field>) -> DataType {
+    DataType::List(Arc::new(Field::new("item", DataType::Struct(fields.into()), true)))
+}
+
+fn string_list() -> DataType {
+    DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)))
+}
+
+/// Every built-in command table along with the name it is registered under.
+pub fn command_table_funcs() -> Vec<(&'static str, Arc<CommandTableFunc>)> {
+    vec![
+        ("ps", ps_table_func()),
+        ("uptime", uptime_table_func()),
+        ("who", who_table_func()),
+        ("ls", ls_table_func()),
+        ("stat", stat_table_func()),
+        ("df", df_table_func()),
+        ("du", du_table_func()),
+        ("blkid", blkid_table_func()),
+        ("env", env_table_func()),
+        ("date", date_table_func()),
+        ("dir", dir_table_func()),
+        ("dpkg_list", dpkg_list_table_func()),
+        ("file", file_table_func()),
+        ("find", find_table_func()),
+        ("free", free_table_func()),
+        ("ss", ss_table_func()),
+        ("netstat", netstat_table_func()),
+        ("ip_addr", ip_addr_table_func()),
+        ("ip_route", ip_route_table_func()),
+        ("arp", arp_table_func()),
+        ("ifconfig", ifconfig_table_func()),
+        ("systemctl_units", systemctl_units_table_func()),
+        ("systemctl_show", systemctl_show_table_func()),
+        ("systemctl_timers", systemctl_timers_table_func()),
+        ("passwd", passwd_table_func()),
+        ("group", group_table_func()),
+        ("shadow", shadow_table_func()),
+        ("sudoers", sudoers_table_func()),
+        ("last", last_table_func()),
+        ("lastb", lastb_table_func()),
+        ("id", id_table_func()),
+        ("rpm_qa", rpm_qa_table_func()),
+        ("apk_list", apk_list_table_func()),
+        ("pacman_q", pacman_q_table_func()),
+        ("pip_list", pip_list_table_func()),
+        ("cargo_install_list", cargo_install_list_table_func()),
+        ("packages", packages_table_func()),
+        ("docker_ps", docker_ps_table_func()),
+        ("docker_images", docker_images_table_func()),
+        ("docker_stats", docker_stats_table_func()),
+        ("docker_inspect", docker_inspect_table_func()),
+        ("k8s_pods", k8s_pods_table_func()),
+        ("k8s_nodes", k8s_nodes_table_func()),
+        ("k8s_events", k8s_events_table_func()),
+        ("k8s_top_pods", k8s_top_pods_table_func()),
+        ("git_log", git_log_table_func()),
+        ("git_branches", git_branches_table_func()),
+        ("git_status", git_status_table_func()),
+        ("git_blame", git_blame_table_func()),
+        ("git_diff_stat", git_diff_stat_table_func()),
+        ("lsblk", lsblk_table_func()),
+        ("lsmod", lsmod_table_func()),
+        ("lscpu", lscpu_table_func()),
+        ("lspci", lspci_table_func()),
+        ("lsusb", lsusb_table_func()),
+        ("dmidecode", dmidecode_table_func()),
+        ("sysctl", sysctl_table_func()),
+        ("mounts", mounts_table_func()),
+        ("crontab", crontab_table_func()),
+        ("cron_files", cron_files_table_func()),
+        ("vmstat", vmstat_table_func()),
+        ("iostat", iostat_table_func()),
+        ("mpstat", mpstat_table_func()),
+        ("pidstat", pidstat_table_func()),
+        ("top", top_table_func()),
+        ("lsof", lsof_table_func()),
+        ("proc_status", proc_status_table_func()),
+        ("proc_cmdline", proc_cmdline_table_func()),
+        ("proc_environ", proc_environ_table_func()),
+        ("proc_limits", proc_limits_table_func()),
+        ("proc_fds", proc_fds_table_func()),
+        ("proc_maps", proc_maps_table_func()),
+        ("proc_io", proc_io_table_func()),
+    ]
+}
+
 pub fn ps_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["ps", "aux"],
-        jc_parser: "ps",
+        parser: Parser::Jc("ps"),
+        args: "",
+        description: "Processes.",
+        platforms: UNIX,
         schema: Arc::new(Schema::new(vec![
             Field::new("user", DataType::Utf8, true),
             Field::new("pid", DataType::Int64, true),
@@ -61,7 +294,10 @@ pub fn ps_table_func() -> Arc<CommandTableFunc> {
 pub fn uptime_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["uptime"],
-        jc_parser: "uptime",
+        parser: Parser::Jc("uptime"),
+        args: "",
+        description: "Uptime, users and load averages.",
+        platforms: UNIX,
         schema: Arc::new(Schema::new(vec![
             Field::new("uptime", DataType::Utf8, true),
             Field::new("users", DataType::Int64, true),
@@ -83,7 +319,10 @@ pub fn uptime_table_func() -> Arc<CommandTableFunc> {
 pub fn who_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["who", "-a"],
-        jc_parser: "who",
+        parser: Parser::Jc("who"),
+        args: "",
+        description: "Who is and was logged in.",
+        platforms: UNIX,
         schema: Arc::new(Schema::new(vec![
             Field::new("user", DataType::Utf8, true),
             Field::new("event", DataType::Utf8, true),
@@ -98,7 +337,10 @@ pub fn who_table_func() -> Arc<CommandTableFunc> {
 pub fn ls_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["ls", "-lah"],
-        jc_parser: "ls",
+        parser: Parser::Jc("ls"),
+        args: "[path]",
+        description: "Files in a directory.",
+        platforms: UNIX,
         schema: Arc::new(Schema::new(vec![
             Field::new("filename", DataType::Utf8, true),
             Field::new("flags", DataType::Utf8, true),
@@ -115,7 +357,10 @@ pub fn ls_table_func() -> Arc<CommandTableFunc> {
 pub fn stat_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["stat"],
-        jc_parser: "stat",
+        parser: Parser::Jc("stat"),
+        args: "path",
+        description: "Information about a file.",
+        platforms: &["linux", "darwin", "freebsd"],
         schema: Arc::new(Schema::new(vec![
             Field::new("file", DataType::Utf8, true),
             Field::new("unix_device", DataType::Int64, true),
@@ -149,7 +394,10 @@ pub fn stat_table_func() -> Arc<CommandTableFunc> {
 pub fn df_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["df", "-h"],
-        jc_parser: "df",
+        parser: Parser::Jc("df"),
+        args: "[path]",
+        description: "Free disk space per filesystem.",
+        platforms: &["linux", "darwin", "freebsd"],
         schema: Arc::new(Schema::new(vec![
             Field::new("filesystem", DataType::Utf8, true),
             Field::new("512_blocks", DataType::Int64, true),
@@ -167,8 +415,11 @@ pub fn df_table_func() -> Arc<CommandTableFunc> {
 
 pub fn du_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
-        command: vec!["du", "-h"],
-        jc_parser: "du",
+        command: vec!["sh", "-c", PARTIAL_OK, "du", "-h"],
+        parser: Parser::Jc("du"),
+        args: "[path]",
+        description: "Disk usage per directory.",
+        platforms: &["linux", "darwin", "aix", "freebsd"],
         schema: Arc::new(Schema::new(vec![
             Field::new("name", DataType::Utf8, true),
             Field::new("size", DataType::Int64, true),
@@ -180,8 +431,12 @@ pub fn du_table_func() -> Arc<CommandTableFunc> {
 //TODO: this might be broken because jc doesnt parse blkid right now?
 pub fn blkid_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
-        command: vec!["blkid"],
-        jc_parser: "blkid",
+        // blkid exits 2 when it finds no devices.
+        command: vec!["sh", "-c", r#"blkid "$@"; status=$?; [ $status -eq 2 ] || exit $status"#, "blkid"],
+        parser: Parser::Jc("blkid"),
+        args: "[device]",
+        description: "Block device attributes.",
+        platforms: &["linux"],
         schema: Arc::new(Schema::new(vec![
             Field::new("device", DataType::Utf8, true),
             Field::new("uuid", DataType::Utf8, true),
@@ -220,7 +475,10 @@ pub fn blkid_table_func() -> Arc<CommandTableFunc> {
 pub fn env_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["env"],
-        jc_parser: "env",
+        parser: Parser::Jc("env"),
+        args: "",
+        description: "Environment variables.",
+        platforms: &["linux", "darwin", "cygwin", "win32", "aix", "freebsd"],
         schema: Arc::new(Schema::new(vec![
             Field::new("name", DataType::Utf8, true),
             Field::new("value", DataType::Utf8, true),
@@ -232,7 +490,10 @@ pub fn env_table_func() -> Arc<CommandTableFunc> {
 pub fn date_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["date"],
-        jc_parser: "date",
+        parser: Parser::Jc("date"),
+        args: "",
+        description: "The current date and time.",
+        platforms: &["linux", "darwin", "freebsd"],
         schema: Arc::new(Schema::new(vec![
             Field::new("year", DataType::Int64, true),
             Field::new("month", DataType::Utf8, true),
@@ -261,7 +522,10 @@ pub fn date_table_func() -> Arc<CommandTableFunc> {
 pub fn dir_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["dir"],
-        jc_parser: "dir",
+        parser: Parser::Jc("dir"),
+        args: "[path]",
+        description: "Files in a directory.",
+        platforms: &["win32"],
         schema: Arc::new(Schema::new(vec![
             Field::new("date", DataType::Utf8, true),
             Field::new("time", DataType::Utf8, true),
@@ -277,8 +541,11 @@ pub fn dir_table_func() -> Arc<CommandTableFunc> {
 
 pub fn dpkg_list_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
-        command: vec!["dpkg", "-l"],
-        jc_parser: "dpkg-l",
+        command: vec!["sh", "-c", PARTIAL_OK, "dpkg", "-l"],
+        parser: Parser::Jc("dpkg-l"),
+        args: "[package]",
+        description: "Installed dpkg packages.",
+        platforms: &["linux"],
         schema: Arc::new(Schema::new(vec![
             Field::new("codes", DataType::Utf8, true),
             Field::new("name", DataType::Utf8, true),
@@ -296,7 +563,10 @@ pub fn dpkg_list_table_func() -> Arc<CommandTableFunc> {
 pub fn file_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["file"],
-        jc_parser: "file",
+        parser: Parser::Jc("file"),
+        args: "path",
+        description: "The type of a file.",
+        platforms: &["linux", "aix", "freebsd", "darwin"],
         schema: Arc::new(Schema::new(vec![
             Field::new("filename", DataType::Utf8, true),
             Field::new("type", DataType::Utf8, true),
@@ -307,8 +577,11 @@ pub fn file_table_func() -> Arc<CommandTableFunc> {
 
 pub fn find_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
-        command: vec!["find"],
-        jc_parser: "find",
+        command: vec!["sh", "-c", PARTIAL_OK, "find"],
+        parser: Parser::Jc("find"),
+        args: "[path]",
+        description: "Files and directories under a path.",
+        platforms: &["linux"],
         schema: Arc::new(Schema::new(vec![
             Field::new("path", DataType::Utf8, true),
             Field::new("node", DataType::Utf8, true),
@@ -321,7 +594,10 @@ pub fn find_table_func() -> Arc<CommandTableFunc> {
 pub fn free_table_func() -> Arc<CommandTableFunc> {
     Arc::new(CommandTableFunc {
         command: vec!["free"],
-        jc_parser: "free",
+        parser: Parser::Jc("free"),
+        args: "",
+        description: "Memory and swap usage.",
+        platforms: &["linux"],
         schema: Arc::new(Schema::new(vec![
             Field::new("type", DataType::Utf8, true),
             Field::new("total", DataType::Int64, true),
@@ -333,4 +609,1372 @@ pub fn free_table_func() -> Arc<CommandTableFunc> {
         ])),
         is_result_array: true,
     })
-}
\ No newline at end of file
+}
+
+pub fn ss_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["ss", "-tunapH"],
+        parser: Parser::Native(parse_ss),
+        args: "",
+        description: "TCP and UDP sockets and the process that owns each one.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("netid", DataType::Utf8, true),
+            Field::new("state", DataType::Utf8, true),
+            Field::new("recv_q", DataType::Int64, true),
+            Field::new("send_q", DataType::Int64, true),
+            Field::new("local_address", DataType::Utf8, true),
+            Field::new("local_port", DataType::Int64, true),
+            Field::new("interface", DataType::Utf8, true),
+            Field::new("peer_address", DataType::Utf8, true),
+            Field::new("peer_port", DataType::Int64, true),
+            Field::new("process", DataType::Utf8, true),
+            Field::new("pid", DataType::Int64, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn netstat_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["netstat", "-tunap"],
+        parser: Parser::Jc("netstat"),
+        args: "",
+        description: "TCP and UDP sockets and the program that owns each one, for hosts without ss.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("proto", DataType::Utf8, true),
+            Field::new("recv_q", DataType::Int64, true),
+            Field::new("send_q", DataType::Int64, true),
+            Field::new("local_address", DataType::Utf8, true),
+            Field::new("local_port", DataType::Utf8, true),
+            Field::new("local_port_num", DataType::Int64, true),
+            Field::new("foreign_address", DataType::Utf8, true),
+            Field::new("foreign_port", DataType::Utf8, true),
+            Field::new("foreign_port_num", DataType::Int64, true),
+            Field::new("state", DataType::Utf8, true),
+            Field::new("program_name", DataType::Utf8, true),
+            Field::new("pid", DataType::Int64, true),
+            Field::new("kind", DataType::Utf8, true),
+        ])),
+        is_result_array: true,
+    })
+}
+
+pub fn ip_addr_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["ip", "-j", "addr"],
+        // One row per address rather than per interface.
+        parser: Parser::Jq(
+            ".[] | . as $link | .addr_info[] | {ifindex: $link.ifindex, ifname: $link.ifname, \
+             operstate: $link.operstate, mtu: $link.mtu, mac: $link.address, family, \
+             address: .local, prefixlen, cidr: \"\\(.local)/\\(.prefixlen)\", broadcast, scope}",
+        ),
+        args: "",
+        description: "Addresses on each network interface.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("ifindex", DataType::Int64, true),
+            Field::new("ifname", DataType::Utf8, true),
+            Field::new("operstate", DataType::Utf8, true),
+            Field::new("mtu", DataType::Int64, true),
+            Field::new("mac", DataType::Utf8, true),
+            Field::new("family", DataType::Utf8, true),
+            Field::new("address", DataType::Utf8, true),
+            Field::new("prefixlen", DataType::Int64, true),
+            Field::new("cidr", DataType::Utf8, true),
+            Field::new("broadcast", DataType::Utf8, true),
+            Field::new("scope", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn ip_route_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["ip", "-j", "route"],
+        parser: Parser::Jq(".[]"),
+        args: "",
+        description: "The routing table.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("dst", DataType::Utf8, true),
+            Field::new("gateway", DataType::Utf8, true),
+            Field::new("dev", DataType::Utf8, true),
+            Field::new("protocol", DataType::Utf8, true),
+            Field::new("scope", DataType::Utf8, true),
+            Field::new("prefsrc", DataType::Utf8, true),
+            Field::new("metric", DataType::Int64, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn arp_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["arp", "-an"],
+        parser: Parser::Jc("arp"),
+        args: "",
+        description: "The ARP cache of neighbours' hardware addresses.",
+        platforms: &["linux", "darwin", "aix", "freebsd"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("address", DataType::Utf8, true),
+            Field::new("hwtype", DataType::Utf8, true),
+            Field::new("hwaddress", DataType::Utf8, true),
+            Field::new("iface", DataType::Utf8, true),
+            Field::new("permanent", DataType::Boolean, true),
+            Field::new("expires", DataType::Int64, true),
+        ])),
+        is_result_array: true,
+    })
+}
+
+pub fn ifconfig_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["ifconfig", "-a"],
+        parser: Parser::Jc("ifconfig"),
+        args: "",
+        description: "Network interfaces, their first addresses and traffic counters.",
+        platforms: &["linux", "aix", "freebsd", "darwin"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("type", DataType::Utf8, true),
+            Field::new("mtu", DataType::Int64, true),
+            Field::new("mac_addr", DataType::Utf8, true),
+            Field::new("ipv4_addr", DataType::Utf8, true),
+            Field::new("ipv4_mask", DataType::Utf8, true),
+            Field::new("ipv4_bcast", DataType::Utf8, true),
+            Field::new("ipv6_addr", DataType::Utf8, true),
+            Field::new("ipv6_mask", DataType::Int64, true),
+            Field::new("ipv6_scope", DataType::Utf8, true),
+            Field::new("rx_packets", DataType::Int64, true),
+            Field::new("rx_bytes", DataType::Int64, true),
+            Field::new("rx_errors", DataType::Int64, true),
+            Field::new("rx_dropped", DataType::Int64, true),
+            Field::new("tx_packets", DataType::Int64, true),
+            Field::new("tx_bytes", DataType::Int64, true),
+            Field::new("tx_errors", DataType::Int64, true),
+            Field::new("tx_dropped", DataType::Int64, true),
+        ])),
+        is_result_array: true,
+    })
+}
+
+pub fn systemctl_units_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["systemctl", "list-units", "--all", "--plain", "--no-pager"],
+        parser: Parser::Jc("systemctl"),
+        args: "",
+        description: "systemd units and whether they're loaded and running.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("unit", DataType::Utf8, true),
+            Field::new("load", DataType::Utf8, true),
+            Field::new("active", DataType::Utf8, true),
+            Field::new("sub", DataType::Utf8, true),
+            Field::new("description", DataType::Utf8, true),
+        ])),
+        is_result_array: true,
+    })
+}
+
+pub fn systemctl_show_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["systemctl", "show", "--no-pager"],
+        parser: Parser::Native(parse_systemctl_show),
+        args: "unit",
+        description: "The properties of a systemd unit, one row each.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("unit", DataType::Utf8, true),
+            Field::new("property", DataType::Utf8, true),
+            Field::new("value", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn systemctl_timers_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["systemctl", "list-timers", "--all", "--output=json", "--no-pager"],
+        // Times are microseconds since the epoch, or 0 for never.
+        parser: Parser::Jq(
+            ".[] | {unit, activates, next: (if .next > 0 then .next else null end), \
+             last: (if .last > 0 then .last else null end)}",
+        ),
+        args: "",
+        description: "systemd timers and when they last and next fire.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("unit", DataType::Utf8, true),
+            Field::new("activates", DataType::Utf8, true),
+            Field::new("next", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new("last", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn passwd_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["cat", "/etc/passwd"],
+        parser: Parser::Jc("passwd"),
+        args: "",
+        description: "Local user accounts from /etc/passwd.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("username", DataType::Utf8, true),
+            Field::new("password", DataType::Utf8, true),
+            Field::new("uid", DataType::Int64, true),
+            Field::new("gid", DataType::Int64, true),
+            Field::new("comment", DataType::Utf8, true),
+            Field::new("home", DataType::Utf8, true),
+            Field::new("shell", DataType::Utf8, true),
+        ])),
+        is_result_array: true,
+    })
+}
+
+pub fn group_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["cat", "/etc/group"],
+        parser: Parser::Native(parse_group),
+        args: "",
+        description: "Local groups from /etc/group, one row per member.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("group_name", DataType::Utf8, true),
+            Field::new("gid", DataType::Int64, true),
+            Field::new("member", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn shadow_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["cat", "/etc/shadow"],
+        parser: Parser::Native(parse_shadow),
+        args: "",
+        description: "Password status and aging from /etc/shadow, like chage -l. Needs root.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("username", DataType::Utf8, true),
+            Field::new("password_status", DataType::Utf8, true),
+            Field::new("last_changed", DataType::Date32, true),
+            Field::new("min_days", DataType::Int64, true),
+            Field::new("max_days", DataType::Int64, true),
+            Field::new("warn_days", DataType::Int64, true),
+            Field::new("inactive_days", DataType::Int64, true),
+            Field::new("password_expires", DataType::Date32, true),
+            Field::new("account_expires", DataType::Date32, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn sudoers_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec![
+            "sh", "-c", PARTIAL_OK, "find", "/etc/sudoers", "/etc/sudoers.d", "-type", "f", "-exec", "grep", "-H", "^", "{}", "+",
+        ],
+        parser: Parser::Native(parse_sudoers),
+        args: "",
+        description: "The rules in /etc/sudoers and /etc/sudoers.d. Needs root.",
+        platforms: &["linux", "darwin", "freebsd"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("file", DataType::Utf8, true),
+            Field::new("principal", DataType::Utf8, true),
+            Field::new("hosts", DataType::Utf8, true),
+            Field::new("runas", DataType::Utf8, true),
+            Field::new("tags", DataType::Utf8, true),
+            Field::new("commands", DataType::Utf8, true),
+            Field::new("line", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+fn last_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("user", DataType::Utf8, true),
+        Field::new("tty", DataType::Utf8, true),
+        Field::new("hostname", DataType::Utf8, true),
+        Field::new("login", DataType::Timestamp(TimeUnit::Second, None), true),
+        Field::new("logout", DataType::Timestamp(TimeUnit::Second, None), true),
+        Field::new("duration_seconds", DataType::Int64, true),
+        Field::new("status", DataType::Utf8, true),
+    ]))
+}
+
+pub fn last_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["env", "TZ=UTC", "last", "-F", "-w"],
+        parser: Parser::Native(parse_last),
+        args: "",
+        description: "Login history from wtmp, with login and logout times in UTC.",
+        platforms: &["linux"],
+        schema: last_schema(),
+        is_result_array: false,
+    })
+}
+
+pub fn lastb_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["env", "TZ=UTC", "lastb", "-F", "-w"],
+        parser: Parser::Native(parse_last),
+        args: "",
+        description: "Failed logins from btmp, with times in UTC. Needs root.",
+        platforms: &["linux"],
+        schema: last_schema(),
+        is_result_array: false,
+    })
+}
+
+pub fn id_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["id"],
+        parser: Parser::Native(parse_id),
+        args: "[user]",
+        description: "A user's ids and the groups they're in, one row per group.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("user", DataType::Utf8, true),
+            Field::new("uid", DataType::Int64, true),
+            Field::new("primary_gid", DataType::Int64, true),
+            Field::new("gid", DataType::Int64, true),
+            Field::new("group_name", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn rpm_qa_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["rpm", "-qa", "--qf", RPM_QUERY_FORMAT],
+        parser: Parser::Native(parse_rpm_qa),
+        args: "",
+        description: "Installed rpm packages.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("epoch", DataType::Int64, true),
+            Field::new("version", DataType::Utf8, true),
+            Field::new("release", DataType::Utf8, true),
+            Field::new("architecture", DataType::Utf8, true),
+            Field::new("installed", DataType::Timestamp(TimeUnit::Second, None), true),
+            Field::new("source_rpm", DataType::Utf8, true),
+            Field::new("summary", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn apk_list_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["apk", "list", "--installed"],
+        parser: Parser::Native(parse_apk_list),
+        args: "",
+        description: "Installed Alpine apk packages.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("version", DataType::Utf8, true),
+            Field::new("architecture", DataType::Utf8, true),
+            Field::new("origin", DataType::Utf8, true),
+            Field::new("license", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn pacman_q_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["pacman", "-Q"],
+        parser: Parser::Native(parse_pacman_q),
+        args: "",
+        description: "Installed pacman packages.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("version", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn pip_list_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["python3", "-m", "pip", "list", "--format=json"],
+        parser: Parser::Jq(".[]"),
+        args: "",
+        description: "Python packages installed for python3.",
+        platforms: &["linux", "darwin", "freebsd", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("version", DataType::Utf8, true),
+            Field::new("editable_project_location", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn cargo_install_list_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["cargo", "install", "--list"],
+        parser: Parser::Native(parse_cargo_install_list),
+        args: "",
+        description: "Crates installed with cargo install and the binaries they provide.",
+        platforms: &["linux", "darwin", "freebsd", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("version", DataType::Utf8, true),
+            Field::new("source", DataType::Utf8, true),
+            Field::new("binaries", string_list(), true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn packages_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", DETECT_PACKAGES],
+        parser: Parser::Native(parse_packages),
+        args: "",
+        description: "Installed packages from whichever of dpkg, rpm, apk or pacman the host has.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("version", DataType::Utf8, true),
+            Field::new("architecture", DataType::Utf8, true),
+            Field::new("source", DataType::Utf8, true),
+            Field::new("manager", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn docker_ps_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["docker", "ps", "--all", "--no-trunc", "--format", JSON_FORMAT],
+        parser: Parser::Native(parse_docker_ps),
+        args: "",
+        description: "Docker containers, running or not.",
+        platforms: &["linux", "darwin", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, true),
+            Field::new("names", DataType::Utf8, true),
+            Field::new("image", DataType::Utf8, true),
+            Field::new("command", DataType::Utf8, true),
+            Field::new("created_at", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new("state", DataType::Utf8, true),
+            Field::new("status", DataType::Utf8, true),
+            Field::new("ports", DataType::Utf8, true),
+            Field::new("labels", DataType::Utf8, true),
+            Field::new("networks", DataType::Utf8, true),
+            Field::new("mounts", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn docker_images_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["docker", "images", "--no-trunc", "--format", JSON_FORMAT],
+        parser: Parser::Native(parse_docker_images),
+        args: "",
+        description: "Docker images.",
+        platforms: &["linux", "darwin", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("repository", DataType::Utf8, true),
+            Field::new("tag", DataType::Utf8, true),
+            Field::new("id", DataType::Utf8, true),
+            Field::new("digest", DataType::Utf8, true),
+            Field::new("created_at", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new("size_bytes", DataType::Int64, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn docker_stats_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["docker", "stats", "--no-stream", "--no-trunc", "--format", JSON_FORMAT],
+        parser: Parser::Native(parse_docker_stats),
+        args: "",
+        description: "CPU, memory, network and disk use of running Docker containers.",
+        platforms: &["linux", "darwin", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("cpu_percent", DataType::Float64, true),
+            Field::new("mem_percent", DataType::Float64, true),
+            Field::new("mem_used_bytes", DataType::Int64, true),
+            Field::new("mem_limit_bytes", DataType::Int64, true),
+            Field::new("net_rx_bytes", DataType::Int64, true),
+            Field::new("net_tx_bytes", DataType::Int64, true),
+            Field::new("block_read_bytes", DataType::Int64, true),
+            Field::new("block_write_bytes", DataType::Int64, true),
+            Field::new("pids", DataType::Int64, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn docker_inspect_table_func() -> Arc<CommandTableFunc> {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, None);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", INSPECT_CONTAINERS, "docker_inspect"],
+        parser: Parser::Native(parse_docker_inspect),
+        args: "[container, ...]",
+        description: "Docker containers in detail, all of them or the ones named.",
+        platforms: &["linux", "darwin"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("image", DataType::Utf8, true),
+            Field::new("image_id", DataType::Utf8, true),
+            Field::new("created", timestamp.clone(), true),
+            Field::new("path", DataType::Utf8, true),
+            Field::new("args", string_list(), true),
+            Field::new("restart_count", DataType::Int64, true),
+            Field::new("status", DataType::Utf8, true),
+            Field::new("pid", DataType::Int64, true),
+            Field::new(
+                "state",
+                DataType::Struct(
+                    vec![
+                        Field::new("status", DataType::Utf8, true),
+                        Field::new("running", DataType::Boolean, true),
+                        Field::new("paused", DataType::Boolean, true),
+                        Field::new("restarting", DataType::Boolean, true),
+                        Field::new("oom_killed", DataType::Boolean, true),
+                        Field::new("dead", DataType::Boolean, true),
+                        Field::new("pid", DataType::Int64, true),
+                        Field::new("exit_code", DataType::Int64, true),
+                        Field::new("error", DataType::Utf8, true),
+                        Field::new("started_at", timestamp.clone(), true),
+                        Field::new("finished_at", timestamp, true),
+                    ]
+                    .into(),
+                ),
+                true,
+            ),
+            Field::new("env", string_list(), true),
+            Field::new("labels", string_map(), true),
+            Field::new(
+                "mounts",
+                struct_list(vec![
+                    Field::new("type", DataType::Utf8, true),
+                    Field::new("name", DataType::Utf8, true),
+                    Field::new("source", DataType::Utf8, true),
+                    Field::new("destination", DataType::Utf8, true),
+                    Field::new("mode", DataType::Utf8, true),
+                    Field::new("rw", DataType::Boolean, true),
+                ]),
+                true,
+            ),
+            Field::new(
+                "ports",
+                struct_list(vec![
+                    Field::new("container_port", DataType::Int64, true),
+                    Field::new("protocol", DataType::Utf8, true),
+                    Field::new("host_ip", DataType::Utf8, true),
+                    Field::new("host_port", DataType::Int64, true),
+                ]),
+                true,
+            ),
+            Field::new(
+                "networks",
+                struct_list(vec![
+                    Field::new("name", DataType::Utf8, true),
+                    Field::new("ip_address", DataType::Utf8, true),
+                    Field::new("gateway", DataType::Utf8, true),
+                    Field::new("mac_address", DataType::Utf8, true),
+                ]),
+                true,
+            ),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn k8s_pods_table_func() -> Arc<CommandTableFunc> {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, None);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", KUBECTL_GET, "pods"],
+        parser: Parser::Native(parse_pods),
+        args: "[context], [namespace]",
+        description: "Kubernetes pods, in every namespace unless one is given.",
+        platforms: &["linux", "darwin", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("namespace", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("uid", DataType::Utf8, true),
+            Field::new("created", timestamp.clone(), true),
+            Field::new("labels", string_map(), true),
+            Field::new("annotations", string_map(), true),
+            Field::new("node", DataType::Utf8, true),
+            Field::new("phase", DataType::Utf8, true),
+            Field::new("pod_ip", DataType::Utf8, true),
+            Field::new("host_ip", DataType::Utf8, true),
+            Field::new("qos_class", DataType::Utf8, true),
+            Field::new("start_time", timestamp.clone(), true),
+            Field::new("ready_containers", DataType::Int64, true),
+            Field::new("total_containers", DataType::Int64, true),
+            Field::new("restarts", DataType::Int64, true),
+            Field::new("owner_kind", DataType::Utf8, true),
+            Field::new("owner_name", DataType::Utf8, true),
+            Field::new(
+                "containers",
+                struct_list(vec![
+                    Field::new("name", DataType::Utf8, true),
+                    Field::new("image", DataType::Utf8, true),
+                    Field::new("ready", DataType::Boolean, true),
+                    Field::new("restart_count", DataType::Int64, true),
+                    Field::new("state", DataType::Utf8, true),
+                    Field::new("reason", DataType::Utf8, true),
+                    Field::new("started_at", timestamp, true),
+                    Field::new("exit_code", DataType::Int64, true),
+                ]),
+                true,
+            ),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn k8s_nodes_table_func() -> Arc<CommandTableFunc> {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, None);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", KUBECTL_GET, "nodes"],
+        parser: Parser::Native(parse_nodes),
+        args: "[context]",
+        description: "Kubernetes nodes, their capacity and conditions.",
+        platforms: &["linux", "darwin", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("uid", DataType::Utf8, true),
+            Field::new("created", timestamp.clone(), true),
+            Field::new("labels", string_map(), true),
+            Field::new("annotations", string_map(), true),
+            Field::new("ready", DataType::Boolean, true),
+            Field::new("unschedulable", DataType::Boolean, true),
+            Field::new("roles", DataType::Utf8, true),
+            Field::new("internal_ip", DataType::Utf8, true),
+            Field::new("kubelet_version", DataType::Utf8, true),
+            Field::new("os_image", DataType::Utf8, true),
+            Field::new("kernel_version", DataType::Utf8, true),
+            Field::new("container_runtime", DataType::Utf8, true),
+            Field::new("cpu_capacity_millicores", DataType::Int64, true),
+            Field::new("memory_capacity_bytes", DataType::Int64, true),
+            Field::new("pods_capacity", DataType::Int64, true),
+            Field::new("cpu_allocatable_millicores", DataType::Int64, true),
+            Field::new("memory_allocatable_bytes", DataType::Int64, true),
+            Field::new(
+                "conditions",
+                struct_list(vec![
+                    Field::new("type", DataType::Utf8, true),
+                    Field::new("status", DataType::Utf8, true),
+                    Field::new("reason", DataType::Utf8, true),
+                    Field::new("message", DataType::Utf8, true),
+                    Field::new("last_transition", timestamp, true),
+                ]),
+                true,
+            ),
+            Field::new(
+                "taints",
+                struct_list(vec![
+                    Field::new("key", DataType::Utf8, true),
+                    Field::new("value", DataType::Utf8, true),
+                    Field::new("effect", DataType::Utf8, true),
+                ]),
+                true,
+            ),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn k8s_events_table_func() -> Arc<CommandTableFunc> {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, None);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", KUBECTL_GET, "events"],
+        parser: Parser::Native(parse_events),
+        args: "[context], [namespace]",
+        description: "Kubernetes events, in every namespace unless one is given.",
+        platforms: &["linux", "darwin", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("namespace", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("type", DataType::Utf8, true),
+            Field::new("reason", DataType::Utf8, true),
+            Field::new("message", DataType::Utf8, true),
+            Field::new("object_kind", DataType::Utf8, true),
+            Field::new("object_name", DataType::Utf8, true),
+            Field::new("object_namespace", DataType::Utf8, true),
+            Field::new("count", DataType::Int64, true),
+            Field::new("first_seen", timestamp.clone(), true),
+            Field::new("last_seen", timestamp, true),
+            Field::new("source", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn k8s_top_pods_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", KUBECTL_TOP, "pods"],
+        parser: Parser::Native(parse_top_pods),
+        args: "[context], [namespace]",
+        description: "CPU and memory use of Kubernetes pods, from metrics-server.",
+        platforms: &["linux", "darwin", "windows"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("namespace", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("cpu_millicores", DataType::Int64, true),
+            Field::new("memory_bytes", DataType::Int64, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn git_log_table_func() -> Arc<CommandTableFunc> {
+    let timestamp = DataType::Timestamp(TimeUnit::Second, None);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", GIT_LOG, "git_log"],
+        parser: Parser::Native(parse_git_log),
+        args: "[path]",
+        description: "Commits of a git repository, with how many files and lines each changed.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("commit", DataType::Utf8, true),
+            Field::new("parents", string_list(), true),
+            Field::new("author_name", DataType::Utf8, true),
+            Field::new("author_email", DataType::Utf8, true),
+            Field::new("author_time", timestamp.clone(), true),
+            Field::new("committer_name", DataType::Utf8, true),
+            Field::new("committer_email", DataType::Utf8, true),
+            Field::new("commit_time", timestamp, true),
+            Field::new("refs", string_list(), true),
+            Field::new("subject", DataType::Utf8, true),
+            Field::new("files_changed", DataType::Int64, true),
+            Field::new("insertions", DataType::Int64, true),
+            Field::new("deletions", DataType::Int64, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn git_branches_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", GIT_BRANCHES, "git_branches"],
+        parser: Parser::Native(parse_git_branches),
+        args: "[path]",
+        description: "Local and remote-tracking branches of a git repository.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("remote", DataType::Boolean, true),
+            Field::new("current", DataType::Boolean, true),
+            Field::new("commit", DataType::Utf8, true),
+            Field::new("upstream", DataType::Utf8, true),
+            Field::new("upstream_gone", DataType::Boolean, true),
+            Field::new("ahead", DataType::Int64, true),
+            Field::new("behind", DataType::Int64, true),
+            Field::new("commit_time", DataType::Timestamp(TimeUnit::Second, None), true),
+            Field::new("subject", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn git_status_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", GIT_STATUS, "git_status"],
+        parser: Parser::Native(parse_git_status),
+        args: "[path]",
+        description: "Changed and untracked files in a git work tree.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("path", DataType::Utf8, true),
+            Field::new("orig_path", DataType::Utf8, true),
+            Field::new("staged", DataType::Utf8, true),
+            Field::new("unstaged", DataType::Utf8, true),
+            Field::new("kind", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn git_blame_table_func() -> Arc<CommandTableFunc> {
+    let timestamp = DataType::Timestamp(TimeUnit::Second, None);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", GIT_BLAME, "git_blame"],
+        parser: Parser::Native(parse_git_blame),
+        args: "path, file",
+        description: "The commit that last changed each line of a file.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("line_number", DataType::Int64, true),
+            Field::new("line", DataType::Utf8, true),
+            Field::new("commit", DataType::Utf8, true),
+            Field::new("original_line", DataType::Int64, true),
+            Field::new("filename", DataType::Utf8, true),
+            Field::new("author_name", DataType::Utf8, true),
+            Field::new("author_email", DataType::Utf8, true),
+            Field::new("author_time", timestamp.clone(), true),
+            Field::new("committer_name", DataType::Utf8, true),
+            Field::new("committer_email", DataType::Utf8, true),
+            Field::new("commit_time", timestamp, true),
+            Field::new("summary", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn git_diff_stat_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", GIT_DIFF_STAT, "git_diff_stat"],
+        parser: Parser::Native(parse_git_diff_stat),
+        args: "[path], [rev_range]",
+        description: "Lines added and removed per file over a revision range, or in the work tree.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(vec![
+            Field::new("path", DataType::Utf8, true),
+            Field::new("old_path", DataType::Utf8, true),
+            Field::new("insertions", DataType::Int64, true),
+            Field::new("deletions", DataType::Int64, true),
+            Field::new("binary", DataType::Boolean, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn lsblk_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["lsblk", "-J", "-b", "-o", LSBLK_COLUMNS],
+        parser: Parser::Native(parse_lsblk),
+        args: "[device]",
+        description: "Block devices, with the device each partition or volume is on as its parent.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("kname", DataType::Utf8, true),
+            Field::new("parent", DataType::Utf8, true),
+            Field::new("type", DataType::Utf8, true),
+            Field::new("size_bytes", DataType::Int64, true),
+            Field::new("fstype", DataType::Utf8, true),
+            Field::new("mountpoint", DataType::Utf8, true),
+            Field::new("label", DataType::Utf8, true),
+            Field::new("uuid", DataType::Utf8, true),
+            Field::new("model", DataType::Utf8, true),
+            Field::new("serial", DataType::Utf8, true),
+            Field::new("read_only", DataType::Boolean, true),
+            Field::new("removable", DataType::Boolean, true),
+            Field::new("rotational", DataType::Boolean, true),
+            Field::new("transport", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn lsmod_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["lsmod"],
+        parser: Parser::Native(parse_lsmod),
+        args: "",
+        description: "Loaded kernel modules.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("module", DataType::Utf8, true),
+            Field::new("size_bytes", DataType::Int64, true),
+            Field::new("used", DataType::Int64, true),
+            Field::new("used_by", string_list(), true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn lscpu_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["env", "LC_ALL=C", "lscpu"],
+        parser: Parser::Native(parse_lscpu),
+        args: "",
+        description: "The CPU's model, topology, feature flags and known vulnerabilities.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("architecture", DataType::Utf8, true),
+            Field::new("vendor", DataType::Utf8, true),
+            Field::new("model_name", DataType::Utf8, true),
+            Field::new("cpus", DataType::Int64, true),
+            Field::new("threads_per_core", DataType::Int64, true),
+            Field::new("cores_per_socket", DataType::Int64, true),
+            Field::new("sockets", DataType::Int64, true),
+            Field::new("numa_nodes", DataType::Int64, true),
+            Field::new("max_mhz", DataType::Float64, true),
+            Field::new("min_mhz", DataType::Float64, true),
+            Field::new("bogomips", DataType::Float64, true),
+            Field::new("virtualization", DataType::Utf8, true),
+            Field::new("hypervisor_vendor", DataType::Utf8, true),
+            Field::new("flags", string_list(), true),
+            Field::new("vulnerabilities", string_map(), true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn lspci_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["lspci", "-vmm", "-nn", "-k", "-D"],
+        parser: Parser::Native(parse_lspci),
+        args: "",
+        description: "PCI devices and the drivers bound to them.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("slot", DataType::Utf8, true),
+            Field::new("class", DataType::Utf8, true),
+            Field::new("class_id", DataType::Utf8, true),
+            Field::new("vendor", DataType::Utf8, true),
+            Field::new("vendor_id", DataType::Utf8, true),
+            Field::new("device", DataType::Utf8, true),
+            Field::new("device_id", DataType::Utf8, true),
+            Field::new("subsystem_vendor", DataType::Utf8, true),
+            Field::new("subsystem_device", DataType::Utf8, true),
+            Field::new("revision", DataType::Utf8, true),
+            Field::new("driver", DataType::Utf8, true),
+            Field::new("module", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn lsusb_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["lsusb"],
+        parser: Parser::Native(parse_lsusb),
+        args: "",
+        description: "USB devices.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("bus", DataType::Int64, true),
+            Field::new("device", DataType::Int64, true),
+            Field::new("vendor_id", DataType::Utf8, true),
+            Field::new("product_id", DataType::Utf8, true),
+            Field::new("description", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn dmidecode_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["dmidecode"],
+        parser: Parser::Native(parse_dmidecode),
+        args: "",
+        description: "The firmware's hardware description, like the system's serial number. Needs root.",
+        platforms: &["linux", "freebsd"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("handle", DataType::Utf8, true),
+            Field::new("type", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("properties", string_map(), true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn sysctl_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sysctl", "-a"],
+        parser: Parser::Native(parse_sysctl),
+        args: "",
+        description: "Kernel parameters.",
+        platforms: &["linux", "darwin", "freebsd"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("key", DataType::Utf8, true),
+            Field::new("value", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn mounts_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["cat", "/proc/self/mountinfo"],
+        parser: Parser::Native(parse_mountinfo),
+        args: "",
+        description: "Mounted filesystems and their options.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("mount_id", DataType::Int64, true),
+            Field::new("parent_id", DataType::Int64, true),
+            Field::new("major", DataType::Int64, true),
+            Field::new("minor", DataType::Int64, true),
+            Field::new("root", DataType::Utf8, true),
+            Field::new("mount_point", DataType::Utf8, true),
+            Field::new("mount_options", DataType::Utf8, true),
+            Field::new("propagation", string_list(), true),
+            Field::new("fstype", DataType::Utf8, true),
+            Field::new("source", DataType::Utf8, true),
+            Field::new("super_options", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+/// The columns the crontab tables share, with each schedule split into its fields.
+/// Macros like `@daily` are expanded into them, except `@reboot`.
+fn cron_fields() -> Vec<Field> {
+    vec![
+        Field::new("user", DataType::Utf8, true),
+        Field::new("line_number", DataType::Int64, true),
+        Field::new("schedule", DataType::Utf8, true),
+        Field::new("minute", DataType::Utf8, true),
+        Field::new("hour", DataType::Utf8, true),
+        Field::new("day_of_month", DataType::Utf8, true),
+        Field::new("month", DataType::Utf8, true),
+        Field::new("day_of_week", DataType::Utf8, true),
+        Field::new("command", DataType::Utf8, true),
+    ]
+}
+
+pub fn crontab_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", CRONTAB, "crontab"],
+        parser: Parser::Native(parse_crontab),
+        args: "[user]",
+        description: "A user's crontab, the current user's if none is given.",
+        platforms: UNIX,
+        schema: Arc::new(Schema::new(cron_fields())),
+        is_result_array: false,
+    })
+}
+
+pub fn cron_files_table_func() -> Arc<CommandTableFunc> {
+    let mut fields = vec![Field::new("file", DataType::Utf8, true)];
+    fields.extend(cron_fields());
+    Arc::new(CommandTableFunc {
+        command: vec![
+            "sh", "-c", PARTIAL_OK, "find", "/etc/crontab", "/etc/cron.d", "/var/spool/cron", "-type", "f", "-exec", "grep", "-Hn",
+            "^", "{}", "+",
+        ],
+        parser: Parser::Native(parse_cron_files),
+        args: "",
+        description: "Jobs in /etc/crontab, /etc/cron.d and every user's crontab. Needs root for the last.",
+        platforms: &["linux", "freebsd"],
+        schema: Arc::new(Schema::new(fields)),
+        is_result_array: false,
+    })
+}
+
+/// A table that samples for `interval` seconds, `count` times, starting with the time
+/// each sample was taken.
+fn sampled_schema(fields: Vec<Field>) -> Arc<Schema> {
+    let mut schema = vec![Field::new("sample_ts", DataType::Timestamp(TimeUnit::Second, None), true)];
+    schema.extend(fields);
+    Arc::new(Schema::new(schema))
+}
+
+pub fn vmstat_table_func() -> Arc<CommandTableFunc> {
+    let field = |name: &str| Field::new(name, DataType::Int64, true);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", VMSTAT, "vmstat"],
+        parser: Parser::Native(parse_vmstat),
+        args: "[interval], [count]",
+        description: "Memory, swap, IO and CPU activity, sampled every interval seconds, 1 by default, count times, 5 by default.",
+        platforms: &["linux"],
+        schema: sampled_schema(vec![
+            field("runnable"),
+            field("blocked"),
+            field("swap_used_kb"),
+            field("free_kb"),
+            field("buffers_kb"),
+            field("cache_kb"),
+            field("swap_in_kb_per_sec"),
+            field("swap_out_kb_per_sec"),
+            field("blocks_in_per_sec"),
+            field("blocks_out_per_sec"),
+            field("interrupts_per_sec"),
+            field("context_switches_per_sec"),
+            field("user_percent"),
+            field("system_percent"),
+            field("idle_percent"),
+            field("iowait_percent"),
+            field("steal_percent"),
+            field("guest_percent"),
+        ]),
+        is_result_array: false,
+    })
+}
+
+pub fn iostat_table_func() -> Arc<CommandTableFunc> {
+    let field = |name: &str| Field::new(name, DataType::Float64, true);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", IOSTAT, "iostat"],
+        parser: Parser::Native(parse_iostat),
+        args: "[interval], [count]",
+        description: "Disk IO per device, sampled like vmstat. Needs sysstat.",
+        platforms: &["linux"],
+        schema: sampled_schema(vec![
+            Field::new("device", DataType::Utf8, true),
+            field("reads_per_sec"),
+            field("writes_per_sec"),
+            field("read_kb_per_sec"),
+            field("write_kb_per_sec"),
+            field("read_merges_per_sec"),
+            field("write_merges_per_sec"),
+            field("read_await_ms"),
+            field("write_await_ms"),
+            field("queue_size"),
+            field("util_percent"),
+        ]),
+        is_result_array: false,
+    })
+}
+
+pub fn mpstat_table_func() -> Arc<CommandTableFunc> {
+    let field = |name: &str| Field::new(name, DataType::Float64, true);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", MPSTAT, "mpstat"],
+        parser: Parser::Native(parse_mpstat),
+        args: "[interval], [count]",
+        description: "CPU use per CPU, and for all of them, sampled like vmstat. Needs sysstat.",
+        platforms: &["linux"],
+        schema: sampled_schema(vec![
+            Field::new("cpu", DataType::Utf8, true),
+            field("usr_percent"),
+            field("nice_percent"),
+            field("sys_percent"),
+            field("iowait_percent"),
+            field("irq_percent"),
+            field("soft_percent"),
+            field("steal_percent"),
+            field("guest_percent"),
+            field("gnice_percent"),
+            field("idle_percent"),
+        ]),
+        is_result_array: false,
+    })
+}
+
+pub fn pidstat_table_func() -> Arc<CommandTableFunc> {
+    let field = |name: &str| Field::new(name, DataType::Float64, true);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PIDSTAT, "pidstat"],
+        parser: Parser::Native(parse_pidstat),
+        args: "[interval], [count]",
+        description: "CPU and memory use per process over each interval, sampled like vmstat. Needs sysstat.",
+        platforms: &["linux"],
+        schema: sampled_schema(vec![
+            Field::new("uid", DataType::Int64, true),
+            Field::new("pid", DataType::Int64, true),
+            field("user_percent"),
+            field("system_percent"),
+            field("guest_percent"),
+            field("wait_percent"),
+            field("cpu_percent"),
+            Field::new("cpu", DataType::Int64, true),
+            field("minor_faults_per_sec"),
+            field("major_faults_per_sec"),
+            Field::new("vsz_kb", DataType::Int64, true),
+            Field::new("rss_kb", DataType::Int64, true),
+            field("mem_percent"),
+            Field::new("command", DataType::Utf8, true),
+        ]),
+        is_result_array: false,
+    })
+}
+
+pub fn top_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", TOP, "top"],
+        parser: Parser::Native(parse_top),
+        args: "[interval], [count]",
+        description: "Processes as top shows them, sampled like vmstat.",
+        platforms: &["linux"],
+        schema: sampled_schema(vec![
+            Field::new("pid", DataType::Int64, true),
+            Field::new("user", DataType::Utf8, true),
+            Field::new("priority", DataType::Utf8, true),
+            Field::new("nice", DataType::Int64, true),
+            Field::new("virt_kb", DataType::Int64, true),
+            Field::new("res_kb", DataType::Int64, true),
+            Field::new("shr_kb", DataType::Int64, true),
+            Field::new("state", DataType::Utf8, true),
+            Field::new("cpu_percent", DataType::Float64, true),
+            Field::new("mem_percent", DataType::Float64, true),
+            Field::new("time", DataType::Utf8, true),
+            Field::new("command", DataType::Utf8, true),
+        ]),
+        is_result_array: false,
+    })
+}
+
+pub fn lsof_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", LSOF, "lsof"],
+        parser: Parser::Native(parse_lsof),
+        args: "[pid]",
+        description: "Open files, sockets and pipes, of one process or all of them. Other users' need root.",
+        platforms: &["linux", "darwin", "freebsd"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("pid", DataType::Int64, true),
+            Field::new("command", DataType::Utf8, true),
+            Field::new("uid", DataType::Int64, true),
+            Field::new("user", DataType::Utf8, true),
+            Field::new("fd", DataType::Utf8, true),
+            Field::new("access", DataType::Utf8, true),
+            Field::new("type", DataType::Utf8, true),
+            Field::new("size", DataType::Int64, true),
+            Field::new("inode", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn proc_status_table_func() -> Arc<CommandTableFunc> {
+    let field = |name: &str| Field::new(name, DataType::Int64, true);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PROC_FILE, "proc_status"],
+        parser: Parser::Native(parse_proc_status),
+        args: "[pid]",
+        description: "Each process's state, ids, memory and context switches from /proc/<pid>/status, of one process or all of them.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            field("pid"),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("state", DataType::Utf8, true),
+            field("ppid"),
+            field("tgid"),
+            field("uid"),
+            field("euid"),
+            field("gid"),
+            field("egid"),
+            field("threads"),
+            field("fd_size"),
+            field("vm_peak_kb"),
+            field("vm_size_kb"),
+            field("vm_rss_kb"),
+            field("vm_swap_kb"),
+            field("voluntary_ctxt_switches"),
+            field("nonvoluntary_ctxt_switches"),
+            Field::new("umask", DataType::Utf8, true),
+            Field::new("cap_eff", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn proc_cmdline_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PROC_STRINGS, "proc_cmdline"],
+        parser: Parser::Native(parse_proc_cmdline),
+        args: "[pid]",
+        description: "Each process's arguments, unlike ps's, which are truncated and can't tell spaces in an argument from between them.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("pid", DataType::Int64, true),
+            Field::new("command", DataType::Utf8, true),
+            Field::new("argv", string_list(), true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn proc_environ_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PROC_STRINGS, "proc_environ"],
+        parser: Parser::Native(parse_proc_environ),
+        args: "[pid]",
+        description: "The environment each process started with, one row per variable. Other users' need root.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("pid", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("value", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn proc_limits_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PROC_FILE, "proc_limits"],
+        parser: Parser::Native(parse_proc_limits),
+        args: "[pid]",
+        description: "Each process's resource limits, with null for unlimited.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("pid", DataType::Int64, true),
+            Field::new("limit", DataType::Utf8, true),
+            Field::new("soft", DataType::Int64, true),
+            Field::new("hard", DataType::Int64, true),
+            Field::new("units", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn proc_fds_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PROC_FDS, "proc_fds"],
+        parser: Parser::Native(parse_proc_fds),
+        args: "[pid]",
+        description: "Each process's open file descriptors and what they point to. Other users' need root.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("pid", DataType::Int64, true),
+            Field::new("fd", DataType::Int64, true),
+            Field::new("type", DataType::Utf8, true),
+            Field::new("inode", DataType::Int64, true),
+            Field::new("target", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn proc_maps_table_func() -> Arc<CommandTableFunc> {
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PROC_FILE, "proc_maps"],
+        parser: Parser::Native(parse_proc_maps),
+        args: "[pid]",
+        description: "Each process's memory mappings, like the libraries it has loaded. Other users' need root.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            Field::new("pid", DataType::Int64, true),
+            Field::new("start", DataType::Utf8, true),
+            Field::new("end", DataType::Utf8, true),
+            Field::new("size_bytes", DataType::Int64, true),
+            Field::new("perms", DataType::Utf8, true),
+            Field::new("offset", DataType::Int64, true),
+            Field::new("device", DataType::Utf8, true),
+            Field::new("inode", DataType::Int64, true),
+            Field::new("path", DataType::Utf8, true),
+        ])),
+        is_result_array: false,
+    })
+}
+
+pub fn proc_io_table_func() -> Arc<CommandTableFunc> {
+    let field = |name: &str| Field::new(name, DataType::Int64, true);
+    Arc::new(CommandTableFunc {
+        command: vec!["sh", "-c", PROC_FILE, "proc_io"],
+        parser: Parser::Native(parse_proc_io),
+        args: "[pid]",
+        description: "Bytes and system calls each process has read and written. Other users' need root.",
+        platforms: &["linux"],
+        schema: Arc::new(Schema::new(vec![
+            field("pid"),
+            field("rchar"),
+            field("wchar"),
+            field("syscr"),
+            field("syscw"),
+            field("read_bytes"),
+            field("write_bytes"),
+            field("cancelled_write_bytes"),
+        ])),
+        is_result_array: false,
+    })
+}
@@ -1,185 +1,302 @@
 use async_trait::async_trait;
-use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::array::{ArrayRef, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use datafusion::arrow::json::ReaderBuilder;
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::catalog::Session;
 use datafusion::datasource::function::TableFunctionImpl;
 use datafusion::datasource::TableProvider;
 use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::SessionConfig;
 use datafusion::physical_plan::memory::MemoryExec;
 use datafusion::physical_plan::ExecutionPlan;
+use datafusion::scalar::ScalarValue;
 use datafusion_expr::{Expr, TableType};
-use ssh2_config::{ParseRule, SshConfig};
-use std::fs::File;
-use std::io::BufReader;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::io::Cursor;
-use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
 
-/// Generic CommandTable that runs a command, pipes its output into `jc`, and provides the data as RecordBatches.
+use crate::error::ZqueryError;
+use crate::remote::Remote;
+use crate::tail::shell_quote;
+
+/// Set on the session by `--strict`, so a `hosts()` fan-out fails if any host does
+/// instead of leaving that host's rows out.
+#[derive(Debug)]
+pub struct StrictFanOut;
+
+fn is_strict(config: &SessionConfig) -> bool {
+    config.get_extension::<StrictFanOut>().is_some()
+}
+
+/// How a command's output becomes rows.
+#[derive(Debug, Clone, Copy)]
+pub enum Parser {
+    /// Piped through `jc --<name>`.
+    Jc(&'static str),
+    /// The command prints JSON itself, like `ip -j addr`, and this `jq` filter turns it
+    /// into one object per row.
+    Jq(&'static str),
+    /// Parsed in zquery, for output `jc` has no parser for or drops things from.
+    Native(fn(&str) -> Vec<Value>),
+}
+
+impl std::fmt::Display for Parser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Parser::Jc(name) => write!(f, "jc --{}", name),
+            Parser::Jq(filter) => write!(f, "jq '{}'", filter),
+            Parser::Native(_) => write!(f, "built in"),
+        }
+    }
+}
+
+impl Parser {
+    /// Turns the command's output into newline delimited JSON, one object per row.
+    fn parse(&self, output: &str, is_result_array: bool) -> std::result::Result<String, ZqueryError> {
+        match self {
+            Parser::Jc(name) => {
+                let json = jc(name, output)?;
+                if is_result_array {
+                    jq(".[]", &json)
+                } else {
+                    Ok(json)
+                }
+            }
+            Parser::Jq(filter) => jq(filter, output),
+            Parser::Native(parse) => Ok(parse(output).iter().map(|row| format!("{}\n", row)).collect()),
+        }
+    }
+}
+
+/// Generic CommandTable that runs a command, parses its output (usually with `jc`), and provides the data as RecordBatches.
 
 #[derive(Debug, Clone)]
 pub struct CommandTable {
     command: Vec<String>,
-    jc_parser: String,
+    parser: Parser,
     schema: SchemaRef,
     is_result_array: bool,
     hostname: String,
     args: Vec<String>,
+    /// The pattern from `hosts('prod-*')`, in which case the schema starts with a `host`
+    /// column. It's matched against the inventory when the table runs.
+    fan_out: Option<String>,
 }
 
 impl CommandTable {
-    fn run_command_remotely(
-        host: &str,
-        command: &[String],
-        args: &[String],
-        jc_parser: &str,
-    ) -> std::io::Result<String> {
-
-        let home = std::env::var("HOME").unwrap();
-
-        let mut reader = BufReader::new(
-            File::open(Path::new(&format!("{}/.ssh/config", home)))
-                .expect("Could not open configuration file"),
-        );
-
-        let config = SshConfig::default()
-            .parse(&mut reader, ParseRule::STRICT)
-            .expect("Failed to parse configuration");
-
-        // Query parameters for your host
-        // If there's no rule for your host, default params are returned
-        let params = config.query(host);
-
-        let mut session = ssh2::Session::new().expect("Failed to create session");
-
-        let hostname = params.host_name.unwrap();
-        let port = params.port.unwrap_or(22);
-        let user = params.user.unwrap_or("root".to_string());
-        let tcp = TcpStream::connect(format!("{}:{}", hostname, port)).unwrap();
-
-        session.set_tcp_stream(tcp);
-        session.handshake().unwrap();
-        // resolve the home directory
-        session
-            .userauth_pubkey_file(&user, None, Path::new(&format!("{}/.ssh/{}", home, "id_rsa")), None)
-            .unwrap();
-
-        let mut channel = session.channel_session().unwrap();
-        //create the command string
-        let mut command_string = command[0].clone();
-        for arg in &command[1..] {
-            command_string.push_str(&format!(" {}", arg));
-        }
-        for arg in args {
-            command_string.push_str(&format!(" {}", arg));
+    /// Runs the command once, locally or over ssh, and parses the output into RecordBatches.
+    pub fn run(&self) -> Result<Vec<RecordBatch>> {
+        self.run_with(&Remote::default(), false)
+    }
+
+    /// Runs the command with the session's transport, inventory and `--strict`.
+    pub fn run_in(&self, config: &SessionConfig) -> Result<Vec<RecordBatch>> {
+        self.run_with(&Remote::from_config(config), is_strict(config))
+    }
+
+    /// Like `run`, but reaching hosts through `remote`, and with `strict` a fan-out
+    /// fails if any of its hosts did.
+    pub fn run_with(&self, remote: &Remote, strict: bool) -> Result<Vec<RecordBatch>> {
+        if let Some(pattern) = &self.fan_out {
+            return self.run_on_hosts(remote, &remote.hosts_matching(pattern)?, strict);
         }
-        channel.exec(&command_string).unwrap();
 
-        // sleep for 3 seconds
-        std::thread::sleep(std::time::Duration::from_secs(3));
-        let mut s = String::new();
+        // Run the command and parse the output
+        let output = if self.hostname == "localhost" {
+            Self::run_command_locally(&self.command, &self.args)?
+        } else {
+            Self::run_command_remotely(remote, &self.hostname, &self.command, &self.args)?
+        };
+        let output = self.parser.parse(&output, self.is_result_array)?;
 
-        loop {
-            let mut buffer = [0; 1024]; // Buffer to hold incoming data
-            let n = channel.read(&mut buffer).unwrap_or(0);
+        let cursor = Cursor::new(output);
+        let reader = ReaderBuilder::new(self.schema.clone())
+            .build(cursor)
+            .map_err(|e| ZqueryError::Parse(format!("Failed to build JSON reader: {}", e)))?;
+        let batches = reader
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| ZqueryError::Parse(format!("Failed to read batches: {}", e)))?;
+        Ok(batches)
+    }
 
-            if n == 0 {
-                break;
-            }
+    /// Runs the command on every host at once and tags each row with its host. Hosts
+    /// that fail are reported and left out, unless every one of them failed or `strict`
+    /// is set.
+    fn run_on_hosts(&self, remote: &Remote, hosts: &[String], strict: bool) -> Result<Vec<RecordBatch>> {
+        let single = CommandTable {
+            schema: Arc::new(Schema::new(self.schema.fields()[1..].to_vec())),
+            fan_out: None,
+            ..self.clone()
+        };
+        let results: Vec<Result<Vec<RecordBatch>>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = hosts
+                .iter()
+                .map(|host| {
+                    let table = CommandTable {
+                        hostname: host.clone(),
+                        ..single.clone()
+                    };
+                    scope.spawn(move || table.run_with(remote, strict))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle.join().unwrap_or_else(|_| {
+                        Err(DataFusionError::Execution("Command panicked".to_string()))
+                    })
+                })
+                .collect()
+        });
 
-            s.push_str(&String::from_utf8_lossy(&buffer[..n]));
+        let mut batches = Vec::new();
+        let mut failed = Vec::new();
+        for (host, result) in hosts.iter().zip(results) {
+            match result {
+                Ok(host_batches) => {
+                    for batch in host_batches {
+                        let mut columns: Vec<ArrayRef> =
+                            vec![Arc::new(StringArray::from(vec![host.as_str(); batch.num_rows()]))];
+                        columns.extend(batch.columns().iter().cloned());
+                        batches.push(RecordBatch::try_new(self.schema.clone(), columns)?);
+                    }
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Warning: {} failed on {}: {}",
+                        self.command.join(" "),
+                        host,
+                        ZqueryError::from(e)
+                    );
+                    failed.push(host.clone());
+                }
+            }
+        }
+        if !failed.is_empty() && (strict || failed.len() == hosts.len()) {
+            return Err(ZqueryError::FanOut {
+                failed,
+                total: hosts.len(),
+            }
+            .into());
         }
+        Ok(batches)
+    }
 
-        // Ensure the channel closes cleanly
-        channel.wait_close().unwrap();
-
-        // Pipe the output into jc
-        let mut jc_cmd = Command::new("jc");
-        jc_cmd.arg(format!("--{}", jc_parser));
-        jc_cmd.arg("-q");
-        jc_cmd.stdin(Stdio::piped());
-        jc_cmd.stdout(Stdio::piped());
-        let mut jc_child = jc_cmd.spawn()?;
-
-        // Write the output of the initial command into jc's stdin
-        let mut jc_stdin = jc_child.stdin.take().expect("Failed to open jc stdin");
-        jc_stdin.write_all(s.as_bytes()).unwrap();
-        // send a newline
-        jc_stdin.write_all(b"\n").unwrap();
-        drop(jc_stdin);
-
-        // Read jc's output
-        let output = jc_child.wait_with_output().unwrap();
-        let stdout = String::from_utf8(output.stdout)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-        Ok(stdout)
+    fn run_command_remotely(
+        remote: &Remote,
+        host: &str,
+        command: &[String],
+        args: &[String],
+    ) -> std::result::Result<String, ZqueryError> {
+        // Quoted so the remote shell sees the same arguments a local run would.
+        let command_string = command
+            .iter()
+            .chain(args)
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        remote.transport.exec(host, &command_string)
     }
 
-    fn run_command_locally(command: &[String], args: &[String], jc_parser: &str) -> std::io::Result<String> {
-        // Run the initial command
+    fn run_command_locally(command: &[String], args: &[String]) -> std::result::Result<String, ZqueryError> {
         let mut cmd = Command::new(&command[0]);
         if command.len() > 1 {
             cmd.args(&command[1..]);
         }
-        if args.len() > 0 {
+        if !args.is_empty() {
             cmd.args(args);
         }
-        cmd.stdout(Stdio::piped());
-        let child = cmd.spawn()?;
-
-        // Pipe the output into jc
-        let mut jc_cmd = Command::new("jc");
-        jc_cmd.arg(format!("--{}", jc_parser));
-        jc_cmd.stdin(Stdio::piped());
-        jc_cmd.stdout(Stdio::piped());
-        let mut jc_child = jc_cmd.spawn()?;
-
-        // Write the output of the initial command into jc's stdin
-        let mut jc_stdin = jc_child.stdin.take().expect("Failed to open jc stdin");
-        let mut child_stdout = child.stdout.expect("Failed to open command stdout");
-
-        std::thread::spawn(move || {
-            let mut buffer = [0; 4096];
-            loop {
-                let n = match child_stdout.read(&mut buffer) {
-                    Ok(0) => break,
-                    Ok(n) => n,
-                    Err(_) => break,
-                };
-                if jc_stdin.write_all(&buffer[..n]).is_err() {
-                    break;
-                }
-            }
-        });
+        let output = cmd
+            .output()
+            .map_err(|e| ZqueryError::Command(format!("Failed to start {}: {}", command[0], e)))?;
+        if !output.status.success() {
+            return Err(exited(command_name(command), output.status, &output.stderr));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
 
-        // Read jc's output
-        let output = jc_child.wait_with_output()?;
-        let stdout = String::from_utf8(output.stdout)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+/// What to call a command in errors. `sh -c` scripts are passed the table's name as `$0`.
+fn command_name(command: &[String]) -> &str {
+    match command {
+        [sh, c, _, name, ..] if sh == "sh" && c == "-c" => name,
+        _ => &command[0],
+    }
+}
 
-        Ok(stdout)
+/// How many lines of a failed command's stderr its error keeps, since something like
+/// `find` can complain about every file it couldn't read.
+const STDERR_LINES: usize = 5;
+
+/// The error for a command that exited non-zero. Commands say why on stderr, usually
+/// starting with their own name, so that's used as is when there's anything in it.
+pub(crate) fn exited(name: &str, status: impl std::fmt::Display, stderr: &[u8]) -> ZqueryError {
+    let stderr = String::from_utf8_lossy(stderr);
+    let lines: Vec<&str> = stderr.trim().lines().collect();
+    match lines.len() {
+        0 => ZqueryError::Command(format!("{} exited with {}", name, status)),
+        n if n > STDERR_LINES => ZqueryError::Command(format!(
+            "{}\n... and {} more lines",
+            lines[..STDERR_LINES].join("\n"),
+            n - STDERR_LINES
+        )),
+        _ => ZqueryError::Command(lines.join("\n")),
     }
 }
 
-fn json_to_ndjson(json: &str) -> String {
-    //run it through jq
+fn spawn(cmd: &mut Command, name: &str) -> std::result::Result<Child, ZqueryError> {
+    cmd.spawn()
+        .map_err(|e| ZqueryError::Command(format!("Failed to start {}: {}", name, e)))
+}
+
+fn piped<T>(pipe: Option<T>, name: &str) -> std::result::Result<T, ZqueryError> {
+    pipe.ok_or_else(|| ZqueryError::Command(format!("Failed to open a pipe to {}", name)))
+}
+
+/// Pipes a command's output through `jc --<parser>`.
+fn jc(parser: &str, input: &str) -> std::result::Result<String, ZqueryError> {
+    let mut cmd = Command::new("jc");
+    cmd.arg(format!("--{}", parser));
+    cmd.arg("-q");
+    filter("jc", &mut cmd, input)
+}
+
+/// Runs JSON through a `jq` filter, printing each result on its own line.
+fn jq(jq_filter: &str, json: &str) -> std::result::Result<String, ZqueryError> {
     let mut cmd = Command::new("jq");
     cmd.arg("-c");
-    cmd.arg(".[]");
+    cmd.arg(jq_filter);
+    filter("jq", &mut cmd, json)
+}
+
+/// Writes `input` to a command and returns what it printed. A non-zero exit means
+/// the input didn't parse, and the command says why on stderr.
+fn filter(name: &str, cmd: &mut Command, input: &str) -> std::result::Result<String, ZqueryError> {
     cmd.stdin(Stdio::piped());
     cmd.stdout(Stdio::piped());
-    let mut child = cmd.spawn().unwrap();
-    {
-        let mut stdin = child.stdin.take().expect("Failed to open jq stdin");
-        stdin.write_all(json.as_bytes()).unwrap();
+    cmd.stderr(Stdio::piped());
+    let mut child = spawn(cmd, name)?;
+    let mut stdin = piped(child.stdin.take(), name)?;
+    let input = input.to_string();
+    // Written from another thread so a large output can't fill the pipe and deadlock.
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| ZqueryError::Command(format!("{}: {}", name, e)))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(ZqueryError::Parse(format!(
+            "{} exited with {}: {}",
+            name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
     }
-    let output = child.wait_with_output().unwrap();
-    String::from_utf8(output.stdout).unwrap()
+    String::from_utf8(output.stdout).map_err(|e| ZqueryError::Parse(e.to_string()))
 }
 
 #[async_trait]
@@ -198,35 +315,12 @@ impl TableProvider for CommandTable {
 
     async fn scan(
         &self,
-        _state: &dyn Session,
+        state: &dyn Session,
         projection: Option<&Vec<usize>>,
         _filters: &[Expr],
         _limit: Option<usize>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        // Run the command and parse the output
-        let mut output = if self.hostname == "localhost" {
-            Self::run_command_locally(&self.command, &self.args, &self.jc_parser).map_err(|e| {
-                DataFusionError::Execution(format!("Failed to execute command: {}", e))
-            })?
-        } else {
-            Self::run_command_remotely(&self.hostname, &self.command, &self.args, &self.jc_parser).map_err(
-                |e| DataFusionError::Execution(format!("Failed to execute command: {}", e)),
-            )?
-        };
-
-        if self.is_result_array {
-            output = json_to_ndjson(&output);
-        }
-
-        let cursor = Cursor::new(output);
-        let reader = ReaderBuilder::new(self.schema.clone())
-            .build(cursor)
-            .map_err(|e| {
-                DataFusionError::Execution(format!("Failed to build JSON reader: {}", e))
-            })?;
-        let batches: Vec<RecordBatch> = reader
-            .collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(|e| DataFusionError::Execution(format!("Failed to read batches: {}", e)))?;
+        let batches = self.run_in(state.config())?;
 
         Ok(Arc::new(MemoryExec::try_new(
             &[batches],
@@ -239,7 +333,13 @@ impl TableProvider for CommandTable {
 #[derive(Debug, Clone)]
 pub struct CommandTableFunc {
     pub command: Vec<&'static str>,
-    pub jc_parser: &'static str,
+    pub parser: Parser,
+    /// The arguments that follow the optional host, e.g. `[path]`.
+    pub args: &'static str,
+    /// What the table lists, for `\dt` and `\d`.
+    pub description: &'static str,
+    /// The platforms `jc` can parse the command's output on, by its names for them.
+    pub platforms: &'static [&'static str],
     pub schema: SchemaRef,
     pub is_result_array: bool,
 }
@@ -253,35 +353,171 @@ fn get_values_from_literals(exprs: &[Expr]) -> Vec<String> {
         }).collect()
 }
 
-impl TableFunctionImpl for CommandTableFunc {
-
-    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
-        let mut hostname = "localhost".to_string();
-        let mut args = Vec::new();
-        // eventually these need to evaled
-        if !exprs.is_empty() {
-            match &exprs[0] {
-                Expr::ScalarFunction(func) => {
-                    //todo, make sure that the function is the host function
-                    if let Expr::Literal(lit) = &func.args[0] {
-                        hostname = lit.to_string();
-                    }
-                    args = get_values_from_literals(&exprs[1..]);
+/// Pulls `key => value` arguments out of a table function call, leaving the
+/// positional ones. The engine rewrites them into `named_arg('key', value)` calls
+/// before planning since DataFusion would otherwise drop them.
+pub fn split_named_args(exprs: &[Expr]) -> (Vec<Expr>, HashMap<String, ScalarValue>) {
+    let mut positional = Vec::new();
+    let mut named = HashMap::new();
+    for expr in exprs {
+        match expr {
+            Expr::ScalarFunction(func) if func.name() == "named_arg" => {
+                if let [Expr::Literal(ScalarValue::Utf8(Some(name))), Expr::Literal(value)] =
+                    func.args.as_slice()
+                {
+                    named.insert(name.clone(), value.clone());
                 }
-                _ => {
-                    args = get_values_from_literals(exprs);
+            }
+            _ => positional.push(expr.clone()),
+        }
+    }
+    (positional, named)
+}
+
+/// Splits table function arguments into the target host and the remaining literal
+/// arguments, so `ls(host('x'), '/tmp')` becomes `("x", ["/tmp"])`. For `hosts('prod-*')`
+/// the "host" is the pattern.
+pub fn host_and_args(exprs: &[Expr]) -> (String, Vec<String>) {
+    let (exprs, _) = split_named_args(exprs);
+    let exprs = exprs.as_slice();
+    let mut hostname = "localhost".to_string();
+    let mut args = Vec::new();
+    // eventually these need to evaled
+    if !exprs.is_empty() {
+        match &exprs[0] {
+            Expr::ScalarFunction(func) => {
+                //todo, make sure that the function is the host function
+                if let Expr::Literal(lit) = &func.args[0] {
+                    hostname = lit.to_string();
                 }
+                args = get_values_from_literals(&exprs[1..]);
+            }
+            _ => {
+                args = get_values_from_literals(exprs);
+            }
+        }
+    }
+    (hostname, args)
+}
+
+impl CommandTableFunc {
+    /// How to call the table, e.g. `ls([host('name') | hosts('pattern')], [path])`.
+    pub fn signature(&self, name: &str) -> String {
+        let host = "[host('name') | hosts('pattern')]";
+        match self.args {
+            "" => format!("{}({})", name, host),
+            args => format!("{}({}, {})", name, host, args),
+        }
+    }
+
+    /// The names of the arguments in `args`, like `path` for `[path]`.
+    fn arg_names(&self) -> Vec<&'static str> {
+        self.args
+            .split(',')
+            .map(|arg| arg.trim().trim_start_matches('[').trim_end_matches(']'))
+            .filter(|arg| !arg.is_empty() && *arg != "...")
+            .collect()
+    }
+
+    /// Builds the CommandTable for a call like `ps()` or `ls(host('x'), '/tmp')`.
+    /// Arguments can also be named, like `vmstat(count => 10)`, which leaves any
+    /// before them empty.
+    pub fn table(&self, exprs: &[Expr]) -> Result<CommandTable> {
+        let (hostname, mut args) = host_and_args(exprs);
+        let (_, named) = split_named_args(exprs);
+        let names = self.arg_names();
+        for (name, value) in named {
+            let Some(position) = names.iter().position(|n| *n == name) else {
+                return Err(DataFusionError::Plan(format!(
+                    "No argument named '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )));
+            };
+            if args.len() <= position {
+                args.resize(position + 1, String::new());
             }
+            args[position] = match value {
+                ScalarValue::Utf8(Some(value)) => value,
+                value => value.to_string(),
+            };
         }
+        let fan_out = match exprs.first() {
+            Some(Expr::ScalarFunction(func)) if func.name() == "hosts" => Some(hostname.clone()),
+            _ => None,
+        };
+        let schema = match fan_out {
+            Some(_) => {
+                let mut fields = vec![Arc::new(Field::new("host", DataType::Utf8, false))];
+                fields.extend(self.schema.fields().iter().cloned());
+                Arc::new(Schema::new(fields))
+            }
+            None => self.schema.clone(),
+        };
 
-        let table = CommandTable {
+        Ok(CommandTable {
             command: self.command.iter().map(|s| s.to_string()).collect(),
-            jc_parser: self.jc_parser.to_string(),
-            schema: self.schema.clone(),
+            parser: self.parser,
+            schema,
             is_result_array: self.is_result_array,
             hostname,
             args,
-        };
-        Ok(Arc::new(table))
+            fan_out,
+        })
+    }
+}
+
+impl TableFunctionImpl for CommandTableFunc {
+
+    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        Ok(Arc::new(self.table(exprs)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn script_table(script: &'static str) -> CommandTableFunc {
+        CommandTableFunc {
+            command: vec!["sh", "-c", script, "script"],
+            parser: Parser::Native(|output| output.lines().map(|line| json!({ "line": line })).collect()),
+            args: "[arg]",
+            description: "",
+            platforms: &["linux"],
+            schema: Arc::new(Schema::new(vec![Field::new("line", DataType::Utf8, true)])),
+            is_result_array: false,
+        }
+    }
+
+    fn run(script: &'static str) -> std::result::Result<Vec<RecordBatch>, ZqueryError> {
+        script_table(script).table(&[]).unwrap().run().map_err(ZqueryError::from)
+    }
+
+    #[test]
+    fn a_failing_command_is_an_error_with_its_stderr() {
+        let error = run("echo partial; echo 'script: it broke' >&2; exit 3").unwrap_err();
+        assert!(matches!(&error, ZqueryError::Command(e) if e == "script: it broke"), "{}", error);
+        assert_eq!(error.exit_code(), 4);
+    }
+
+    #[test]
+    fn a_failing_command_without_stderr_names_its_status() {
+        let error = run("exit 2").unwrap_err();
+        assert!(error.to_string().contains("script exited with exit status: 2"), "{}", error);
+    }
+
+    #[test]
+    fn a_missing_command_is_an_error() {
+        let error = run("exec zquery-no-such-command").unwrap_err();
+        assert!(error.to_string().contains("zquery-no-such-command"), "{}", error);
+        assert_eq!(error.exit_code(), 4);
+    }
+
+    #[test]
+    fn a_command_that_succeeds_returns_its_rows() {
+        let batches = run("echo one; echo two >&2; echo three").unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
     }
 }
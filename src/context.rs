@@ -0,0 +1,218 @@
+use datafusion::execution::context::{SessionConfig, SessionContext};
+use datafusion_expr::ScalarUDF;
+use std::sync::Arc;
+
+use crate::commands::*;
+use crate::commandtable::{CommandTableFunc, StrictFanOut};
+use crate::cron::CronNextTableFunc;
+use crate::diff::DiffTableFunc;
+use crate::net::{CidrContains, CidrNetwork, IpScope};
+use crate::packages::VersionCmp;
+use crate::remote::{Inventory, Remote, Ssh, Transport};
+use crate::stream::{StreamTableFunc, Tumble};
+use crate::systemd::JournalTableFunc;
+use crate::tail::{TailJournalTableFunc, TailTableFunc};
+
+/// The command tables registered on a session, kept on its `SessionConfig` for
+/// `describe`, `\dt` and completion.
+#[derive(Debug, Clone)]
+pub struct CommandTables(pub Vec<(String, Arc<CommandTableFunc>)>);
+
+impl CommandTables {
+    /// The session's command tables, or the built-in ones if it wasn't made by a
+    /// `ZqueryContext`.
+    pub fn of(config: &SessionConfig) -> Self {
+        match config.get_extension::<CommandTables>() {
+            Some(tables) => tables.as_ref().clone(),
+            None => CommandTables(
+                command_table_funcs()
+                    .into_iter()
+                    .map(|(name, func)| (name.to_string(), func))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<CommandTableFunc>> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, func)| func)
+    }
+}
+
+/// Builds a DataFusion `SessionContext` with zquery's command tables, `stream`, `diff`,
+/// `tail` and the functions they need registered, for running zquery inside another
+/// program. Start from `ZqueryContext::new()`, change what you need and call `build`.
+#[derive(Debug, Clone)]
+pub struct ZqueryContext {
+    config: SessionConfig,
+    builtin_tables: bool,
+    tables: Vec<(String, Arc<CommandTableFunc>)>,
+    remote: Remote,
+    strict: bool,
+}
+
+impl Default for ZqueryContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZqueryContext {
+    pub fn new() -> Self {
+        Self {
+            // Coalescing would hold back stream() samples until thousands of rows piled
+            // up, and command tables are small enough that it buys nothing for them anyway.
+            config: SessionConfig::new().with_coalesce_batches(false),
+            builtin_tables: true,
+            tables: Vec::new(),
+            remote: Remote::default(),
+            strict: false,
+        }
+    }
+
+    /// Starts from this config instead of the default one.
+    pub fn with_config(mut self, config: SessionConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Leaves out `ps`, `df` and the rest, so only tables added with
+    /// `with_command_table` are available.
+    pub fn without_builtin_tables(mut self) -> Self {
+        self.builtin_tables = false;
+        self
+    }
+
+    /// Adds a command table, replacing any built-in one with the same name.
+    pub fn with_command_table(mut self, name: impl Into<String>, func: CommandTableFunc) -> Self {
+        let name = name.into();
+        self.tables.retain(|(n, _)| *n != name);
+        self.tables.push((name, Arc::new(func)));
+        self
+    }
+
+    /// How commands reach hosts other than `localhost`, ssh by default.
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.remote.transport = Arc::new(transport);
+        self
+    }
+
+    /// The hosts `hosts('pattern')` picks from, the hosts in `~/.ssh/config` by default.
+    pub fn with_inventory(mut self, inventory: impl Inventory + 'static) -> Self {
+        self.remote.inventory = Arc::new(inventory);
+        self
+    }
+
+    /// Uses ssh with this config, key and user both to reach hosts and to list them.
+    pub fn with_ssh(mut self, ssh: Ssh) -> Self {
+        let ssh = Arc::new(ssh);
+        self.remote = Remote {
+            transport: ssh.clone(),
+            inventory: ssh,
+        };
+        self
+    }
+
+    /// Fails a whole `hosts()` fan-out when any host fails, like `--strict`.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn build(self) -> SessionContext {
+        let mut tables: Vec<(String, Arc<CommandTableFunc>)> = Vec::new();
+        if self.builtin_tables {
+            for (name, func) in command_table_funcs() {
+                if !self.tables.iter().any(|(n, _)| n == name) {
+                    tables.push((name.to_string(), func));
+                }
+            }
+        }
+        tables.extend(self.tables);
+
+        let mut config = self
+            .config
+            .with_extension(Arc::new(self.remote.clone()))
+            .with_extension(Arc::new(CommandTables(tables.clone())));
+        if self.strict {
+            config = config.with_extension(Arc::new(StrictFanOut));
+        }
+
+        let ctx = SessionContext::new_with_config(config);
+        ctx.register_udf(ScalarUDF::from(Host::new()));
+        ctx.register_udf(ScalarUDF::from(Hosts::new()));
+        ctx.register_udf(ScalarUDF::from(NamedArg::new()));
+        ctx.register_udf(ScalarUDF::from(Tumble::new()));
+        ctx.register_udf(ScalarUDF::from(CidrContains::new()));
+        ctx.register_udf(ScalarUDF::from(CidrNetwork::new()));
+        ctx.register_udf(ScalarUDF::from(IpScope::new()));
+        ctx.register_udf(ScalarUDF::from(VersionCmp::new()));
+        for (name, func) in &tables {
+            ctx.register_udtf(name, func.clone());
+            ctx.register_udf(ScalarUDF::from(TableArg::new(name)));
+        }
+        ctx.register_udtf("stream", Arc::new(StreamTableFunc::new(&tables)));
+        ctx.register_udtf("diff", Arc::new(DiffTableFunc::new(&tables, ctx.state_weak_ref())));
+        ctx.register_udtf("tail", Arc::new(TailTableFunc { remote: self.remote.clone() }));
+        ctx.register_udtf("journal", Arc::new(JournalTableFunc));
+        ctx.register_udtf("cron_next", Arc::new(CronNextTableFunc::new(&tables)));
+        ctx.register_udtf("tail_journal", Arc::new(TailJournalTableFunc { remote: self.remote }));
+        ctx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commandtable::Parser;
+    use crate::engine::sql_to_df;
+    use crate::error::ZqueryError;
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use parking_lot::Mutex;
+    use serde_json::json;
+    use std::io::Read;
+
+    /// Answers every command with the host it was run on, and remembers the commands.
+    #[derive(Debug, Default)]
+    struct FakeTransport {
+        commands: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Transport for FakeTransport {
+        fn exec(&self, host: &str, command: &str) -> Result<String, ZqueryError> {
+            self.commands.lock().push((host.to_string(), command.to_string()));
+            Ok(format!("hello from {}\n", host))
+        }
+
+        fn follow(&self, host: &str, _command: &str) -> Result<Box<dyn Read + Send>, ZqueryError> {
+            Err(ZqueryError::Ssh(format!("Could not connect to {}:22", host)))
+        }
+    }
+
+    fn greetings() -> CommandTableFunc {
+        CommandTableFunc {
+            command: vec!["greet", "--loudly"],
+            parser: Parser::Native(|output| output.lines().map(|line| json!({ "line": line })).collect()),
+            args: "[name]",
+            description: "",
+            platforms: &["linux"],
+            schema: Arc::new(Schema::new(vec![Field::new("line", DataType::Utf8, true)])),
+            is_result_array: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn remote_tables_run_through_the_sessions_transport() {
+        let transport = FakeTransport::default();
+        let commands = transport.commands.clone();
+        let ctx = ZqueryContext::new()
+            .without_builtin_tables()
+            .with_command_table("greetings", greetings())
+            .with_transport(transport)
+            .build();
+        let sql = "select line from greetings(host('x'), 'it''s me')";
+        let batches = sql_to_df(&ctx, sql).await.unwrap().collect().await.unwrap();
+        assert_eq!(batches[0].column(0).as_string::<i32>().value(0), "hello from x");
+        assert_eq!(*commands.lock(), [("x".to_string(), r"'greet' '--loudly' 'it'\''s me'".to_string())]);
+    }
+}
@@ -0,0 +1,513 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
+use datafusion::arrow::array::{ArrayRef, StringArray, TimestampMicrosecondArray, UInt32Array};
+use datafusion::arrow::compute::take;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, DFSchema, Result};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::TaskContext;
+use datafusion::optimizer::simplify_expressions::{ExprSimplifier, SimplifyContext};
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::scalar::ScalarValue;
+use datafusion_expr::execution_props::ExecutionProps;
+use datafusion_expr::expr::Cast;
+use datafusion_expr::{Expr, TableType};
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::commandtable::CommandTableFunc;
+use crate::diff::collect_table;
+
+/// Prints whose crontab it is, then the crontab. `$1` is the user, the current one if
+/// there's none. crontab fails when the user has no crontab, which is left as no jobs.
+pub const CRONTAB: &str = r#"echo "${1:-$(id -un)}"; { error=$(crontab ${1:+-u "$1"} -l 2>&1 >&3); } 3>&1 || case $error in "no crontab for"*) ;; *) echo "$error" >&2; exit 1;; esac"#;
+
+/// How many runs `cron_next` lists for each schedule when not told.
+const DEFAULT_RUNS: usize = 10;
+
+/// `@daily` and the like, as the five fields they stand for.
+fn expand_macro(schedule: &str) -> Option<&'static str> {
+    Some(match schedule {
+        "@yearly" | "@annually" => "0 0 1 1 *",
+        "@monthly" => "0 0 1 * *",
+        "@weekly" => "0 0 * * 0",
+        "@daily" | "@midnight" => "0 0 * * *",
+        "@hourly" => "0 * * * *",
+        _ => return None,
+    })
+}
+
+/// Splits the first `n` whitespace separated fields off a line, returning them and
+/// the rest of it as written.
+fn split_fields(line: &str, n: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(n);
+    let mut rest = line.trim_start();
+    for _ in 0..n {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some((fields, rest))
+}
+
+/// Parses one crontab line into its schedule, fields and command. System crontabs,
+/// `/etc/crontab` and `/etc/cron.d`, have a user between the schedule and the command.
+/// Comments, blank lines and variable assignments like `MAILTO=root` are skipped.
+fn parse_line(line: &str, with_user: bool) -> Option<Map<String, Value>> {
+    static VARIABLE: OnceLock<Regex> = OnceLock::new();
+    let variable = VARIABLE.get_or_init(|| Regex::new(r"^\s*[A-Za-z_][A-Za-z0-9_]*\s*=").unwrap());
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') || variable.is_match(trimmed) {
+        return None;
+    }
+    let mut row = Map::new();
+    let (schedule, rest) = if trimmed.starts_with('@') {
+        let (fields, rest) = split_fields(trimmed, 1)?;
+        let schedule = fields[0];
+        if let Some(fields) = expand_macro(schedule) {
+            set_fields(&mut row, &fields.split(' ').collect::<Vec<_>>());
+        }
+        (schedule.to_string(), rest)
+    } else {
+        let (fields, rest) = split_fields(trimmed, 5)?;
+        set_fields(&mut row, &fields);
+        (fields.join(" "), rest)
+    };
+    let command = if with_user {
+        let (user, command) = split_fields(rest, 1)?;
+        row.insert("user".into(), json!(user[0]));
+        command
+    } else {
+        rest
+    };
+    row.insert("schedule".into(), json!(schedule));
+    row.insert("command".into(), json!(command));
+    Some(row)
+}
+
+fn set_fields(row: &mut Map<String, Value>, fields: &[&str]) {
+    for (name, field) in ["minute", "hour", "day_of_month", "month", "day_of_week"].iter().zip(fields) {
+        row.insert(name.to_string(), json!(field));
+    }
+}
+
+/// Parses `CRONTAB`, whose first line is the user.
+pub fn parse_crontab(output: &str) -> Vec<Value> {
+    let mut lines = output.lines();
+    let user = lines.next().map(str::trim);
+    lines
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let mut row = parse_line(line, false)?;
+            row.insert("user".into(), json!(user));
+            row.insert("line_number".into(), json!(i + 1));
+            Some(Value::Object(row))
+        })
+        .collect()
+}
+
+/// Parses `grep -Hn ^` over the system crontabs and the spool. Spool crontabs are named
+/// after their user and have no user column.
+pub fn parse_cron_files(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let (file, line_number, line) = (parts.next()?, parts.next()?, parts.next()?);
+            let system = file == "/etc/crontab" || file.starts_with("/etc/cron.d/");
+            let mut row = parse_line(line, system)?;
+            if !system {
+                row.insert("user".into(), json!(file.rsplit('/').next()));
+            }
+            row.insert("file".into(), json!(file));
+            row.insert("line_number".into(), json!(line_number.parse::<i64>().ok()));
+            Some(Value::Object(row))
+        })
+        .collect()
+}
+
+/// A parsed schedule, with a bit set for each minute, hour and so on it runs at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// cron runs on days matching either the day of month or the day of week when both
+    /// are restricted, and on days matching both otherwise.
+    either_day: bool,
+}
+
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Parses a field like `*/15`, `1-5`, `mon,wed` or `9-17/2` into bits `min` to `max`.
+/// `first` is what the first name stands for.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], first: u32) -> Option<u64> {
+    let value = |s: &str| -> Option<u32> {
+        let lower = s.to_ascii_lowercase();
+        match names.iter().position(|name| *name == lower) {
+            Some(i) => Some(i as u32 + first),
+            None => s.parse().ok(),
+        }
+    };
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // `5/10` means from 5 to the end, every 10.
+            None if part.contains('/') => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        bits |= (start..=end).step_by(step as usize).fold(0, |bits, i| bits | 1 << i);
+    }
+    Some(bits)
+}
+
+impl Schedule {
+    /// Parses five cron fields or a macro like `@daily`. `@reboot` has no times, so it
+    /// doesn't parse.
+    pub fn parse(schedule: &str) -> Option<Self> {
+        let schedule = schedule.trim();
+        let schedule = expand_macro(schedule).unwrap_or(schedule);
+        let fields: Vec<&str> = schedule.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return None;
+        };
+        let mut weekdays = parse_field(weekday, 0, 7, WEEKDAYS, 0)?;
+        // Sunday is both 0 and 7.
+        if weekdays & 1 << 7 != 0 {
+            weekdays |= 1;
+        }
+        Some(Self {
+            minutes: parse_field(minute, 0, 59, &[], 0)?,
+            hours: parse_field(hour, 0, 23, &[], 0)?,
+            days: parse_field(day, 1, 31, &[], 0)?,
+            months: parse_field(month, 1, 12, MONTHS, 1)?,
+            weekdays,
+            either_day: !day.starts_with('*') && !weekday.starts_with('*'),
+        })
+    }
+
+    fn runs_on(&self, date: NaiveDate) -> bool {
+        let day = self.days & 1 << date.day() != 0;
+        let weekday = self.weekdays & 1 << date.weekday().num_days_from_sunday() != 0;
+        if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first time after `after` this runs, giving up past five years out for
+    /// schedules like `0 0 30 2 *` that never do.
+    pub fn next(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let limit = after + Duration::days(5 * 366);
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        while t < limit {
+            let date = t.date();
+            if self.months & 1 << date.month() == 0 {
+                let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.runs_on(date) {
+                t = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & 1 << t.hour() == 0 {
+                t = date.and_hms_opt(t.hour(), 0, 0)? + Duration::hours(1);
+            } else if self.minutes & 1 << t.minute() == 0 {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+/// Evaluates a constant argument like `now()` or `timestamp '2024-01-01 02:00:00'`.
+fn constant(expr: &Expr) -> Result<ScalarValue> {
+    let props = ExecutionProps::new().with_query_execution_start_time(Utc::now());
+    let context = SimplifyContext::new(&props).with_schema(Arc::new(DFSchema::empty()));
+    match ExprSimplifier::new(context).simplify(expr.clone())? {
+        Expr::Literal(value) => Ok(value),
+        // The simplifier leaves casts that fail alone, so cast again for the error.
+        Expr::Cast(Cast { expr, data_type }) => match *expr {
+            Expr::Literal(value) => value.cast_to(&data_type),
+            other => plan_err!("cron_next expects a constant, got {}", other),
+        },
+        other => plan_err!("cron_next expects a constant, got {}", other),
+    }
+}
+
+/// Table function for `cron_next('*/15 * * * *', now(), 10)`, the next times a schedule
+/// runs. Given a table with a `schedule` column instead, like `cron_files()`, it lists
+/// the next runs of every row, so jobs that overlap can be found with a `group by`.
+/// Times are in the zone of the start time, UTC for `now()`.
+#[derive(Debug)]
+pub struct CronNextTableFunc {
+    tables: HashMap<String, Arc<CommandTableFunc>>,
+}
+
+impl CronNextTableFunc {
+    pub fn new(tables: &[(String, Arc<CommandTableFunc>)]) -> Self {
+        Self {
+            tables: tables
+                .iter()
+                .map(|(name, func)| (name.clone(), func.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl TableFunctionImpl for CronNextTableFunc {
+    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let source = match exprs.first() {
+            Some(Expr::Literal(ScalarValue::Utf8(Some(schedule)))) => {
+                if Schedule::parse(schedule).is_none() {
+                    return plan_err!("Could not parse '{}' as a cron schedule", schedule);
+                }
+                Source::Schedule(schedule.clone())
+            }
+            Some(Expr::ScalarFunction(func)) => match self.tables.get(func.name()) {
+                Some(table_func) => {
+                    let table = table_func.table(&func.args)?;
+                    if table.schema().index_of("schedule").is_err() {
+                        return plan_err!("{} has no schedule column", func.name());
+                    }
+                    Source::Table(Arc::new(table))
+                }
+                None => return plan_err!("{} is not a command table", func.name()),
+            },
+            _ => return plan_err!("cron_next expects a schedule or a table, e.g. cron_next('0 2 * * *', now(), 5)"),
+        };
+        let from = match exprs.get(1) {
+            Some(expr) => match constant(expr)?.cast_to(&DataType::Timestamp(TimeUnit::Microsecond, None))? {
+                ScalarValue::TimestampMicrosecond(Some(micros), _) => DateTime::from_timestamp_micros(micros),
+                other => return plan_err!("cron_next expects a start time, got {}", other),
+            },
+            None => DateTime::from_timestamp_micros(Utc::now().timestamp_micros()),
+        };
+        let Some(from) = from.map(|t| t.naive_utc()) else {
+            return plan_err!("cron_next's start time is out of range");
+        };
+        let runs = match exprs.get(2).map(constant).transpose()? {
+            Some(ScalarValue::Int64(Some(runs))) if runs > 0 => runs as usize,
+            Some(other) => return plan_err!("cron_next expects a positive number of runs, got {}", other),
+            None => DEFAULT_RUNS,
+        };
+        Ok(Arc::new(CronNextTable::new(source, from, runs)))
+    }
+}
+
+enum Source {
+    Schedule(String),
+    Table(Arc<dyn TableProvider>),
+}
+
+/// The next `runs` times after `from` of a schedule, or of each row of a table.
+pub struct CronNextTable {
+    source: Source,
+    from: NaiveDateTime,
+    runs: usize,
+    schema: SchemaRef,
+}
+
+impl CronNextTable {
+    fn new(source: Source, from: NaiveDateTime, runs: usize) -> Self {
+        let mut fields = vec![Field::new("run_time", DataType::Timestamp(TimeUnit::Microsecond, None), false)];
+        match &source {
+            Source::Schedule(_) => fields.push(Field::new("schedule", DataType::Utf8, false)),
+            Source::Table(table) => fields.extend(table.schema().fields().iter().map(|f| f.as_ref().clone())),
+        }
+        Self {
+            source,
+            from,
+            runs,
+            schema: Arc::new(Schema::new(fields)),
+        }
+    }
+
+    /// The run times of each schedule, along with the index of the schedule they're for.
+    fn run_times<'a>(&self, schedules: impl Iterator<Item = Option<&'a str>>) -> (Vec<i64>, Vec<u32>) {
+        let (mut times, mut rows) = (Vec::new(), Vec::new());
+        for (row, schedule) in schedules.enumerate() {
+            let Some(schedule) = schedule.and_then(Schedule::parse) else {
+                continue;
+            };
+            let mut t = self.from;
+            for _ in 0..self.runs {
+                let Some(next) = schedule.next(t) else { break };
+                times.push(next.and_utc().timestamp_micros());
+                rows.push(row as u32);
+                t = next;
+            }
+        }
+        (times, rows)
+    }
+}
+
+#[async_trait]
+impl TableProvider for CronNextTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let batch = match &self.source {
+            Source::Schedule(schedule) => {
+                let (times, _) = self.run_times(std::iter::once(Some(schedule.as_str())));
+                let schedules = StringArray::from(vec![schedule.as_str(); times.len()]);
+                RecordBatch::try_new(
+                    self.schema.clone(),
+                    vec![Arc::new(TimestampMicrosecondArray::from(times)), Arc::new(schedules)],
+                )?
+            }
+            Source::Table(table) => {
+                let task_ctx = Arc::new(TaskContext::from(state));
+                let rows = collect_table(table, state, task_ctx).await?;
+                let schedules = rows.column_by_name("schedule").expect("checked when planning");
+                let schedules = datafusion::arrow::compute::cast(schedules, &DataType::Utf8)?;
+                let schedules = schedules.as_any().downcast_ref::<StringArray>().expect("cast to Utf8");
+                let (times, indices) = self.run_times(schedules.iter());
+                let indices = UInt32Array::from(indices);
+                let mut columns: Vec<ArrayRef> = vec![Arc::new(TimestampMicrosecondArray::from(times))];
+                for column in rows.columns() {
+                    columns.push(take(column, &indices, None)?);
+                }
+                RecordBatch::try_new(self.schema.clone(), columns)?
+            }
+        };
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema.clone(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::TimestampMicrosecondType;
+
+    fn bits(values: impl IntoIterator<Item = u32>) -> u64 {
+        values.into_iter().fold(0, |bits, i| bits | 1 << i)
+    }
+
+    fn at(t: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    /// The next `n` runs of a schedule from Monday 2026-10-19 10:07.
+    fn runs(schedule: &str, n: usize) -> Vec<String> {
+        let schedule = Schedule::parse(schedule).unwrap();
+        let mut t = at("2026-10-19 10:07");
+        (0..n)
+            .map_while(|_| {
+                t = schedule.next(t)?;
+                Some(t.format("%Y-%m-%d %a %H:%M").to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fields_take_ranges_steps_lists_and_names() {
+        assert_eq!(parse_field("*/15", 0, 59, &[], 0), Some(bits([0, 15, 30, 45])));
+        assert_eq!(parse_field("1-5", 0, 59, &[], 0), Some(bits(1..=5)));
+        assert_eq!(parse_field("9-17/2", 0, 23, &[], 0), Some(bits([9, 11, 13, 15, 17])));
+        assert_eq!(parse_field("5/20", 0, 59, &[], 0), Some(bits([5, 25, 45])));
+        assert_eq!(parse_field("1,3,5", 0, 59, &[], 0), Some(bits([1, 3, 5])));
+        assert_eq!(parse_field("mon,WED-fri", 0, 7, WEEKDAYS, 0), Some(bits([1, 3, 4, 5])));
+        assert_eq!(parse_field("jan,jul-aug", 1, 12, MONTHS, 1), Some(bits([1, 7, 8])));
+        for bad in ["60", "5-1", "*/0", "*/x", "foo", "", "1-"] {
+            assert_eq!(parse_field(bad, 0, 59, &[], 0), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn schedules_need_five_fields_or_a_macro() {
+        assert!(Schedule::parse("0 0 * *").is_none());
+        assert!(Schedule::parse("0 0 * * * *").is_none());
+        assert!(Schedule::parse("@reboot").is_none());
+        assert!(Schedule::parse("0 0 32 * *").is_none());
+        assert_eq!(Schedule::parse("@daily").unwrap().minutes, bits([0]));
+        assert_eq!(Schedule::parse("0 0 * * 7").unwrap().weekdays, bits([0, 7]));
+    }
+
+    #[test]
+    fn next_runs() {
+        assert_eq!(runs("*/15 * * * *", 3), ["2026-10-19 Mon 10:15", "2026-10-19 Mon 10:30", "2026-10-19 Mon 10:45"]);
+        assert_eq!(
+            runs("0 9-17/4 * * mon-fri", 3),
+            ["2026-10-19 Mon 13:00", "2026-10-19 Mon 17:00", "2026-10-20 Tue 09:00"]
+        );
+        assert_eq!(runs("@daily", 2), ["2026-10-20 Tue 00:00", "2026-10-21 Wed 00:00"]);
+        assert_eq!(runs("@hourly", 1), ["2026-10-19 Mon 11:00"]);
+        assert_eq!(runs("0 0 * * 7", 1), ["2026-10-25 Sun 00:00"]);
+        assert_eq!(runs("30 4 1 jan *", 1), ["2027-01-01 Fri 04:30"]);
+        assert_eq!(runs("0 0 29 feb *", 1), ["2028-02-29 Tue 00:00"]);
+        assert!(runs("0 0 30 feb *", 1).is_empty());
+    }
+
+    #[test]
+    fn a_restricted_day_of_month_and_day_of_week_run_on_either() {
+        assert_eq!(
+            runs("0 0 1 * fri", 4),
+            ["2026-10-23 Fri 00:00", "2026-10-30 Fri 00:00", "2026-11-01 Sun 00:00", "2026-11-06 Fri 00:00"]
+        );
+        // With either one left as `*`, only the other counts.
+        assert_eq!(runs("0 0 */10 * *", 2), ["2026-10-21 Wed 00:00", "2026-10-31 Sat 00:00"]);
+        assert_eq!(runs("0 0 * * fri", 2), ["2026-10-23 Fri 00:00", "2026-10-30 Fri 00:00"]);
+    }
+
+    #[tokio::test]
+    async fn cron_next_lists_the_next_runs() {
+        let ctx = ZqueryContext::new().build();
+        let sql = "select run_time, schedule from cron_next('0 2 * * *', timestamp '2026-10-19 10:07:00', 2)";
+        let batches = sql_to_df(&ctx, sql).await.unwrap().collect().await.unwrap();
+        let times: Vec<i64> = batches[0].column(0).as_primitive::<TimestampMicrosecondType>().values().to_vec();
+        let expected = [at("2026-10-20 02:00"), at("2026-10-21 02:00")].map(|t| t.and_utc().timestamp_micros());
+        assert_eq!(times, expected);
+        assert_eq!(batches[0].column(1).as_string::<i32>().value(0), "0 2 * * *");
+
+        for (sql, message) in [
+            ("select * from cron_next('0 2 * *')", "Could not parse '0 2 * *' as a cron schedule"),
+            ("select * from cron_next('@daily', now(), 0)", "positive number of runs"),
+        ] {
+            let error = sql_to_df(&ctx, sql).await.unwrap_err();
+            assert!(error.to_string().contains(message), "{}: {}", sql, error);
+        }
+    }
+}
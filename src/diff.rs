@@ -0,0 +1,388 @@
+use async_trait::async_trait;
+use datafusion::arrow::array::{
+    new_null_array, ArrayRef, ListBuilder, StringBuilder, UInt32Array,
+};
+use datafusion::arrow::compute::{cast, concat, concat_batches, take};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, Result};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::TableProvider;
+use datafusion::error::DataFusionError;
+use datafusion::execution::context::{SessionContext, SessionState};
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::{collect, ExecutionPlan};
+use datafusion::scalar::ScalarValue;
+use datafusion_expr::{Expr, TableType};
+use futures::FutureExt;
+use parking_lot::RwLock;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+
+use crate::commandtable::{split_named_args, CommandTableFunc};
+
+/// Table function for `diff('before', ps(), key => 'pid')`. Each side is either a
+/// live command table or the name of a table registered in the session, such as one
+/// made with `create table before as select * from ps()` or a `create external table`
+/// over a saved snapshot.
+#[derive(Debug)]
+pub struct DiffTableFunc {
+    tables: HashMap<String, Arc<CommandTableFunc>>,
+    state: Weak<RwLock<SessionState>>,
+}
+
+impl DiffTableFunc {
+    pub fn new(
+        tables: &[(String, Arc<CommandTableFunc>)],
+        state: Weak<RwLock<SessionState>>,
+    ) -> Self {
+        Self {
+            tables: tables
+                .iter()
+                .map(|(name, func)| (name.clone(), func.clone()))
+                .collect(),
+            state,
+        }
+    }
+
+    fn resolve(&self, expr: &Expr) -> Result<Side> {
+        match expr {
+            Expr::ScalarFunction(func) => match self.tables.get(func.name()) {
+                Some(table_func) => Ok(Side::Command(Arc::new(table_func.table(&func.args)?))),
+                None => plan_err!("{} is not a command table", func.name()),
+            },
+            Expr::Literal(ScalarValue::Utf8(Some(name))) => {
+                // Planning only needs the columns, which the session's own tables have
+                // ready without waiting. The table itself is looked up when diff runs.
+                let table = match session(&self.state)?.table_provider(name.as_str()).now_or_never() {
+                    Some(table) => table?,
+                    None => return plan_err!("Couldn't look up '{}' for diff without waiting on its catalog", name),
+                };
+                Ok(Side::Session { name: name.clone(), schema: table.schema() })
+            }
+            other => plan_err!(
+                "diff expects a command table like ps() or a quoted table name, got {}",
+                other
+            ),
+        }
+    }
+}
+
+impl TableFunctionImpl for DiffTableFunc {
+    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let (positional, named) = split_named_args(exprs);
+        let (old, new) = match positional.as_slice() {
+            [old, new, ..] => (self.resolve(old)?, self.resolve(new)?),
+            _ => return plan_err!("diff expects two tables, e.g. diff('before', ps(), key => 'pid')"),
+        };
+        let key = match (named.get("key"), positional.get(2)) {
+            (Some(ScalarValue::Utf8(Some(key))), _) => key.clone(),
+            (None, Some(Expr::Literal(ScalarValue::Utf8(Some(key))))) => key.clone(),
+            _ => return plan_err!("diff needs the column(s) to match rows on, e.g. key => 'pid'"),
+        };
+        let keys: Vec<String> = key.split(',').map(|k| k.trim().to_string()).collect();
+        for key in &keys {
+            if old.schema().index_of(key).is_err() || new.schema().index_of(key).is_err() {
+                return plan_err!("Both sides of diff need a '{}' column", key);
+            }
+        }
+        Ok(Arc::new(DiffTable::new(old, new, keys, self.state.clone())))
+    }
+}
+
+fn session(state: &Weak<RwLock<SessionState>>) -> Result<SessionContext> {
+    match state.upgrade() {
+        Some(state) => Ok(SessionContext::new_with_state(state.read().clone())),
+        None => plan_err!("The session has already been closed"),
+    }
+}
+
+/// One side of a diff: a live command table, or a table in the session that's looked
+/// up by name when the diff runs.
+pub enum Side {
+    Command(Arc<dyn TableProvider>),
+    Session { name: String, schema: SchemaRef },
+}
+
+impl Side {
+    fn schema(&self) -> SchemaRef {
+        match self {
+            Side::Command(table) => table.schema(),
+            Side::Session { schema, .. } => schema.clone(),
+        }
+    }
+}
+
+/// The rows that were added, removed or changed between two tables, matched on `keys`.
+pub struct DiffTable {
+    old: Side,
+    new: Side,
+    keys: Vec<String>,
+    schema: SchemaRef,
+    state: Weak<RwLock<SessionState>>,
+}
+
+impl DiffTable {
+    pub fn new(old: Side, new: Side, keys: Vec<String>, state: Weak<RwLock<SessionState>>) -> Self {
+        let mut fields = vec![
+            Field::new("change", DataType::Utf8, false),
+            Field::new(
+                "changed_columns",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true,
+            ),
+        ];
+        fields.extend(
+            new.schema()
+                .fields()
+                .iter()
+                .map(|f| f.as_ref().clone().with_nullable(true)),
+        );
+        Self {
+            old,
+            new,
+            keys,
+            schema: Arc::new(Schema::new(fields)),
+            state,
+        }
+    }
+
+    async fn table(&self, side: &Side) -> Result<Arc<dyn TableProvider>> {
+        match side {
+            Side::Command(table) => Ok(table.clone()),
+            Side::Session { name, .. } => session(&self.state)?.table_provider(name.as_str()).await,
+        }
+    }
+
+    fn diff(&self, old: &RecordBatch, new: &RecordBatch) -> Result<RecordBatch> {
+        let new_schema = new.schema();
+        // Line the old rows up with the new columns so removed rows can share them.
+        let old_columns = new_schema
+            .fields()
+            .iter()
+            .map(|f| match old.column_by_name(f.name()) {
+                Some(column) => Ok(cast(column, f.data_type())?),
+                None => Ok(new_null_array(f.data_type(), old.num_rows())),
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+        let key_indices = self
+            .keys
+            .iter()
+            .map(|k| new_schema.index_of(k))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let compared: Vec<usize> = (0..new_schema.fields().len())
+            .filter(|i| !key_indices.contains(i))
+            .filter(|i| old.column_by_name(new_schema.field(*i).name()).is_some())
+            .collect();
+
+        let row_key = |columns: &[ArrayRef], row: usize| {
+            key_indices
+                .iter()
+                .map(|i| ScalarValue::try_from_array(&columns[*i], row))
+                .collect::<Result<Vec<_>>>()
+        };
+        let mut old_rows = HashMap::new();
+        for row in 0..old.num_rows() {
+            let key = row_key(&old_columns, row)?;
+            if old_rows.contains_key(&key) {
+                return self.duplicate_key("old", &key);
+            }
+            old_rows.insert(key, row);
+        }
+
+        let mut changes = StringBuilder::new();
+        let mut changed_columns = ListBuilder::new(StringBuilder::new());
+        let mut new_indices = Vec::new();
+        let mut seen = HashSet::new();
+        let mut new_keys = HashSet::new();
+        for row in 0..new.num_rows() {
+            let key = row_key(new.columns(), row)?;
+            if new_keys.contains(&key) {
+                return self.duplicate_key("new", &key);
+            }
+            let old_row = old_rows.get(&key).copied();
+            new_keys.insert(key);
+            let old_row = match old_row {
+                Some(old_row) => old_row,
+                None => {
+                    changes.append_value("added");
+                    changed_columns.append_null();
+                    new_indices.push(row as u32);
+                    continue;
+                }
+            };
+            seen.insert(old_row);
+            let mut differs = Vec::new();
+            for i in &compared {
+                if ScalarValue::try_from_array(new.column(*i), row)?
+                    != ScalarValue::try_from_array(&old_columns[*i], old_row)?
+                {
+                    differs.push(new_schema.field(*i).name().clone());
+                }
+            }
+            if !differs.is_empty() {
+                changes.append_value("changed");
+                changed_columns.values().extend(differs.into_iter().map(Some));
+                changed_columns.append(true);
+                new_indices.push(row as u32);
+            }
+        }
+        let removed: Vec<u32> = (0..old.num_rows())
+            .filter(|row| !seen.contains(row))
+            .map(|row| row as u32)
+            .collect();
+        for _ in &removed {
+            changes.append_value("removed");
+            changed_columns.append_null();
+        }
+
+        let new_indices = UInt32Array::from(new_indices);
+        let removed = UInt32Array::from(removed);
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(changes.finish()) as ArrayRef,
+            Arc::new(changed_columns.finish()),
+        ];
+        for (i, old_column) in old_columns.iter().enumerate() {
+            let added_or_changed = take(new.column(i), &new_indices, None)?;
+            let removed = take(old_column, &removed, None)?;
+            columns.push(concat(&[added_or_changed.as_ref(), removed.as_ref()])?);
+        }
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+
+    /// Rows are matched one to one, so a key that repeats can't say which rows
+    /// correspond.
+    fn duplicate_key<T>(&self, side: &str, key: &[ScalarValue]) -> Result<T> {
+        let key = key.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+        plan_err!(
+            "diff key '{}' isn't unique, ({}) appears more than once in the {} table. Add columns to the key, e.g. key => 'a,b'",
+            self.keys.join(","),
+            key,
+            side
+        )
+    }
+}
+
+pub(crate) async fn collect_table(
+    table: &Arc<dyn TableProvider>,
+    state: &dyn Session,
+    task_ctx: Arc<TaskContext>,
+) -> Result<RecordBatch> {
+    let plan = table.scan(state, None, &[], None).await?;
+    let batches = collect(plan, task_ctx).await?;
+    Ok(concat_batches(&table.schema(), &batches)?)
+}
+
+#[async_trait]
+impl TableProvider for DiffTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let task_ctx = Arc::new(TaskContext::from(state));
+        let old = collect_table(&self.table(&self.old).await?, state, task_ctx.clone()).await?;
+        let new = collect_table(&self.table(&self.new).await?, state, task_ctx).await?;
+        let batch = self.diff(&old, &new).map_err(|e| match e {
+            DataFusionError::Plan(_) => e,
+            e => DataFusionError::Execution(format!("Failed to diff tables: {}", e)),
+        })?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema.clone(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use datafusion::prelude::SessionContext;
+
+    async fn session(old: &str, new: &str) -> SessionContext {
+        let ctx = ZqueryContext::new().build();
+        for (name, rows) in [("old", old), ("new", new)] {
+            let sql = format!("create table {} as select * from (values {}) as t(a, b, v)", name, rows);
+            sql_to_df(&ctx, &sql).await.unwrap().collect().await.unwrap();
+        }
+        ctx
+    }
+
+    async fn diff(ctx: &SessionContext, key: &str) -> datafusion::error::Result<String> {
+        let sql = format!(
+            "select change, changed_columns, a, b, v from diff('old', 'new', key => '{}') order by change, a, b",
+            key
+        );
+        let batches = sql_to_df(ctx, &sql).await?.collect().await?;
+        Ok(pretty_format_batches(&batches)?.to_string())
+    }
+
+    #[tokio::test]
+    async fn rows_are_matched_on_a_composite_key() {
+        let ctx = session(
+            "(1, 'x', 10), (1, 'y', 20), (2, 'x', 30)",
+            "(1, 'x', 10), (1, 'y', 25), (2, 'y', 40)",
+        )
+        .await;
+        let expected = [
+            "+---------+-----------------+---+---+----+",
+            "| change  | changed_columns | a | b | v  |",
+            "+---------+-----------------+---+---+----+",
+            "| added   |                 | 2 | y | 40 |",
+            "| changed | [v]             | 1 | y | 25 |",
+            "| removed |                 | 2 | x | 30 |",
+            "+---------+-----------------+---+---+----+",
+        ];
+        assert_eq!(diff(&ctx, "a, b").await.unwrap(), expected.join("\n"));
+    }
+
+    #[tokio::test]
+    async fn a_key_that_repeats_is_an_error() {
+        let ctx = session("(1, 'x', 10), (1, 'y', 20)", "(1, 'x', 10), (2, 'y', 20)").await;
+        let error = diff(&ctx, "a").await.unwrap_err().to_string();
+        assert!(error.contains("diff key 'a' isn't unique, (1) appears more than once in the old table"), "{}", error);
+
+        let ctx = session("(1, 'x', 10), (2, 'y', 20)", "(1, 'x', 10), (2, 'x', 20), (2, 'y', 30)").await;
+        let error = diff(&ctx, "a").await.unwrap_err().to_string();
+        assert!(error.contains("(2) appears more than once in the new table"), "{}", error);
+        assert!(diff(&ctx, "a,b").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn an_added_key_that_repeats_is_an_error() {
+        let ctx = session("(1, 'x', 10)", "(1, 'x', 10), (2, 'x', 20), (2, 'y', 30)").await;
+        let error = diff(&ctx, "a").await.unwrap_err().to_string();
+        assert!(error.contains("(2) appears more than once in the new table"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn session_tables_are_read_when_the_diff_runs() {
+        let ctx = session("(1, 'x', 10)", "(1, 'x', 10)").await;
+        let df = sql_to_df(&ctx, "select change, a from diff('old', 'new', key => 'a')").await.unwrap();
+        sql_to_df(&ctx, "insert into new values (2, 'y', 20)").await.unwrap().collect().await.unwrap();
+        let batches = df.collect().await.unwrap();
+        let expected = ["+--------+---+", "| change | a |", "+--------+---+", "| added  | 2 |", "+--------+---+"];
+        assert_eq!(pretty_format_batches(&batches).unwrap().to_string(), expected.join("\n"));
+    }
+}
@@ -0,0 +1,296 @@
+use chrono::DateTime;
+use serde_json::{json, Map, Value};
+
+/// `--format` for docker's list commands, one JSON object per line.
+pub const JSON_FORMAT: &str = "{{json .}}";
+
+/// Inspects the containers given as arguments, or every container when there are none.
+/// The arguments reach the script as `$@` since `sh -c` takes the ones after its name.
+pub const INSPECT_CONTAINERS: &str =
+    r#"if [ $# -eq 0 ]; then ids=$(docker ps -aq) || exit; set -- $ids; fi; [ $# -eq 0 ] || docker inspect "$@""#;
+
+/// The objects from `--format '{{json .}}'`, skipping anything else docker printed.
+fn json_lines(output: &str) -> impl Iterator<Item = Map<String, Value>> + '_ {
+    output.lines().filter_map(|line| match serde_json::from_str(line) {
+        Ok(Value::Object(object)) => Some(object),
+        _ => None,
+    })
+}
+
+/// A string field, with docker's placeholders for nothing as null.
+fn text(object: &Map<String, Value>, key: &str) -> Value {
+    match object.get(key).and_then(|v| v.as_str()) {
+        None | Some("") | Some("<none>") | Some("N/A") | Some("--") => Value::Null,
+        Some(s) => json!(s),
+    }
+}
+
+/// Parses docker's human sizes, `7.5MiB` or `1.2kB`, into bytes.
+fn parse_size(size: &str) -> Option<i64> {
+    let size = size.trim();
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (number, unit) = size.split_at(split);
+    let multiplier: f64 = match unit.trim() {
+        "B" => 1.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number.parse::<f64>().ok()? * multiplier).round() as i64)
+}
+
+/// Splits `used / limit` pairs like `MemUsage` and `NetIO` into bytes.
+fn parse_pair(pair: &Value) -> (Option<i64>, Option<i64>) {
+    let Some((a, b)) = pair.as_str().and_then(|p| p.split_once(" / ")) else {
+        return (None, None);
+    };
+    (parse_size(a), parse_size(b))
+}
+
+/// docker prints times as `2024-01-02 03:04:05 +0000 UTC`, with the zone name after
+/// the offset, which comes back as microseconds since the epoch.
+fn parse_created(created: &Value) -> Value {
+    let Some(created) = created.as_str() else {
+        return Value::Null;
+    };
+    let without_zone = created.rsplitn(2, ' ').last().unwrap_or(created);
+    DateTime::parse_from_str(without_zone, "%Y-%m-%d %H:%M:%S %z")
+        .or_else(|_| DateTime::parse_from_str(created, "%Y-%m-%d %H:%M:%S %z"))
+        .map(|t| json!(t.timestamp_micros()))
+        .unwrap_or(Value::Null)
+}
+
+/// Parses `docker ps --format '{{json .}}'`.
+pub fn parse_docker_ps(output: &str) -> Vec<Value> {
+    json_lines(output)
+        .map(|c| {
+            json!({
+                "id": text(&c, "ID"),
+                "names": text(&c, "Names"),
+                "image": text(&c, "Image"),
+                "command": c.get("Command").and_then(|v| v.as_str()).map(|s| s.trim_matches('"')),
+                "created_at": parse_created(&c["CreatedAt"]),
+                "state": text(&c, "State"),
+                "status": text(&c, "Status"),
+                "ports": text(&c, "Ports"),
+                "labels": text(&c, "Labels"),
+                "networks": text(&c, "Networks"),
+                "mounts": text(&c, "Mounts"),
+            })
+        })
+        .collect()
+}
+
+/// Parses `docker images --format '{{json .}}'`.
+pub fn parse_docker_images(output: &str) -> Vec<Value> {
+    json_lines(output)
+        .map(|i| {
+            json!({
+                "repository": text(&i, "Repository"),
+                "tag": text(&i, "Tag"),
+                "id": text(&i, "ID"),
+                "digest": text(&i, "Digest"),
+                "created_at": parse_created(&i["CreatedAt"]),
+                "size_bytes": i.get("Size").and_then(|s| s.as_str()).and_then(parse_size),
+            })
+        })
+        .collect()
+}
+
+/// Parses `docker stats --no-stream --format '{{json .}}'`, with percentages as numbers
+/// and sizes in bytes.
+pub fn parse_docker_stats(output: &str) -> Vec<Value> {
+    let percent = |v: &Value| v.as_str().and_then(|p| p.trim_end_matches('%').parse::<f64>().ok());
+    json_lines(output)
+        .map(|s| {
+            let (mem_used, mem_limit) = parse_pair(&s["MemUsage"]);
+            let (net_rx, net_tx) = parse_pair(&s["NetIO"]);
+            let (block_read, block_write) = parse_pair(&s["BlockIO"]);
+            json!({
+                "id": text(&s, "ID"),
+                "name": text(&s, "Name"),
+                "cpu_percent": percent(&s["CPUPerc"]),
+                "mem_percent": percent(&s["MemPerc"]),
+                "mem_used_bytes": mem_used,
+                "mem_limit_bytes": mem_limit,
+                "net_rx_bytes": net_rx,
+                "net_tx_bytes": net_tx,
+                "block_read_bytes": block_read,
+                "block_write_bytes": block_write,
+                "pids": s.get("PIDs").and_then(|p| p.as_str()).and_then(|p| p.parse::<i64>().ok()),
+            })
+        })
+        .collect()
+}
+
+/// Parses `docker inspect` into snake_case columns, keeping mounts, ports, networks and
+/// labels nested. Times that docker leaves at year 1 for never are null.
+pub fn parse_docker_inspect(output: &str) -> Vec<Value> {
+    let Ok(Value::Array(containers)) = serde_json::from_str::<Value>(output) else {
+        return Vec::new();
+    };
+    let time = |v: &Value| match v.as_str() {
+        Some(t) if !t.starts_with("0001-") => DateTime::parse_from_rfc3339(t)
+            .map(|t| json!(t.timestamp_micros()))
+            .unwrap_or(Value::Null),
+        _ => Value::Null,
+    };
+    containers
+        .iter()
+        .map(|c| {
+            let state = &c["State"];
+            let config = &c["Config"];
+            let mounts: Vec<Value> = c["Mounts"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|m| {
+                    json!({
+                        "type": m["Type"],
+                        "name": m["Name"],
+                        "source": m["Source"],
+                        "destination": m["Destination"],
+                        "mode": m["Mode"],
+                        "rw": m["RW"],
+                    })
+                })
+                .collect();
+            let mut ports = Vec::new();
+            for (port, bindings) in c["NetworkSettings"]["Ports"].as_object().into_iter().flatten() {
+                let (container_port, protocol) = port.split_once('/').unwrap_or((port, "tcp"));
+                let container_port = container_port.parse::<i64>().ok();
+                let bindings = bindings.as_array().cloned().unwrap_or_default();
+                if bindings.is_empty() {
+                    ports.push(json!({ "container_port": container_port, "protocol": protocol }));
+                }
+                for binding in bindings {
+                    ports.push(json!({
+                        "container_port": container_port,
+                        "protocol": protocol,
+                        "host_ip": binding["HostIp"],
+                        "host_port": binding["HostPort"].as_str().and_then(|p| p.parse::<i64>().ok()),
+                    }));
+                }
+            }
+            let networks: Vec<Value> = c["NetworkSettings"]["Networks"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, network)| {
+                    json!({
+                        "name": name,
+                        "ip_address": network["IPAddress"],
+                        "gateway": network["Gateway"],
+                        "mac_address": network["MacAddress"],
+                    })
+                })
+                .collect();
+            json!({
+                "id": c["Id"],
+                "name": c["Name"].as_str().map(|n| n.trim_start_matches('/')),
+                "image": config["Image"],
+                "image_id": c["Image"],
+                "created": time(&c["Created"]),
+                "path": c["Path"],
+                "args": c["Args"],
+                "restart_count": c["RestartCount"],
+                "status": state["Status"],
+                "pid": state["Pid"].as_i64().filter(|pid| *pid > 0),
+                "state": {
+                    "status": state["Status"],
+                    "running": state["Running"],
+                    "paused": state["Paused"],
+                    "restarting": state["Restarting"],
+                    "oom_killed": state["OOMKilled"],
+                    "dead": state["Dead"],
+                    "pid": state["Pid"],
+                    "exit_code": state["ExitCode"],
+                    "error": text(state.as_object().unwrap_or(&Map::new()), "Error"),
+                    "started_at": time(&state["StartedAt"]),
+                    "finished_at": time(&state["FinishedAt"]),
+                },
+                "env": config["Env"],
+                "labels": config["Labels"],
+                "mounts": mounts,
+                "ports": ports,
+                "networks": networks,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_parsed_into_bytes() {
+        assert_eq!(parse_size("0B"), Some(0));
+        assert_eq!(parse_size("1.2kB"), Some(1200));
+        assert_eq!(parse_size("7.5MiB"), Some(7_864_320));
+        assert_eq!(parse_size("2GB"), Some(2_000_000_000));
+        assert_eq!(parse_size("1.5XB"), None);
+        assert_eq!(parse_size("--"), None);
+    }
+
+    #[test]
+    fn created_times_drop_the_zone_name() {
+        let micros = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").unwrap().timestamp_micros();
+        assert_eq!(parse_created(&json!("2024-01-02 03:04:05 +0000 UTC")), json!(micros));
+        assert_eq!(parse_created(&json!("2024-01-02 03:04:05 +0000")), json!(micros));
+        assert_eq!(parse_created(&json!("yesterday")), Value::Null);
+    }
+
+    #[test]
+    fn stats_are_numbers_and_bytes() {
+        let output = concat!(
+            r#"{"BlockIO":"4.1MB / 0B","CPUPerc":"0.25%","Container":"abc","ID":"abc","MemPerc":"1.50%","MemUsage":"7.5MiB / 1GiB","Name":"web","NetIO":"1.2kB / 648B","PIDs":"5"}"#,
+            "\n",
+            "WARNING: not JSON\n",
+        );
+        let stats = parse_docker_stats(output);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0]["cpu_percent"], json!(0.25));
+        assert_eq!(stats[0]["mem_used_bytes"], json!(7_864_320));
+        assert_eq!(stats[0]["mem_limit_bytes"], json!(1024 * 1024 * 1024));
+        assert_eq!(stats[0]["net_rx_bytes"], json!(1200));
+        assert_eq!(stats[0]["block_write_bytes"], json!(0));
+        assert_eq!(stats[0]["pids"], json!(5));
+    }
+
+    #[test]
+    fn placeholders_are_null() {
+        let output = r#"{"Repository":"<none>","Tag":"<none>","ID":"sha256:1","Digest":"<none>","CreatedAt":"2024-01-02 03:04:05 +0000 UTC","Size":"72.8MB"}"#;
+        let images = parse_docker_images(output);
+        assert_eq!(images[0]["repository"], Value::Null);
+        assert_eq!(images[0]["tag"], Value::Null);
+        assert_eq!(images[0]["id"], json!("sha256:1"));
+        assert_eq!(images[0]["size_bytes"], json!(72_800_000));
+    }
+
+    #[test]
+    fn inspect_times_left_at_year_one_are_null() {
+        let output = json!([{
+            "Id": "abc",
+            "Name": "/web",
+            "Created": "2024-01-02T03:04:05.123456789Z",
+            "State": { "Status": "created", "Pid": 0, "StartedAt": "0001-01-01T00:00:00Z" },
+            "Config": { "Image": "nginx" },
+            "NetworkSettings": { "Ports": { "80/tcp": [{ "HostIp": "0.0.0.0", "HostPort": "8080" }] } },
+        }]);
+        let containers = parse_docker_inspect(&output.to_string());
+        assert_eq!(containers[0]["name"], json!("web"));
+        assert_eq!(containers[0]["pid"], Value::Null);
+        assert_eq!(containers[0]["state"]["started_at"], Value::Null);
+        assert_eq!(
+            containers[0]["ports"],
+            json!([{ "container_port": 80, "protocol": "tcp", "host_ip": "0.0.0.0", "host_port": 8080 }])
+        );
+    }
+}
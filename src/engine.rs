@@ -1,78 +1,321 @@
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+use datafusion::dataframe::DataFrame;
+use datafusion::error::Result;
 use datafusion::execution::context::SessionContext;
-use datafusion_expr::ScalarUDF;
-
-use crate::commands::*;
-
-async fn set_up() -> std::io::Result<SessionContext> {
-    let ctx = SessionContext::new();
-    let host = ScalarUDF::from(Host::new());
-    ctx.register_udf(host.clone());
-    ctx.register_udtf("ps", ps_table_func());
-    ctx.register_udtf("uptime", uptime_table_func());
-    ctx.register_udtf("who", who_table_func());
-    ctx.register_udtf("ls", ls_table_func());
-    ctx.register_udtf("stat", stat_table_func());
-    ctx.register_udtf("df", df_table_func());
-    ctx.register_udtf("du", du_table_func());
-    ctx.register_udtf("blkid", blkid_table_func());
-    ctx.register_udtf("env", env_table_func());
-    ctx.register_udtf("date", date_table_func());
-    ctx.register_udtf("dir", dir_table_func());
-    ctx.register_udtf("dpkg_list", dpkg_list_table_func());
-    ctx.register_udtf("file", file_table_func());
-    ctx.register_udtf("find", find_table_func());
-    ctx.register_udtf("free", free_table_func());
-    Ok(ctx)
-}
-
-async fn eval_sql(ctx: &SessionContext, sql: String) -> std::io::Result<()> {
-    let result = ctx.sql(&sql).await;
-    match result {
-        Ok(df) => {
-            df.show().await.unwrap();
+use datafusion::common::plan_err;
+use datafusion::common::DFSchema;
+use datafusion::logical_expr::{DescribeTable, LogicalPlan};
+use datafusion::sql::parser::{DFParser, Statement as DFStatement};
+use datafusion::sql::sqlparser::dialect::dialect_from_str;
+use datafusion::sql::sqlparser::ast::{
+    Expr as SQLExpr, Function, FunctionArg, FunctionArgExpr, FunctionArgumentList,
+    FunctionArguments, Ident, ObjectName, Statement, TableFactor, Value, VisitMut, VisitorMut,
+};
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use futures::future::{try_join_all, BoxFuture};
+use futures::stream::{self, BoxStream, StreamExt};
+use futures::Future;
+use std::net::ToSocketAddrs;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use crate::context::{CommandTables, ZqueryContext};
+use crate::flight::serve_flight;
+use crate::http::serve_http;
+use crate::stream::parse_duration;
+use crate::pg::{load_users, serve_pg};
+use crate::tui::{load_dashboard, tui, Dashboard};
+use crate::watch::{load_rules, watch};
+
+/// A session with everything zquery provides, as the CLI and servers use it.
+pub async fn set_up() -> std::io::Result<SessionContext> {
+    Ok(ZqueryContext::new().build())
+}
+
+/// Plans a single statement like `SessionContext::sql`, but first rewrites
+/// `key => value` table function arguments into `named_arg('key', value)` calls since
+/// DataFusion would otherwise silently drop them.
+pub async fn sql_to_df(ctx: &SessionContext, sql: &str) -> Result<DataFrame> {
+    let state = ctx.state();
+    let dialect = state.config().options().sql_parser.dialect.clone();
+    let statement = state.sql_to_statement(sql, &dialect)?;
+    statement_to_df(ctx, statement).await
+}
+
+/// Parses a string that may hold several `;` separated statements.
+pub fn sql_to_statements(ctx: &SessionContext, sql: &str) -> Result<Vec<DFStatement>> {
+    let state = ctx.state();
+    let dialect_name = &state.config().options().sql_parser.dialect;
+    let dialect = match dialect_from_str(dialect_name) {
+        Some(dialect) => dialect,
+        None => return plan_err!("Unsupported SQL dialect: {}", dialect_name),
+    };
+    Ok(DFParser::parse_sql_with_dialect(sql, dialect.as_ref())?.into())
+}
+
+/// Plans a parsed statement without running it, rewriting named arguments the same
+/// way `sql_to_df` does.
+pub async fn statement_to_plan(ctx: &SessionContext, mut statement: DFStatement) -> Result<LogicalPlan> {
+    if let Some(plan) = describe_command_table(ctx, &statement) {
+        return Ok(plan);
+    }
+    rewrite_named_args(&mut statement);
+    ctx.state().statement_to_plan(statement).await
+}
+
+/// Runs `task` on a thread of its own, since command tables block while they're
+/// planned and run, rather than holding up the runtime's workers.
+pub(crate) async fn spawn_query<T: Send + 'static>(
+    task: impl Future<Output = T> + Send + 'static,
+) -> std::result::Result<T, tokio::task::JoinError> {
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || runtime.block_on(task)).await
+}
+
+/// Runs a plan on a thread of its own and forwards its batches as they're produced,
+/// for servers that stream results back. The query stops once the stream is dropped.
+pub(crate) async fn execute_streaming(
+    ctx: SessionContext,
+    plan: LogicalPlan,
+) -> Result<(SchemaRef, BoxStream<'static, Result<RecordBatch>>)> {
+    let (schema_tx, schema_rx) = tokio::sync::oneshot::channel();
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        runtime.block_on(async move {
+            let batches = match ctx.execute_logical_plan(plan).await {
+                Ok(df) => df.execute_stream().await,
+                Err(e) => Err(e),
+            };
+            let mut batches = match batches {
+                Ok(batches) => batches,
+                Err(e) => {
+                    let _ = schema_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = schema_tx.send(Ok(batches.schema()));
+            while let Some(batch) = batches.next().await {
+                if tx.send(batch).await.is_err() {
+                    break;
+                }
+            }
+        })
+    });
+    let schema = schema_rx
+        .await
+        .map_err(|e| datafusion::error::DataFusionError::External(Box::new(e)))??;
+    let batches = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|batch| (batch, rx)) });
+    Ok((schema, batches.boxed()))
+}
+
+/// Whether an `Authorization` header value is `Bearer <token>`. The token is compared
+/// in constant time, so how long a wrong guess takes to refuse doesn't say how much of
+/// it was right.
+pub(crate) fn bearer_token_matches(authorization: Option<&str>, token: &str) -> bool {
+    match authorization.and_then(|v| v.strip_prefix("Bearer ")) {
+        Some(given) => given.as_bytes().ct_eq(token.as_bytes()).into(),
+        None => false,
+    }
+}
+
+pub async fn statement_to_df(ctx: &SessionContext, statement: DFStatement) -> Result<DataFrame> {
+    let plan = statement_to_plan(ctx, statement).await?;
+    ctx.execute_logical_plan(plan).await
+}
+
+/// `describe ps` lists a command table's columns like it would for a table in the
+/// session, which DataFusion can't do itself since `ps` is only a table function.
+fn describe_command_table(ctx: &SessionContext, statement: &DFStatement) -> Option<LogicalPlan> {
+    let table_name = match statement {
+        DFStatement::Statement(statement) => match statement.as_ref() {
+            Statement::ExplainTable { table_name, .. } => table_name,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let [name] = table_name.0.as_slice() else {
+        return None;
+    };
+    let tables = CommandTables::of(ctx.state().config());
+    let func = tables.get(&normalize_ident(name))?;
+    let output_schema = DFSchema::try_from(LogicalPlan::describe_schema()).ok()?;
+    Some(LogicalPlan::DescribeTable(DescribeTable {
+        schema: func.schema.clone(),
+        output_schema: Arc::new(output_schema),
+    }))
+}
+
+/// A name the way DataFusion looks it up: lowercased, unless it was quoted, in which
+/// case it's taken as written.
+pub(crate) fn normalize_ident(ident: &Ident) -> String {
+    match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_ascii_lowercase(),
+    }
+}
+
+fn rewrite_named_args(statement: &mut DFStatement) {
+    match statement {
+        DFStatement::Statement(statement) => {
+            let _ = statement.visit(&mut NamedTableArgs);
         }
-        Err(e) => {
-            println!("Error: {:?}", e);
+        DFStatement::Explain(explain) => rewrite_named_args(&mut explain.statement),
+        _ => {}
+    }
+}
+
+struct NamedTableArgs;
+
+impl VisitorMut for NamedTableArgs {
+    type Break = ();
+
+    fn pre_visit_table_factor(&mut self, table_factor: &mut TableFactor) -> ControlFlow<()> {
+        if let TableFactor::Table { args: Some(args), .. } = table_factor {
+            for arg in args.iter_mut() {
+                if let FunctionArg::Named { name, arg: FunctionArgExpr::Expr(value), .. } = arg {
+                    let call = SQLExpr::Function(Function {
+                        name: ObjectName(vec![Ident::new("named_arg")]),
+                        parameters: FunctionArguments::None,
+                        args: FunctionArguments::List(FunctionArgumentList {
+                            duplicate_treatment: None,
+                            args: vec![
+                                FunctionArg::Unnamed(FunctionArgExpr::Expr(SQLExpr::Value(
+                                    Value::SingleQuotedString(name.value.clone()),
+                                ))),
+                                FunctionArg::Unnamed(FunctionArgExpr::Expr(value.clone())),
+                            ],
+                            clauses: vec![],
+                        }),
+                        filter: None,
+                        null_treatment: None,
+                        over: None,
+                        within_group: vec![],
+                    });
+                    *arg = FunctionArg::Unnamed(FunctionArgExpr::Expr(call));
+                }
+            }
         }
+        ControlFlow::Continue(())
     }
-    Ok(())
 }
-pub async fn cli_eval(sql: String) -> std::io::Result<()> {
-    let ctx = set_up().await.unwrap();
-    eval_sql(&ctx, sql).await.unwrap();
+
+pub async fn cli_watch(rules: PathBuf) -> std::io::Result<()> {
+    let rules = load_rules(&rules)?;
+    let ctx = set_up().await?;
+    watch(ctx, rules).await;
     Ok(())
 }
 
-pub async fn cli_repl() -> std::io::Result<()> {
-    let ctx = set_up().await.unwrap();
+pub async fn cli_tui(dashboard: Option<PathBuf>) -> std::io::Result<()> {
+    let dashboard = match dashboard {
+        Some(path) => load_dashboard(&path)?,
+        None => Dashboard::default(),
+    };
+    let ctx = set_up().await?;
+    tui(ctx, dashboard).await
+}
 
-    let mut rl = DefaultEditor::new().unwrap();
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
+/// Addresses like `:8080` listen on every interface.
+fn listen_addr(addr: &str) -> String {
+    match addr.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{}", port),
+        None => addr.to_string(),
     }
-    loop {
-        let readline = rl.readline(">> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(line.as_str()).unwrap();
-                eval_sql(&ctx, line).await.unwrap();
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+}
+
+/// Where to serve HTTP or Flight SQL without a `--token`. Anyone who can connect can run
+/// DDL and read files, so `:8080` only listens on loopback and other addresses have to
+/// be loopback ones.
+fn unauthenticated_addr(addr: &str) -> std::io::Result<String> {
+    let addr = match addr.strip_prefix(':') {
+        Some(port) => format!("127.0.0.1:{}", port),
+        None => addr.to_string(),
+    };
+    if !addr.to_socket_addrs()?.all(|a| a.ip().is_loopback()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Refusing to serve {} without a --token, anyone who could reach it could run any SQL", addr),
+        ));
+    }
+    Ok(addr)
+}
+
+pub async fn cli_serve(
+    pg: Option<String>,
+    users: Option<PathBuf>,
+    http: Option<String>,
+    flight: Option<String>,
+    token: Option<String>,
+    timeout: String,
+) -> std::io::Result<()> {
+    let timeout = match parse_duration(&timeout) {
+        Some(timeout) => timeout,
+        None => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Could not parse '{}' as a duration", timeout),
+            ))
         }
+    };
+    let mut servers: Vec<BoxFuture<std::io::Result<()>>> = Vec::new();
+    if let (Some(addr), Some(users)) = (pg, users) {
+        let users = load_users(&users)?;
+        servers.push(Box::pin(async move { serve_pg(&listen_addr(&addr), users).await }));
+    }
+    let token_addr = |addr: &str| match token {
+        Some(_) => Ok(listen_addr(addr)),
+        None => unauthenticated_addr(addr),
+    };
+    if let Some(addr) = http {
+        let (addr, token) = (token_addr(&addr)?, token.clone());
+        servers.push(Box::pin(async move { serve_http(&addr, token, timeout, ZqueryContext::new()).await }));
     }
-    rl.save_history("history.txt").unwrap();
+    if let Some(addr) = flight {
+        let (addr, token) = (token_addr(&addr)?, token.clone());
+        servers.push(Box::pin(async move { serve_flight(&addr, token, ZqueryContext::new()).await }));
+    }
+    if servers.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Nothing to serve, pass --pg <addr> --users <file>, --http <addr> or --flight <addr>",
+        ));
+    }
+    try_join_all(servers).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bearer_tokens_have_to_match_exactly() {
+        let cases = [
+            (Some("Bearer s3cret"), true),
+            (Some("Bearer s3cre"), false),
+            (Some("Bearer s3cret2"), false),
+            (Some("Bearer S3CRET"), false),
+            (Some("bearer s3cret"), false),
+            (Some("s3cret"), false),
+            (Some("Bearer "), false),
+            (None, false),
+        ];
+        for (authorization, expected) in cases {
+            assert_eq!(bearer_token_matches(authorization, "s3cret"), expected, "{:?}", authorization);
+        }
+    }
+
+    #[test]
+    fn servers_without_a_token_only_listen_on_loopback() {
+        assert_eq!(unauthenticated_addr(":8080").unwrap(), "127.0.0.1:8080");
+        assert_eq!(unauthenticated_addr("[::1]:8080").unwrap(), "[::1]:8080");
+        let error = unauthenticated_addr("0.0.0.0:8080").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("without a --token"), "{}", error);
+        assert_eq!(listen_addr(":8080"), "0.0.0.0:8080");
+    }
+}
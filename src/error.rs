@@ -0,0 +1,224 @@
+use datafusion::arrow::error::ArrowError;
+use datafusion::error::DataFusionError;
+use std::fmt;
+
+/// Why a query failed, so `--eval` can exit with a code scripts can tell apart.
+#[derive(Debug)]
+pub enum ZqueryError {
+    /// The SQL didn't parse or plan, or DataFusion failed running it.
+    Sql(DataFusionError),
+    /// A command (or `jc`/`jq`) couldn't be started or exited non-zero, with what it
+    /// wrote to stderr.
+    Command(String),
+    /// A command's output couldn't be parsed into rows.
+    Parse(String),
+    /// Reading `~/.ssh/config`, connecting or authenticating to a host failed.
+    Ssh(String),
+    /// Some hosts in a `hosts()` fan-out failed, with `--strict`, or all of them did.
+    FanOut { failed: Vec<String>, total: usize },
+    /// Statements in a `--continue-on-error` script failed. Exits with the first one's code.
+    Statements {
+        failed: usize,
+        total: usize,
+        first: Box<ZqueryError>,
+    },
+    /// A bad `\` command in a script or the REPL.
+    Usage(String),
+    Io(std::io::Error),
+}
+
+impl ZqueryError {
+    /// 2 is for bad arguments, which clap also uses for the command line.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ZqueryError::Io(_) => 1,
+            ZqueryError::Usage(_) => 2,
+            ZqueryError::Sql(_) => 3,
+            ZqueryError::Command(_) => 4,
+            ZqueryError::Parse(_) => 5,
+            ZqueryError::Ssh(_) => 6,
+            ZqueryError::FanOut { .. } => 7,
+            ZqueryError::Statements { first, .. } => first.exit_code(),
+        }
+    }
+
+    /// A copy of an error found behind a reference. io and DataFusion errors aren't
+    /// Clone, so those keep only their message.
+    fn clone_category(&self) -> ZqueryError {
+        match self {
+            ZqueryError::Sql(e) => ZqueryError::Sql(DataFusionError::Execution(e.to_string())),
+            ZqueryError::Command(e) => ZqueryError::Command(e.clone()),
+            ZqueryError::Parse(e) => ZqueryError::Parse(e.clone()),
+            ZqueryError::Ssh(e) => ZqueryError::Ssh(e.clone()),
+            ZqueryError::FanOut { failed, total } => ZqueryError::FanOut {
+                failed: failed.clone(),
+                total: *total,
+            },
+            ZqueryError::Statements { failed, total, first } => ZqueryError::Statements {
+                failed: *failed,
+                total: *total,
+                first: Box::new(first.clone_category()),
+            },
+            ZqueryError::Usage(e) => ZqueryError::Usage(e.clone()),
+            ZqueryError::Io(e) => ZqueryError::Io(std::io::Error::new(e.kind(), e.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ZqueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZqueryError::Sql(e) => write!(f, "{}", e),
+            ZqueryError::Command(e) => write!(f, "Command failed: {}", e),
+            ZqueryError::Parse(e) => write!(f, "Failed to parse command output: {}", e),
+            ZqueryError::Ssh(e) => write!(f, "ssh: {}", e),
+            ZqueryError::FanOut { failed, total } => write!(
+                f,
+                "Failed on {} of {} hosts: {}",
+                failed.len(),
+                total,
+                failed.join(", ")
+            ),
+            ZqueryError::Statements { failed, total, .. } => {
+                write!(f, "{} of {} statements failed", failed, total)
+            }
+            ZqueryError::Usage(e) => write!(f, "{}", e),
+            ZqueryError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ZqueryError {}
+
+impl From<std::io::Error> for ZqueryError {
+    fn from(e: std::io::Error) -> Self {
+        ZqueryError::Io(e)
+    }
+}
+
+impl From<ssh2::Error> for ZqueryError {
+    fn from(e: ssh2::Error) -> Self {
+        ZqueryError::Ssh(e.to_string())
+    }
+}
+
+/// Command tables fail inside DataFusion, which carries the error along as an
+/// `External` one, so dig it back out to keep its category.
+impl From<DataFusionError> for ZqueryError {
+    fn from(e: DataFusionError) -> Self {
+        let mut current = &e;
+        loop {
+            match current {
+                DataFusionError::Context(_, inner) => current = inner,
+                DataFusionError::IoError(inner) => {
+                    if let Some(found) = inner.get_ref().and_then(|e| e.downcast_ref::<ZqueryError>()) {
+                        return found.clone_category();
+                    }
+                    break;
+                }
+                DataFusionError::External(inner)
+                | DataFusionError::ArrowError(ArrowError::ExternalError(inner), _) => {
+                    if let Some(found) = inner.downcast_ref::<ZqueryError>() {
+                        return found.clone_category();
+                    }
+                    if let Some(inner) = inner.downcast_ref::<DataFusionError>() {
+                        current = inner;
+                    } else {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+        ZqueryError::Sql(e)
+    }
+}
+
+impl From<ZqueryError> for DataFusionError {
+    fn from(e: ZqueryError) -> Self {
+        match e {
+            ZqueryError::Sql(e) => e,
+            e => DataFusionError::External(Box::new(e)),
+        }
+    }
+}
+
+impl From<ZqueryError> for std::io::Error {
+    fn from(e: ZqueryError) -> Self {
+        match e {
+            ZqueryError::Io(e) => e,
+            e => std::io::Error::other(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commandtable::{CommandTableFunc, Parser};
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    #[test]
+    fn each_category_exits_with_its_own_code() {
+        let cases = [
+            (ZqueryError::Io(std::io::Error::other("disk full")), 1),
+            (ZqueryError::Usage("\\nope".into()), 2),
+            (ZqueryError::Sql(DataFusionError::Plan("no such table".into())), 3),
+            (ZqueryError::Command("ps: it broke".into()), 4),
+            (ZqueryError::Parse("not json".into()), 5),
+            (ZqueryError::Ssh("connection refused".into()), 6),
+            (ZqueryError::FanOut { failed: vec!["web2".into()], total: 3 }, 7),
+            (
+                ZqueryError::Statements { failed: 2, total: 5, first: Box::new(ZqueryError::Ssh("x".into())) },
+                6,
+            ),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.exit_code(), code, "{}", error);
+        }
+    }
+
+    #[test]
+    fn categories_survive_being_carried_through_datafusion() {
+        let command = || ZqueryError::Command("ps: it broke".into());
+        let cases = [
+            DataFusionError::from(command()),
+            DataFusionError::Context("scan".into(), Box::new(command().into())),
+            DataFusionError::ArrowError(ArrowError::ExternalError(Box::new(command())), None),
+            DataFusionError::IoError(std::io::Error::other(command())),
+            DataFusionError::External(Box::new(DataFusionError::from(command()))),
+        ];
+        for error in cases {
+            let message = error.to_string();
+            let error = ZqueryError::from(error);
+            assert_eq!(error.exit_code(), 4, "{}", message);
+            assert_eq!(error.to_string(), "Command failed: ps: it broke");
+        }
+        let error = ZqueryError::from(DataFusionError::Execution("division by zero".into()));
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[tokio::test]
+    async fn a_failing_command_table_exits_as_a_command_failure() {
+        let broken = CommandTableFunc {
+            command: vec!["sh", "-c", "echo it broke >&2; exit 1", "broken"],
+            parser: Parser::Native(|_| Vec::new()),
+            args: "",
+            description: "",
+            platforms: &["linux"],
+            schema: Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, true)])),
+            is_result_array: false,
+        };
+        let ctx = ZqueryContext::new().without_builtin_tables().with_command_table("broken", broken).build();
+        let result = match sql_to_df(&ctx, "select * from broken()").await {
+            Ok(df) => df.collect().await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        let error = ZqueryError::from(result.unwrap_err());
+        assert_eq!(error.exit_code(), 4, "{}", error);
+        assert!(error.to_string().ends_with("it broke"), "{}", error);
+    }
+}
@@ -0,0 +1,537 @@
+// tonic::Status is what the Flight service traits return, large or not.
+#![allow(clippy::result_large_err)]
+
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::sql::metadata::{SqlInfoData, SqlInfoDataBuilder};
+use arrow_flight::sql::server::FlightSqlService;
+use arrow_flight::sql::{
+    ActionClosePreparedStatementRequest, ActionCreatePreparedStatementRequest,
+    ActionCreatePreparedStatementResult, CommandGetCatalogs, CommandGetDbSchemas,
+    CommandGetSqlInfo, CommandGetTableTypes, CommandGetTables, CommandPreparedStatementQuery,
+    CommandStatementQuery, ProstMessageExt, SqlInfo, TicketStatementQuery,
+};
+use arrow_flight::{
+    Action, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, IpcMessage, PollInfo, SchemaAsIpc, SchemaResult, Ticket,
+};
+use datafusion::arrow::array::{ArrayRef, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::ipc::writer::IpcWriteOptions;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::DataFusionError;
+use datafusion::execution::context::SessionContext;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::sql::parser::Statement as DFStatement;
+use datafusion::sql::sqlparser::ast::{SetExpr, Statement};
+use futures::{stream, TryStreamExt};
+use prost::Message;
+use std::sync::{Arc, OnceLock};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::context::{CommandTables, ZqueryContext};
+use crate::engine::{bearer_token_matches, execute_streaming, spawn_query, sql_to_statements, statement_to_plan};
+
+const CATALOG: &str = "datafusion";
+const SCHEMA: &str = "public";
+/// Command tables are called like functions, so clients shouldn't treat them as
+/// plain tables they can `select * from`.
+const TABLE_TYPE: &str = "TABLE FUNCTION";
+
+/// Serves Arrow Flight SQL on `addr`, running each query in a fresh session built from
+/// `context`. Queries stream the batches DataFusion produces straight to the client.
+pub async fn serve_flight(addr: &str, token: Option<String>, context: ZqueryContext) -> std::io::Result<()> {
+    let addr = addr
+        .parse()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let service = ZqueryFlight(ZqueryFlightService { context });
+    let service = FlightServiceServer::with_interceptor(service, move |request| {
+        check_token(request, token.as_deref())
+    });
+    eprintln!("Listening for Flight SQL requests on {}", addr);
+    Server::builder()
+        .add_service(service)
+        .serve(addr)
+        .await
+        .map_err(std::io::Error::other)
+}
+
+fn check_token(request: Request<()>, token: Option<&str>) -> Result<Request<()>, Status> {
+    if let Some(token) = token {
+        let given = request.metadata().get("authorization").and_then(|v| v.to_str().ok());
+        if !bearer_token_matches(given, token) {
+            return Err(Status::unauthenticated("Missing or wrong bearer token"));
+        }
+    }
+    Ok(request)
+}
+
+fn status(e: DataFusionError) -> Status {
+    match e {
+        DataFusionError::SQL(..) | DataFusionError::Plan(_) | DataFusionError::SchemaError(..) => {
+            Status::invalid_argument(e.to_string())
+        }
+        _ => Status::internal(e.to_string()),
+    }
+}
+
+fn sql_info() -> &'static SqlInfoData {
+    static INFO: OnceLock<SqlInfoData> = OnceLock::new();
+    INFO.get_or_init(|| {
+        let mut builder = SqlInfoDataBuilder::new();
+        builder.append(SqlInfo::FlightSqlServerName, "zquery");
+        builder.append(SqlInfo::FlightSqlServerVersion, env!("CARGO_PKG_VERSION"));
+        builder.append(SqlInfo::FlightSqlServerArrowVersion, "1.3");
+        builder.append(SqlInfo::FlightSqlServerReadOnly, true);
+        builder.build().expect("Failed to build SqlInfo")
+    })
+}
+
+/// Parses the single statement in `sql`, turning away anything that isn't a query
+/// since the server tells clients it's read-only.
+fn query_statement(ctx: &SessionContext, sql: &str) -> Result<DFStatement, Status> {
+    let mut statements = sql_to_statements(ctx, sql).map_err(status)?;
+    if statements.len() != 1 {
+        return Err(Status::invalid_argument("Expected exactly one statement"));
+    }
+    let statement = statements.remove(0);
+    if !is_query(&statement) {
+        return Err(Status::permission_denied("Flight SQL is read-only, only queries can be run"));
+    }
+    Ok(statement)
+}
+
+/// Whether the statement only reads, unlike DDL, `COPY ... TO` or `select ... into`.
+fn is_query(statement: &DFStatement) -> bool {
+    match statement {
+        DFStatement::Statement(statement) => match statement.as_ref() {
+            Statement::Query(query) => {
+                !matches!(query.body.as_ref(), SetExpr::Select(select) if select.into.is_some())
+            }
+            Statement::ExplainTable { .. } | Statement::ShowTables { .. } | Statement::ShowColumns { .. } => true,
+            _ => false,
+        },
+        DFStatement::Explain(explain) => is_query(&explain.statement),
+        _ => false,
+    }
+}
+
+/// Plans a single query on a thread of its own, returning the session it's for.
+async fn plan_query(context: ZqueryContext, sql: String) -> Result<(SessionContext, LogicalPlan), Status> {
+    spawn_query(async move {
+        let ctx = context.build();
+        let statement = query_statement(&ctx, &sql)?;
+        let plan = statement_to_plan(&ctx, statement).await.map_err(status)?;
+        Ok((ctx, plan))
+    })
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?
+}
+
+/// Plans a single query without running it to find the schema of its results.
+async fn result_schema(context: ZqueryContext, sql: String) -> Result<Schema, Status> {
+    let (_, plan) = plan_query(context, sql).await?;
+    Ok(plan.schema().as_arrow().clone())
+}
+
+/// A FlightInfo whose single endpoint hands `ticket` back to `do_get`.
+fn flight_info(
+    ticket: impl ProstMessageExt,
+    schema: &Schema,
+    descriptor: FlightDescriptor,
+) -> Result<Response<FlightInfo>, Status> {
+    let endpoint = FlightEndpoint::new().with_ticket(Ticket::new(ticket.as_any().encode_to_vec()));
+    let info = FlightInfo::new()
+        .try_with_schema(schema)
+        .map_err(|e| Status::internal(e.to_string()))?
+        .with_endpoint(endpoint)
+        .with_descriptor(descriptor);
+    Ok(Response::new(info))
+}
+
+type DoGetStream = <ZqueryFlightService as FlightService>::DoGetStream;
+
+fn batch_response(
+    schema: SchemaRef,
+    batch: Result<RecordBatch, FlightError>,
+) -> Result<Response<DoGetStream>, Status> {
+    let stream = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(stream::once(async { batch }))
+        .map_err(Status::from);
+    Ok(Response::new(Box::pin(stream)))
+}
+
+/// Runs the query and streams its batches to the client as they're produced. The query
+/// stops once the client stops reading.
+async fn query_response(context: ZqueryContext, sql: String) -> Result<Response<DoGetStream>, Status> {
+    let (ctx, plan) = plan_query(context, sql).await?;
+    let (schema, batches) = execute_streaming(ctx, plan).await.map_err(status)?;
+    let batches = batches.map_err(|e| FlightError::ExternalError(Box::new(e)));
+    let stream = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(batches)
+        .map_err(Status::from);
+    Ok(Response::new(Box::pin(stream)))
+}
+
+fn handle_to_sql(handle: &[u8]) -> Result<String, Status> {
+    String::from_utf8(handle.to_vec()).map_err(|e| Status::invalid_argument(e.to_string()))
+}
+
+/// Statement and prepared statement handles are just the SQL itself, since every
+/// query gets a fresh session and there's nothing else to keep between calls.
+#[derive(Clone)]
+struct ZqueryFlightService {
+    context: ZqueryContext,
+}
+
+#[tonic::async_trait]
+impl FlightSqlService for ZqueryFlightService {
+    type FlightService = ZqueryFlightService;
+
+    async fn get_flight_info_statement(
+        &self,
+        query: CommandStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = result_schema(self.context.clone(), query.query.clone()).await?;
+        let ticket = TicketStatementQuery {
+            statement_handle: query.query.into_bytes().into(),
+        };
+        flight_info(ticket, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_prepared_statement(
+        &self,
+        query: CommandPreparedStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let sql = handle_to_sql(&query.prepared_statement_handle)?;
+        let schema = result_schema(self.context.clone(), sql).await?;
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_table_types(
+        &self,
+        query: CommandGetTableTypes,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        flight_info(query, &table_types_schema(), request.into_inner())
+    }
+
+    async fn get_flight_info_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder(sql_info()).schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_statement(
+        &self,
+        ticket: TicketStatementQuery,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        query_response(self.context.clone(), handle_to_sql(&ticket.statement_handle)?).await
+    }
+
+    async fn do_get_prepared_statement(
+        &self,
+        query: CommandPreparedStatementQuery,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        query_response(self.context.clone(), handle_to_sql(&query.prepared_statement_handle)?).await
+    }
+
+    async fn do_get_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG);
+        batch_response(builder.schema(), builder.build())
+    }
+
+    async fn do_get_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG, SCHEMA);
+        batch_response(builder.schema(), builder.build())
+    }
+
+    /// Lists the session's command tables, with their columns when the client asks for
+    /// them.
+    async fn do_get_tables(
+        &self,
+        query: CommandGetTables,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let ctx = self.context.clone().build();
+        let mut builder = query.into_builder();
+        for (name, func) in CommandTables::of(ctx.state().config()).0 {
+            builder
+                .append(CATALOG, SCHEMA, name, TABLE_TYPE, &func.schema)
+                .map_err(Status::from)?;
+        }
+        batch_response(builder.schema(), builder.build())
+    }
+
+    async fn do_get_table_types(
+        &self,
+        _query: CommandGetTableTypes,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let schema = Arc::new(table_types_schema());
+        let types: ArrayRef = Arc::new(StringArray::from(vec![TABLE_TYPE]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![types]).map_err(FlightError::Arrow);
+        batch_response(schema, batch)
+    }
+
+    async fn do_get_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let builder = query.into_builder(sql_info());
+        batch_response(builder.schema(), builder.build())
+    }
+
+    async fn do_action_create_prepared_statement(
+        &self,
+        query: ActionCreatePreparedStatementRequest,
+        _request: Request<Action>,
+    ) -> Result<ActionCreatePreparedStatementResult, Status> {
+        let schema = result_schema(self.context.clone(), query.query.clone()).await?;
+        let IpcMessage(dataset_schema) = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|e: datafusion::arrow::error::ArrowError| Status::internal(e.to_string()))?;
+        Ok(ActionCreatePreparedStatementResult {
+            prepared_statement_handle: query.query.into_bytes().into(),
+            dataset_schema,
+            parameter_schema: Default::default(),
+        })
+    }
+
+    async fn do_action_close_prepared_statement(
+        &self,
+        _query: ActionClosePreparedStatementRequest,
+        _request: Request<Action>,
+    ) -> Result<(), Status> {
+        Ok(())
+    }
+
+    async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
+}
+
+fn table_types_schema() -> Schema {
+    Schema::new(vec![Field::new("table_type", DataType::Utf8, false)])
+}
+
+/// arrow-flight answers everything but GetSchema for a `FlightSqlService`, so this
+/// answers that from the FlightInfo and hands the rest to it.
+#[derive(Clone)]
+struct ZqueryFlight(ZqueryFlightService);
+
+#[tonic::async_trait]
+impl FlightService for ZqueryFlight {
+    type HandshakeStream = <ZqueryFlightService as FlightService>::HandshakeStream;
+    type ListFlightsStream = <ZqueryFlightService as FlightService>::ListFlightsStream;
+    type DoGetStream = DoGetStream;
+    type DoPutStream = <ZqueryFlightService as FlightService>::DoPutStream;
+    type DoExchangeStream = <ZqueryFlightService as FlightService>::DoExchangeStream;
+    type DoActionStream = <ZqueryFlightService as FlightService>::DoActionStream;
+    type ListActionsStream = <ZqueryFlightService as FlightService>::ListActionsStream;
+
+    async fn handshake(
+        &self,
+        request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        FlightService::handshake(&self.0, request).await
+    }
+
+    async fn list_flights(
+        &self,
+        request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        FlightService::list_flights(&self.0, request).await
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        FlightService::get_flight_info(&self.0, request).await
+    }
+
+    async fn poll_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        FlightService::poll_flight_info(&self.0, request).await
+    }
+
+    /// The schema a FlightInfo carries is encoded the same way GetSchema's is.
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        let info = FlightService::get_flight_info(&self.0, request).await?.into_inner();
+        Ok(Response::new(SchemaResult { schema: info.schema }))
+    }
+
+    async fn do_get(&self, request: Request<Ticket>) -> Result<Response<Self::DoGetStream>, Status> {
+        FlightService::do_get(&self.0, request).await
+    }
+
+    async fn do_put(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        FlightService::do_put(&self.0, request).await
+    }
+
+    async fn do_exchange(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        FlightService::do_exchange(&self.0, request).await
+    }
+
+    async fn do_action(&self, request: Request<Action>) -> Result<Response<Self::DoActionStream>, Status> {
+        FlightService::do_action(&self.0, request).await
+    }
+
+    async fn list_actions(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        FlightService::list_actions(&self.0, request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commandtable::{CommandTableFunc, Parser};
+    use arrow_flight::sql::client::FlightSqlServiceClient;
+    use arrow_flight::FlightClient;
+    use datafusion::arrow::array::AsArray;
+    use serde_json::json;
+    use tonic::transport::Channel;
+
+    fn greetings() -> CommandTableFunc {
+        CommandTableFunc {
+            command: vec!["sh", "-c", "echo hello; echo world", "greetings"],
+            parser: Parser::Native(|output| output.lines().map(|line| json!({ "line": line })).collect()),
+            args: "",
+            description: "",
+            platforms: &["linux"],
+            schema: Arc::new(Schema::new(vec![Field::new("line", DataType::Utf8, true)])),
+            is_result_array: false,
+        }
+    }
+
+    /// Serves a session whose only command table is `greetings()`.
+    async fn serve() -> Channel {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let context = ZqueryContext::new().without_builtin_tables().with_command_table("greetings", greetings());
+        tokio::spawn(async move { serve_flight(&format!("127.0.0.1:{}", port), None, context).await });
+        for _ in 0..50 {
+            if let Ok(channel) = Channel::from_shared(format!("http://127.0.0.1:{}", port)).unwrap().connect().await {
+                return channel;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("couldn't connect to the Flight SQL server on port {}", port);
+    }
+
+    async fn fetch(client: &mut FlightSqlServiceClient<Channel>, info: FlightInfo) -> Vec<RecordBatch> {
+        let ticket = info.endpoint[0].ticket.clone().unwrap();
+        client.do_get(ticket).await.unwrap().try_collect().await.unwrap()
+    }
+
+    fn strings(batches: &[RecordBatch], column: &str) -> Vec<String> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                let values = batch.column_by_name(column).unwrap().as_string::<i32>();
+                values.iter().map(|v| v.unwrap().to_string()).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn a_query_and_its_schema_round_trip() {
+        let channel = serve().await;
+        let mut client = FlightSqlServiceClient::new(channel.clone());
+        let sql = "select line from greetings() order by line";
+        let info = client.execute(sql.to_string(), None).await.unwrap();
+        assert_eq!(strings(&fetch(&mut client, info).await, "line"), ["hello", "world"]);
+
+        let query = CommandStatementQuery { query: sql.to_string(), transaction_id: None };
+        let descriptor = FlightDescriptor::new_cmd(query.as_any().encode_to_vec());
+        let schema = FlightClient::new(channel).get_schema(descriptor).await.unwrap();
+        assert_eq!(schema.field(0).name(), "line");
+    }
+
+    #[tokio::test]
+    async fn get_tables_lists_the_sessions_command_tables() {
+        let mut client = FlightSqlServiceClient::new(serve().await);
+        let query = CommandGetTables { include_schema: true, ..Default::default() };
+        let info = client.get_tables(query).await.unwrap();
+        let batches = fetch(&mut client, info).await;
+        assert_eq!(strings(&batches, "table_name"), ["greetings"]);
+        assert_eq!(strings(&batches, "table_type"), [TABLE_TYPE]);
+    }
+
+    #[tokio::test]
+    async fn statements_that_write_are_refused() {
+        let mut client = FlightSqlServiceClient::new(serve().await);
+        let path = std::env::temp_dir().join(format!("zquery-flight-{}.csv", std::process::id()));
+        for sql in [
+            format!("copy (select 1) to '{}'", path.display()),
+            "create table t as select 1".to_string(),
+            "select 1 into t".to_string(),
+            "explain analyze create table t as select 1".to_string(),
+        ] {
+            let error = client.execute(sql.clone(), None).await.unwrap_err();
+            assert!(error.to_string().contains("read-only"), "{}: {}", sql, error);
+        }
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,347 @@
+use datafusion::arrow::array::{Array, ArrayRef, StringArray};
+use datafusion::arrow::csv::WriterBuilder as CsvWriterBuilder;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::json::{writer::JsonArray, writer::LineDelimited, WriterBuilder};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::error::Result;
+use datafusion::parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The formats query results can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// An ASCII table, the default for the CLI.
+    Table,
+    /// A JSON array of objects, one per row.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    Csv,
+    Tsv,
+    Markdown,
+    Parquet,
+    /// An Arrow IPC stream.
+    Arrow,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "ndjson" | "jsonl" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "parquet" => Ok(Format::Parquet),
+            "arrow" => Ok(Format::Arrow),
+            _ => Err(format!(
+                "Unknown format '{}', expected table, csv, tsv, json, ndjson, markdown, parquet or arrow",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Picks the format for a MIME type like those in an `Accept` header.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime.split(';').next().unwrap_or_default().trim() {
+            "application/json" | "*/*" | "application/*" => Some(Format::Json),
+            "application/x-ndjson" | "application/jsonl" => Some(Format::Ndjson),
+            "text/csv" => Some(Format::Csv),
+            "text/tab-separated-values" => Some(Format::Tsv),
+            "text/markdown" => Some(Format::Markdown),
+            "text/plain" => Some(Format::Table),
+            "application/vnd.apache.parquet" => Some(Format::Parquet),
+            "application/vnd.apache.arrow.stream" => Some(Format::Arrow),
+            _ => None,
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            Format::Table => "text/plain",
+            Format::Json => "application/json",
+            Format::Ndjson => "application/x-ndjson",
+            Format::Csv => "text/csv",
+            Format::Tsv => "text/tab-separated-values",
+            Format::Markdown => "text/markdown",
+            Format::Parquet => "application/vnd.apache.parquet",
+            Format::Arrow => "application/vnd.apache.arrow.stream",
+        }
+    }
+
+    /// Whether results can be written a batch at a time as a stream produces them.
+    /// Parquet and Arrow files only make sense once a query has finished.
+    pub fn is_streamable(&self) -> bool {
+        !matches!(self, Format::Parquet | Format::Arrow)
+    }
+}
+
+/// How the CLI prints results, from `--format`, `--max-width` and `--output`.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub format: Format,
+    pub options: WriteOptions,
+    /// Write to this file instead of stdout.
+    pub path: Option<PathBuf>,
+}
+
+impl Output {
+    /// Opens `--output` for writing, or stdout when there isn't one.
+    pub fn open(&self) -> std::io::Result<Box<dyn Write + Send>> {
+        Ok(match &self.path {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(std::io::stdout()),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Cut off table and markdown cells longer than this.
+    pub max_width: Option<usize>,
+    /// Whether CSV and TSV start with a header row.
+    pub header: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            max_width: None,
+            header: true,
+        }
+    }
+}
+
+/// Writes batches in the given format. JSON keeps numbers as numbers and writes nulls
+/// out rather than dropping the key.
+pub fn write_batches<W: Write + Send>(
+    mut out: W,
+    schema: &Schema,
+    batches: &[RecordBatch],
+    format: Format,
+    options: &WriteOptions,
+) -> Result<()> {
+    match format {
+        Format::Table => {
+            let batches = truncated(batches, options.max_width)?;
+            writeln!(out, "{}", pretty_format_batches(&batches)?)?;
+        }
+        // The JSON array writer writes nothing at all when there are no rows.
+        Format::Json if batches.iter().all(|b| b.num_rows() == 0) => {
+            out.write_all(b"[]")?;
+        }
+        Format::Json => {
+            let mut writer = WriterBuilder::new()
+                .with_explicit_nulls(true)
+                .build::<_, JsonArray>(out);
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+        }
+        Format::Ndjson => {
+            let mut writer = WriterBuilder::new()
+                .with_explicit_nulls(true)
+                .build::<_, LineDelimited>(out);
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+        }
+        Format::Csv | Format::Tsv => {
+            let delimiter = if format == Format::Tsv { b'\t' } else { b',' };
+            let mut writer = CsvWriterBuilder::new()
+                .with_header(options.header)
+                .with_delimiter(delimiter)
+                .build(out);
+            for batch in batches {
+                writer.write(batch)?;
+            }
+        }
+        Format::Markdown => write_markdown(out, schema, batches, options.max_width)?,
+        Format::Parquet => {
+            let mut writer = ArrowWriter::try_new(out, Arc::new(schema.clone()), None)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.close()?;
+        }
+        Format::Arrow => {
+            let mut writer = StreamWriter::try_new(out, schema)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Formats every value of a column as text, cutting off any longer than `max_width`.
+fn cells(array: &ArrayRef, max_width: Option<usize>) -> Result<Vec<Option<String>>> {
+    let formatter = ArrayFormatter::try_new(array.as_ref(), &FormatOptions::default())?;
+    Ok((0..array.len())
+        .map(|i| {
+            if array.is_null(i) {
+                return None;
+            }
+            let value = formatter.value(i).to_string();
+            Some(match max_width {
+                Some(width) if value.chars().count() > width => {
+                    let kept: String = value.chars().take(width.saturating_sub(1)).collect();
+                    format!("{}…", kept)
+                }
+                _ => value,
+            })
+        })
+        .collect())
+}
+
+fn truncated(batches: &[RecordBatch], max_width: Option<usize>) -> Result<Vec<RecordBatch>> {
+    if max_width.is_none() {
+        return Ok(batches.to_vec());
+    }
+    batches
+        .iter()
+        .map(|batch| {
+            let fields: Vec<Field> = batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| Field::new(f.name(), DataType::Utf8, true))
+                .collect();
+            let columns = batch
+                .columns()
+                .iter()
+                .map(|c| Ok(Arc::new(StringArray::from(cells(c, max_width)?)) as ArrayRef))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+        })
+        .collect()
+}
+
+fn write_markdown<W: Write>(
+    mut out: W,
+    schema: &Schema,
+    batches: &[RecordBatch],
+    max_width: Option<usize>,
+) -> Result<()> {
+    let escape = |s: &str| s.replace('|', "\\|").replace('\n', " ");
+    let names: Vec<String> = schema.fields().iter().map(|f| escape(f.name())).collect();
+    writeln!(out, "| {} |", names.join(" | "))?;
+    writeln!(out, "|{}|", vec!["---"; names.len()].join("|"))?;
+    for batch in batches {
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|c| cells(c, max_width))
+            .collect::<Result<Vec<_>>>()?;
+        for row in 0..batch.num_rows() {
+            let values: Vec<String> = columns
+                .iter()
+                .map(|c| escape(c[row].as_deref().unwrap_or_default()))
+                .collect();
+            writeln!(out, "| {} |", values.join(" | "))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int64Array;
+
+    fn batch() -> (Schema, RecordBatch) {
+        let schema = Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("note", DataType::Utf8, true),
+            Field::new("pid", DataType::Int64, true),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema.clone()),
+            vec![
+                Arc::new(StringArray::from(vec![Some("nginx"), Some("a very long name")])),
+                Arc::new(StringArray::from(vec![Some("a | b\nc"), None])),
+                Arc::new(Int64Array::from(vec![Some(1), None])),
+            ],
+        )
+        .unwrap();
+        (schema, batch)
+    }
+
+    fn written(format: Format, options: WriteOptions) -> String {
+        let (schema, batch) = batch();
+        let mut out = Vec::new();
+        write_batches(&mut out, &schema, &[batch], format, &options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn max_width_cuts_off_long_table_cells() {
+        let options = WriteOptions { max_width: Some(6), header: true };
+        assert_eq!(
+            written(Format::Table, options),
+            "\
++--------+--------+-----+
+| name   | note   | pid |
++--------+--------+-----+
+| nginx  | a | b… | 1   |
+| a ver… |        |     |
++--------+--------+-----+
+"
+        );
+    }
+
+    #[test]
+    fn markdown_escapes_pipes_and_newlines() {
+        assert_eq!(
+            written(Format::Markdown, WriteOptions::default()),
+            "\
+| name | note | pid |
+|---|---|---|
+| nginx | a \\| b c | 1 |
+| a very long name |  |  |
+"
+        );
+        let options = WriteOptions { max_width: Some(4), header: true };
+        assert!(written(Format::Markdown, options).contains("| a v… |  |  |\n"));
+    }
+
+    #[test]
+    fn csv_and_tsv_quote_what_they_need_to_and_can_leave_out_the_header() {
+        assert_eq!(
+            written(Format::Csv, WriteOptions::default()),
+            "name,note,pid\nnginx,\"a | b\nc\",1\na very long name,,\n"
+        );
+        let options = WriteOptions { max_width: None, header: false };
+        assert_eq!(written(Format::Tsv, options), "nginx\t\"a | b\nc\"\t1\na very long name\t\t\n");
+        // Only the table and markdown are for people to read, so values stay whole.
+        let options = WriteOptions { max_width: Some(4), header: true };
+        assert!(written(Format::Csv, options).contains("a very long name"));
+    }
+
+    #[test]
+    fn json_keeps_nulls_and_empty_results_are_an_array() {
+        assert_eq!(
+            written(Format::Json, WriteOptions::default()),
+            r#"[{"name":"nginx","note":"a | b\nc","pid":1},{"name":"a very long name","note":null,"pid":null}]"#
+        );
+        let (schema, batch) = batch();
+        let mut out = Vec::new();
+        write_batches(&mut out, &schema, &[batch.slice(0, 0)], Format::Json, &WriteOptions::default()).unwrap();
+        assert_eq!(out, b"[]");
+    }
+}
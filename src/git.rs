@@ -0,0 +1,333 @@
+use serde_json::{json, Map, Value};
+
+/// `git log` with each commit's fields split by unit separators and commits by record
+/// separators, followed by the `--numstat` lines of the files it changed.
+pub const GIT_LOG: &str =
+    r#"exec git -C "${1:-.}" log --format='%x1e%H%x1f%P%x1f%an%x1f%ae%x1f%at%x1f%cn%x1f%ce%x1f%ct%x1f%D%x1f%s' --numstat"#;
+
+/// Local and remote-tracking branches, one per line.
+pub const GIT_BRANCHES: &str = r#"exec git -C "${1:-.}" for-each-ref --format='%(HEAD)%1f%(refname)%1f%(objectname)%1f%(upstream:short)%1f%(upstream:track,nobracket)%1f%(committerdate:unix)%1f%(subject)' refs/heads refs/remotes"#;
+
+pub const GIT_STATUS: &str = r#"exec git -C "${1:-.}" status --porcelain=v2 -z"#;
+
+pub const GIT_BLAME: &str = r#"exec git -C "$1" blame --line-porcelain -- "$2""#;
+
+/// The files changed in a revision range, or in the working tree when there's none. `-z`
+/// keeps paths unquoted.
+pub const GIT_DIFF_STAT: &str = r#"exec git -C "${1:-.}" diff --numstat -z ${2:+"$2"} --"#;
+
+/// Counts from `--numstat`, which are `-` for binary files.
+fn count(n: &str) -> Option<i64> {
+    n.parse().ok()
+}
+
+fn unix_time(t: &str) -> Option<i64> {
+    t.trim().parse().ok()
+}
+
+/// Parses `GIT_LOG`, one row per commit with the totals of its numstat lines.
+pub fn parse_git_log(output: &str) -> Vec<Value> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let (header, numstat) = record.split_once('\n').unwrap_or((record, ""));
+            let fields: Vec<&str> = header.split('\x1f').collect();
+            if fields.len() < 10 {
+                return None;
+            }
+            let (mut files, mut insertions, mut deletions) = (0, 0, 0);
+            for line in numstat.lines() {
+                let mut stat = line.splitn(3, '\t');
+                let (Some(added), Some(deleted), Some(_)) = (stat.next(), stat.next(), stat.next()) else {
+                    continue;
+                };
+                files += 1;
+                insertions += count(added).unwrap_or(0);
+                deletions += count(deleted).unwrap_or(0);
+            }
+            let list = |s: &str, separator: &str| -> Vec<String> {
+                s.split(separator).filter(|p| !p.is_empty()).map(String::from).collect()
+            };
+            Some(json!({
+                "commit": fields[0],
+                "parents": list(fields[1], " "),
+                "author_name": fields[2],
+                "author_email": fields[3],
+                "author_time": unix_time(fields[4]),
+                "committer_name": fields[5],
+                "committer_email": fields[6],
+                "commit_time": unix_time(fields[7]),
+                "refs": list(fields[8], ", "),
+                "subject": fields[9],
+                "files_changed": files,
+                "insertions": insertions,
+                "deletions": deletions,
+            }))
+        })
+        .collect()
+}
+
+/// Parses `GIT_BRANCHES`. `ahead` and `behind` count commits against the upstream, and
+/// `upstream_gone` is true when the upstream branch was deleted.
+pub fn parse_git_branches(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\x1f').collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            let (name, remote) = match fields[1].strip_prefix("refs/heads/") {
+                Some(name) => (name, false),
+                None => (fields[1].strip_prefix("refs/remotes/").unwrap_or(fields[1]), true),
+            };
+            let (mut ahead, mut behind) = (None, None);
+            for track in fields[4].split(", ") {
+                match track.split_once(' ') {
+                    Some(("ahead", n)) => ahead = n.parse::<i64>().ok(),
+                    Some(("behind", n)) => behind = n.parse::<i64>().ok(),
+                    _ => {}
+                }
+            }
+            let upstream = (!fields[3].is_empty()).then_some(fields[3]);
+            if upstream.is_some() && fields[4] != "gone" {
+                ahead = ahead.or(Some(0));
+                behind = behind.or(Some(0));
+            }
+            Some(json!({
+                "name": name,
+                "remote": remote,
+                "current": fields[0] == "*",
+                "commit": fields[2],
+                "upstream": upstream,
+                "upstream_gone": upstream.map(|_| fields[4] == "gone"),
+                "ahead": ahead,
+                "behind": behind,
+                "commit_time": unix_time(fields[5]),
+                "subject": fields[6],
+            }))
+        })
+        .collect()
+}
+
+/// Parses `git status --porcelain=v2 -z`. `staged` and `unstaged` are the index and
+/// work tree letters git uses, with `.` for unchanged as null.
+pub fn parse_git_status(output: &str) -> Vec<Value> {
+    let mut entries = output.split('\0');
+    let mut rows = Vec::new();
+    while let Some(entry) = entries.next() {
+        let letter = |c: char| (c != '.').then(|| c.to_string());
+        let mut row = Map::new();
+        match entry.split_at_checked(2) {
+            // Ordinary changes: 1 XY sub mH mI mW hH hI path
+            Some(("1 ", rest)) => {
+                let fields: Vec<&str> = rest.splitn(8, ' ').collect();
+                let [xy, .., path] = fields[..] else { continue };
+                let mut xy = xy.chars();
+                row.insert("path".into(), json!(path));
+                row.insert("staged".into(), json!(xy.next().and_then(letter)));
+                row.insert("unstaged".into(), json!(xy.next().and_then(letter)));
+                row.insert("kind".into(), json!("changed"));
+            }
+            // Renames and copies, followed by the original path: 2 XY sub mH mI mW hH hI Xscore path
+            Some(("2 ", rest)) => {
+                let fields: Vec<&str> = rest.splitn(9, ' ').collect();
+                let [xy, .., score, path] = fields[..] else { continue };
+                let mut xy = xy.chars();
+                row.insert("path".into(), json!(path));
+                row.insert("orig_path".into(), json!(entries.next()));
+                row.insert("staged".into(), json!(xy.next().and_then(letter)));
+                row.insert("unstaged".into(), json!(xy.next().and_then(letter)));
+                let kind = if score.starts_with('C') { "copied" } else { "renamed" };
+                row.insert("kind".into(), json!(kind));
+            }
+            // Unmerged: u XY sub m1 m2 m3 mW h1 h2 h3 path
+            Some(("u ", rest)) => {
+                let fields: Vec<&str> = rest.splitn(10, ' ').collect();
+                let [xy, .., path] = fields[..] else { continue };
+                let mut xy = xy.chars();
+                row.insert("path".into(), json!(path));
+                row.insert("staged".into(), json!(xy.next().and_then(letter)));
+                row.insert("unstaged".into(), json!(xy.next().and_then(letter)));
+                row.insert("kind".into(), json!("unmerged"));
+            }
+            Some(("? ", path)) => {
+                row.insert("path".into(), json!(path));
+                row.insert("kind".into(), json!("untracked"));
+            }
+            _ => continue,
+        }
+        rows.push(Value::Object(row));
+    }
+    rows
+}
+
+/// Parses `git blame --line-porcelain`, which repeats the commit's details for every line.
+pub fn parse_git_blame(output: &str) -> Vec<Value> {
+    let mut rows = Vec::new();
+    let mut row = Map::new();
+    for line in output.lines() {
+        if let Some(text) = line.strip_prefix('\t') {
+            row.insert("line".into(), json!(text));
+            rows.push(Value::Object(std::mem::take(&mut row)));
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        if row.is_empty() {
+            // The header: commit, line in the original file, line in the final file.
+            let numbers: Vec<i64> = value.split(' ').filter_map(|n| n.parse().ok()).collect();
+            row.insert("commit".into(), json!(key));
+            row.insert("original_line".into(), json!(numbers.first()));
+            row.insert("line_number".into(), json!(numbers.get(1)));
+            continue;
+        }
+        let email = || value.trim_start_matches('<').trim_end_matches('>');
+        match key {
+            "author" => row.insert("author_name".into(), json!(value)),
+            "author-mail" => row.insert("author_email".into(), json!(email())),
+            "author-time" => row.insert("author_time".into(), json!(unix_time(value))),
+            "committer" => row.insert("committer_name".into(), json!(value)),
+            "committer-mail" => row.insert("committer_email".into(), json!(email())),
+            "committer-time" => row.insert("commit_time".into(), json!(unix_time(value))),
+            "summary" => row.insert("summary".into(), json!(value)),
+            "filename" => row.insert("filename".into(), json!(value)),
+            _ => None,
+        };
+    }
+    rows
+}
+
+/// Parses `git diff --numstat -z`, one row per file. A rename is written with an empty
+/// path and then the old and new paths.
+pub fn parse_git_diff_stat(output: &str) -> Vec<Value> {
+    let mut entries = output.split('\0');
+    let mut rows = Vec::new();
+    while let Some(entry) = entries.next() {
+        let mut stat = entry.splitn(3, '\t');
+        let (Some(added), Some(deleted), Some(path)) = (stat.next(), stat.next(), stat.next()) else {
+            continue;
+        };
+        let (old_path, path) = if path.is_empty() {
+            (entries.next(), entries.next().unwrap_or_default())
+        } else {
+            (None, path.trim_start_matches('\n'))
+        };
+        rows.push(json!({
+            "path": path,
+            "old_path": old_path,
+            "insertions": count(added),
+            "deletions": count(deleted),
+            "binary": added == "-" && deleted == "-",
+        }));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_entries_parse_with_renames_taking_the_next_entry() {
+        let output = concat!(
+            "1 M. N... 100644 100644 100644 814f4a422927b82f5f8a43f8fab6d3839e3983f2 ddc897f039f57aa91e16efa6dfde386c4255206f a.txt\0",
+            "1 .M N... 100644 100644 100644 bdc955b7b2e610ad5a72302b139a2e6cb325519a bdc955b7b2e610ad5a72302b139a2e6cb325519a bin.dat\0",
+            "2 RM N... 100644 100644 100644 2fa992c0b8b5c6acd2bdd4fa31de29d29799bdd5 2fa992c0b8b5c6acd2bdd4fa31de29d29799bdd5 R100 new name.txt\0old name.txt\0",
+            "2 C. N... 100644 100644 100644 2fa992c0b8b5c6acd2bdd4fa31de29d29799bdd5 2fa992c0b8b5c6acd2bdd4fa31de29d29799bdd5 C75 copy.txt\0a.txt\0",
+            "u UU N... 100644 100644 100644 100644 814f4a422927b82f5f8a43f8fab6d3839e3983f2 ddc897f039f57aa91e16efa6dfde386c4255206f bdc955b7b2e610ad5a72302b139a2e6cb325519a both.txt\0",
+            "? untracked.txt\0",
+            "! ignored.log\0",
+        );
+        assert_eq!(
+            parse_git_status(output),
+            vec![
+                json!({"path": "a.txt", "staged": "M", "unstaged": null, "kind": "changed"}),
+                json!({"path": "bin.dat", "staged": null, "unstaged": "M", "kind": "changed"}),
+                json!({"path": "new name.txt", "orig_path": "old name.txt", "staged": "R", "unstaged": "M", "kind": "renamed"}),
+                json!({"path": "copy.txt", "orig_path": "a.txt", "staged": "C", "unstaged": null, "kind": "copied"}),
+                json!({"path": "both.txt", "staged": "U", "unstaged": "U", "kind": "unmerged"}),
+                json!({"path": "untracked.txt", "kind": "untracked"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_stats_parse_renames_from_the_entries_after_an_empty_path() {
+        let output = concat!(
+            "2\t1\ta.txt\0",
+            "-\t-\tbin.dat\0",
+            "1\t0\t\0old name.txt\0new name.txt\0",
+            "1\t0\tuntracked.txt\0",
+        );
+        assert_eq!(
+            parse_git_diff_stat(output),
+            vec![
+                json!({"path": "a.txt", "old_path": null, "insertions": 2, "deletions": 1, "binary": false}),
+                json!({"path": "bin.dat", "old_path": null, "insertions": null, "deletions": null, "binary": true}),
+                json!({"path": "new name.txt", "old_path": "old name.txt", "insertions": 1, "deletions": 0, "binary": false}),
+                json!({"path": "untracked.txt", "old_path": null, "insertions": 1, "deletions": 0, "binary": false}),
+            ]
+        );
+    }
+
+    #[test]
+    fn blame_has_a_row_per_line_with_its_commit() {
+        let output = "\
+f30625e8325a685d4e3cc96fed7424d3d92e6298 1 1 1
+author Ada Lovelace
+author-mail <ada@example.com>
+author-time 1700000000
+author-tz +0000
+committer Ada Lovelace
+committer-mail <ada@example.com>
+committer-time 1700000000
+committer-tz +0000
+summary first
+boundary
+filename a.txt
+\tone
+a4b7261fc7cf22205ad7b01a53638480edcfe96e 2 2 2
+author Ada Lovelace
+author-mail <ada@example.com>
+author-time 1792391020
+author-tz +0000
+committer Grace Hopper
+committer-mail <grace@example.com>
+committer-time 1792391021
+committer-tz +0000
+summary second
+previous f30625e8325a685d4e3cc96fed7424d3d92e6298 a.txt
+filename a.txt
+\tTWO
+a4b7261fc7cf22205ad7b01a53638480edcfe96e 3 3
+author Ada Lovelace
+author-mail <ada@example.com>
+author-time 1792391020
+author-tz +0000
+committer Grace Hopper
+committer-mail <grace@example.com>
+committer-time 1792391021
+committer-tz +0000
+summary second
+previous f30625e8325a685d4e3cc96fed7424d3d92e6298 a.txt
+filename a.txt
+\t\tindented
+";
+        let rows = parse_git_blame(output);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            json!({
+                "commit": "f30625e8325a685d4e3cc96fed7424d3d92e6298", "original_line": 1, "line_number": 1,
+                "author_name": "Ada Lovelace", "author_email": "ada@example.com", "author_time": 1700000000,
+                "committer_name": "Ada Lovelace", "committer_email": "ada@example.com", "commit_time": 1700000000,
+                "summary": "first", "filename": "a.txt", "line": "one",
+            })
+        );
+        assert_eq!(rows[1]["committer_email"], "grace@example.com");
+        assert_eq!(rows[1]["commit_time"], 1792391021);
+        assert_eq!(rows[1]["line"], "TWO");
+        assert_eq!((&rows[2]["original_line"], &rows[2]["line_number"]), (&json!(3), &json!(3)));
+        assert_eq!(rows[2]["line"], "\tindented");
+    }
+}
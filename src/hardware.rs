@@ -0,0 +1,464 @@
+use serde_json::{json, Map, Value};
+
+/// `lsblk`'s columns. Without `-o` it leaves out the model, serial and UUIDs.
+pub const LSBLK_COLUMNS: &str = "NAME,KNAME,TYPE,SIZE,FSTYPE,MOUNTPOINT,LABEL,UUID,MODEL,SERIAL,RO,RM,ROTA,TRAN";
+
+/// A number lsblk printed, which older versions quote.
+fn number(value: &Value) -> Value {
+    match value {
+        Value::String(s) => json!(s.trim().parse::<i64>().ok()),
+        Value::Number(_) => value.clone(),
+        _ => Value::Null,
+    }
+}
+
+/// A flag lsblk printed, which older versions write as `"0"` or `"1"`.
+fn flag(value: &Value) -> Value {
+    match value {
+        Value::String(s) => json!(s == "1"),
+        Value::Bool(_) => value.clone(),
+        _ => Value::Null,
+    }
+}
+
+/// Parses `lsblk -J -b`, flattening the tree of partitions, LVM volumes and the like into
+/// rows with the `parent` device they sit on.
+pub fn parse_lsblk(output: &str) -> Vec<Value> {
+    fn walk(devices: &Value, parent: Option<&str>, rows: &mut Vec<Value>) {
+        for device in devices.as_array().into_iter().flatten() {
+            rows.push(json!({
+                "name": device["name"],
+                "kname": device["kname"],
+                "parent": parent,
+                "type": device["type"],
+                "size_bytes": number(&device["size"]),
+                "fstype": device["fstype"],
+                "mountpoint": device["mountpoint"],
+                "label": device["label"],
+                "uuid": device["uuid"],
+                "model": device["model"].as_str().map(str::trim),
+                "serial": device["serial"],
+                "read_only": flag(&device["ro"]),
+                "removable": flag(&device["rm"]),
+                "rotational": flag(&device["rota"]),
+                "transport": device["tran"],
+            }));
+            walk(&device["children"], device["name"].as_str(), rows);
+        }
+    }
+    let mut rows = Vec::new();
+    if let Ok(lsblk) = serde_json::from_str::<Value>(output) {
+        walk(&lsblk["blockdevices"], None, &mut rows);
+    }
+    rows
+}
+
+/// Parses `lsmod`. `used_by` names the modules using this one, which can be fewer than
+/// `used` counts since references from outside modules have no name.
+pub fn parse_lsmod(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 {
+                return None;
+            }
+            let used_by: Vec<&str> = fields
+                .get(3)
+                .map(|u| u.split(',').filter(|m| !m.is_empty() && *m != "-").collect())
+                .unwrap_or_default();
+            Some(json!({
+                "module": fields[0],
+                "size_bytes": fields[1].parse::<i64>().ok(),
+                "used": fields[2].parse::<i64>().ok(),
+                "used_by": used_by,
+            }))
+        })
+        .collect()
+}
+
+/// Parses `lscpu` in the C locale into one row. `flags` are the CPU's feature flags and
+/// `vulnerabilities` maps each one the kernel checks for to its mitigation.
+pub fn parse_lscpu(output: &str) -> Vec<Value> {
+    let fields: Map<String, Value> = output
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), json!(value.trim())))
+        .collect();
+    if fields.is_empty() {
+        return Vec::new();
+    }
+    let text = |key: &str| fields.get(key).cloned().unwrap_or(Value::Null);
+    let integer = |key: &str| json!(fields.get(key).and_then(|v| v.as_str()?.parse::<i64>().ok()));
+    let float = |key: &str| json!(fields.get(key).and_then(|v| v.as_str()?.parse::<f64>().ok()));
+    let flags: Vec<&str> = fields
+        .get("Flags")
+        .and_then(|f| f.as_str())
+        .map(|f| f.split_whitespace().collect())
+        .unwrap_or_default();
+    let vulnerabilities: Map<String, Value> = fields
+        .iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("Vulnerability ")?.to_string(), value.clone())))
+        .collect();
+    vec![json!({
+        "architecture": text("Architecture"),
+        "vendor": text("Vendor ID"),
+        "model_name": text("Model name"),
+        "cpus": integer("CPU(s)"),
+        "threads_per_core": integer("Thread(s) per core"),
+        "cores_per_socket": integer("Core(s) per socket"),
+        "sockets": integer("Socket(s)"),
+        "numa_nodes": integer("NUMA node(s)"),
+        "max_mhz": float("CPU max MHz"),
+        "min_mhz": float("CPU min MHz"),
+        "bogomips": float("BogoMIPS"),
+        "virtualization": text("Virtualization"),
+        "hypervisor_vendor": text("Hypervisor vendor"),
+        "flags": flags,
+        "vulnerabilities": vulnerabilities,
+    })]
+}
+
+/// Splits `Intel Corporation [8086]`, the way `-nn` writes names, into the name and id.
+fn name_and_id(value: &str) -> (Option<&str>, Option<&str>) {
+    match value.rsplit_once(" [") {
+        Some((name, id)) if id.ends_with(']') => (Some(name), Some(id.trim_end_matches(']'))),
+        _ if value.starts_with('[') => (None, Some(value.trim_matches(|c| c == '[' || c == ']'))),
+        _ => (Some(value).filter(|v| !v.is_empty()), None),
+    }
+}
+
+/// Parses `lspci -vmm -nn -k -D`, whose records are blank-line separated `Key: value`
+/// lines.
+pub fn parse_lspci(output: &str) -> Vec<Value> {
+    output
+        .split("\n\n")
+        .filter_map(|record| {
+            let fields: Map<String, Value> = record
+                .lines()
+                .filter_map(|line| line.split_once(":\t"))
+                .map(|(key, value)| (key.to_string(), json!(value.trim())))
+                .collect();
+            let slot = fields.get("Slot")?;
+            let split = |key: &str| name_and_id(fields.get(key).and_then(|v| v.as_str()).unwrap_or_default());
+            let (class, class_id) = split("Class");
+            let (vendor, vendor_id) = split("Vendor");
+            let (device, device_id) = split("Device");
+            let (subsystem_vendor, _) = split("SVendor");
+            let (subsystem_device, _) = split("SDevice");
+            Some(json!({
+                "slot": slot,
+                "class": class,
+                "class_id": class_id,
+                "vendor": vendor,
+                "vendor_id": vendor_id,
+                "device": device,
+                "device_id": device_id,
+                "subsystem_vendor": subsystem_vendor,
+                "subsystem_device": subsystem_device,
+                "revision": fields.get("Rev"),
+                "driver": fields.get("Driver"),
+                "module": fields.get("Module"),
+            }))
+        })
+        .collect()
+}
+
+/// Parses `lsusb`: `Bus 001 Device 002: ID 8087:0024 Intel Corp. Hub`.
+pub fn parse_lsusb(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.splitn(7, ' ').collect();
+            let ["Bus", bus, "Device", device, "ID", id, rest @ ..] = &fields[..] else {
+                return None;
+            };
+            let (vendor_id, product_id) = id.split_once(':')?;
+            Some(json!({
+                "bus": bus.parse::<i64>().ok(),
+                "device": device.trim_end_matches(':').parse::<i64>().ok(),
+                "vendor_id": vendor_id,
+                "product_id": product_id,
+                "description": rest.first().map(|d| d.trim()).filter(|d| !d.is_empty()),
+            }))
+        })
+        .collect()
+}
+
+/// Parses `dmidecode`, one row per structure with its properties as a map. Properties
+/// that are lists, like a BIOS's characteristics, are joined with `, `.
+pub fn parse_dmidecode(output: &str) -> Vec<Value> {
+    let mut rows = Vec::new();
+    for record in output.split("\n\n") {
+        let mut lines = record.lines();
+        let Some(header) = lines.next().and_then(|h| h.strip_prefix("Handle ")) else {
+            continue;
+        };
+        // Handle 0x0001, DMI type 1, 27 bytes
+        let mut header = header.split(", ");
+        let handle = header.next();
+        let dmi_type = header
+            .next()
+            .and_then(|t| t.strip_prefix("DMI type "))
+            .and_then(|t| t.parse::<i64>().ok());
+        let name = lines.next().map(str::trim);
+        let mut properties = Map::new();
+        let mut last: Option<String> = None;
+        for line in lines {
+            if let Some(item) = line.strip_prefix("\t\t") {
+                if let Some(Value::String(list)) = last.as_ref().and_then(|key| properties.get_mut(key)) {
+                    if !list.is_empty() {
+                        list.push_str(", ");
+                    }
+                    list.push_str(item.trim());
+                }
+            } else if let Some((key, value)) = line.trim().split_once(':') {
+                properties.insert(key.to_string(), json!(value.trim()));
+                last = Some(key.to_string());
+            }
+        }
+        rows.push(json!({
+            "handle": handle,
+            "type": dmi_type,
+            "name": name,
+            "properties": properties,
+        }));
+    }
+    rows
+}
+
+/// Parses `sysctl -a`, which Linux writes as `key = value` and the BSDs as `key: value`.
+pub fn parse_sysctl(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(" = ").or_else(|| line.split_once(": "))?;
+            Some(json!({ "key": key.trim(), "value": value.trim() }))
+        })
+        .collect()
+}
+
+/// Undoes the octal escapes the kernel writes for spaces and the like in paths.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|o| u8::from_str_radix(std::str::from_utf8(o).ok()?, 8).ok());
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parses `/proc/self/mountinfo`:
+/// `36 35 98:0 /mnt1 /mnt/parent rw,noatime master:1 - ext3 /dev/root rw,errors=continue`,
+/// where the optional fields before the `-` say how the mount propagates.
+pub fn parse_mountinfo(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (mount, filesystem) = line.split_once(" - ")?;
+            let mount: Vec<&str> = mount.split(' ').collect();
+            let filesystem: Vec<&str> = filesystem.split(' ').collect();
+            if mount.len() < 6 || filesystem.len() < 3 {
+                return None;
+            }
+            let (major, minor) = mount[2].split_once(':').unwrap_or((mount[2], ""));
+            Some(json!({
+                "mount_id": mount[0].parse::<i64>().ok(),
+                "parent_id": mount[1].parse::<i64>().ok(),
+                "major": major.parse::<i64>().ok(),
+                "minor": minor.parse::<i64>().ok(),
+                "root": unescape(mount[3]),
+                "mount_point": unescape(mount[4]),
+                "mount_options": mount[5],
+                "propagation": &mount[6..],
+                "fstype": filesystem[0],
+                "source": unescape(filesystem[1]),
+                "super_options": filesystem[2],
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn octal_escapes_are_undone() {
+        let cases = [
+            ("/mnt/plain", "/mnt/plain"),
+            ("/mnt/my\\040disk", "/mnt/my disk"),
+            ("/mnt/tab\\011and\\012newline", "/mnt/tab\tand\nnewline"),
+            ("/mnt/back\\134slash", "/mnt/back\\slash"),
+            ("/mnt/caf\\303\\251", "/mnt/café"),
+            ("/mnt/not\\08octal", "/mnt/not\\08octal"),
+            ("/mnt/short\\04", "/mnt/short\\04"),
+            ("/mnt/ends\\", "/mnt/ends\\"),
+        ];
+        for (field, expected) in cases {
+            assert_eq!(unescape(field), expected, "{}", field);
+        }
+    }
+
+    #[test]
+    fn mountinfo_parses_escaped_mount_points_and_propagation() {
+        let output = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw,errors=remount-ro
+36 22 0:32 / /media/usb\\040stick rw,nosuid master:7 shared:12 - vfat /dev/sdb1 rw,fmask=0022
+41 22 0:35 /exports /srv/nfs\\011share rw - nfs4 server:/exports\\040all rw,vers=4.2
+not a mount line
+";
+        let rows = parse_mountinfo(output);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[1],
+            json!({
+                "mount_id": 36, "parent_id": 22, "major": 0, "minor": 32, "root": "/",
+                "mount_point": "/media/usb stick", "mount_options": "rw,nosuid",
+                "propagation": ["master:7", "shared:12"], "fstype": "vfat", "source": "/dev/sdb1",
+                "super_options": "rw,fmask=0022",
+            })
+        );
+        assert_eq!(rows[0]["propagation"], json!(["shared:1"]));
+        assert_eq!(rows[2]["root"], "/exports");
+        assert_eq!(rows[2]["mount_point"], "/srv/nfs\tshare");
+        assert_eq!(rows[2]["propagation"], json!([]));
+        assert_eq!(rows[2]["source"], "server:/exports all");
+    }
+
+    #[test]
+    fn lsblk_trees_flatten_with_their_parents() {
+        let output = r#"{
+   "blockdevices": [
+      {"name":"nvme0n1", "kname":"nvme0n1", "type":"disk", "size":512110190592, "fstype":null, "mountpoint":null, "label":null, "uuid":null, "model":"Samsung SSD 970 EVO Plus 500GB   ", "serial":"S4EVNX0N123456", "ro":false, "rm":false, "rota":false, "tran":"nvme",
+         "children": [
+            {"name":"nvme0n1p1", "kname":"nvme0n1p1", "type":"part", "size":536870912, "fstype":"vfat", "mountpoint":"/boot/efi", "label":null, "uuid":"ABCD-1234", "model":null, "serial":null, "ro":false, "rm":false, "rota":false, "tran":null},
+            {"name":"nvme0n1p2", "kname":"nvme0n1p2", "type":"part", "size":511571623936, "fstype":"crypto_LUKS", "mountpoint":null, "label":null, "uuid":"1111", "model":null, "serial":null, "ro":false, "rm":false, "rota":false, "tran":null,
+               "children": [
+                  {"name":"cryptroot", "kname":"dm-0", "type":"crypt", "size":511554846720, "fstype":"ext4", "mountpoint":"/", "label":"root", "uuid":"2222", "model":null, "serial":null, "ro":false, "rm":false, "rota":false, "tran":null}
+               ]
+            }
+         ]
+      },
+      {"name":"sr0", "kname":"sr0", "type":"rom", "size":"1073741312", "fstype":null, "mountpoint":null, "label":null, "uuid":null, "model":"DVD-RW", "serial":null, "ro":"0", "rm":"1", "rota":"1", "tran":"sata"}
+   ]
+}"#;
+        let rows = parse_lsblk(output);
+        let tree: Vec<(&str, Option<&str>, &str)> = rows
+            .iter()
+            .map(|r| (r["name"].as_str().unwrap(), r["parent"].as_str(), r["type"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            tree,
+            vec![
+                ("nvme0n1", None, "disk"),
+                ("nvme0n1p1", Some("nvme0n1"), "part"),
+                ("nvme0n1p2", Some("nvme0n1"), "part"),
+                ("cryptroot", Some("nvme0n1p2"), "crypt"),
+                ("sr0", None, "rom"),
+            ]
+        );
+        assert_eq!(rows[0]["model"], "Samsung SSD 970 EVO Plus 500GB");
+        assert_eq!(rows[0]["size_bytes"], 512110190592i64);
+        assert_eq!(rows[3]["kname"], "dm-0");
+        assert_eq!(rows[3]["mountpoint"], "/");
+        // Older lsblk quotes numbers and writes flags as "0" and "1".
+        assert_eq!(rows[4]["size_bytes"], 1073741312);
+        assert_eq!(
+            (&rows[4]["read_only"], &rows[4]["removable"], &rows[4]["rotational"]),
+            (&json!(false), &json!(true), &json!(true))
+        );
+        assert_eq!(parse_lsblk("lsblk: not json"), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn dmidecode_structures_parse_with_list_properties_joined() {
+        let output = "\
+# dmidecode 3.3
+Getting SMBIOS data from sysfs.
+SMBIOS 3.0.0 present.
+
+Handle 0x0000, DMI type 0, 24 bytes
+BIOS Information
+\tVendor: LENOVO
+\tRelease Date: 08/10/2023
+\tCharacteristics:
+\t\tPCI is supported
+\t\tBIOS is upgradeable
+\tBIOS Revision: 1.45
+
+Handle 0x0001, DMI type 1, 27 bytes
+System Information
+\tManufacturer: LENOVO
+\tUUID: 4c4c4544-0042-3510-8052-b4c04f4e4332
+\tWake-up Type: Power Switch
+
+Handle 0x0040, DMI type 127, 4 bytes
+End Of Table
+";
+        assert_eq!(
+            parse_dmidecode(output),
+            vec![
+                json!({"handle": "0x0000", "type": 0, "name": "BIOS Information", "properties": {
+                    "Vendor": "LENOVO", "Release Date": "08/10/2023",
+                    "Characteristics": "PCI is supported, BIOS is upgradeable", "BIOS Revision": "1.45",
+                }}),
+                json!({"handle": "0x0001", "type": 1, "name": "System Information", "properties": {
+                    "Manufacturer": "LENOVO", "UUID": "4c4c4544-0042-3510-8052-b4c04f4e4332",
+                    "Wake-up Type": "Power Switch",
+                }}),
+                json!({"handle": "0x0040", "type": 127, "name": "End Of Table", "properties": {}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn lspci_records_split_names_from_ids() {
+        let output = "\
+Slot:\t0000:00:02.0
+Class:\tVGA compatible controller [0300]
+Vendor:\tIntel Corporation [8086]
+Device:\tUHD Graphics 620 [5917]
+SVendor:\tLenovo [17aa]
+SDevice:\tThinkPad T480 [225d]
+Rev:\t07
+Driver:\ti915
+Module:\ti915
+
+Slot:\t0000:03:00.0
+Class:\tEthernet controller [0200]
+Vendor:\tIntel Corporation [8086]
+Device:\tEthernet Controller [I225-V] [15f3]
+Rev:\t03
+";
+        assert_eq!(
+            parse_lspci(output),
+            vec![
+                json!({
+                    "slot": "0000:00:02.0", "class": "VGA compatible controller", "class_id": "0300",
+                    "vendor": "Intel Corporation", "vendor_id": "8086", "device": "UHD Graphics 620",
+                    "device_id": "5917", "subsystem_vendor": "Lenovo", "subsystem_device": "ThinkPad T480",
+                    "revision": "07", "driver": "i915", "module": "i915",
+                }),
+                json!({
+                    "slot": "0000:03:00.0", "class": "Ethernet controller", "class_id": "0200",
+                    "vendor": "Intel Corporation", "vendor_id": "8086", "device": "Ethernet Controller [I225-V]",
+                    "device_id": "15f3", "subsystem_vendor": null, "subsystem_device": null,
+                    "revision": "03", "driver": null, "module": null,
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use datafusion::execution::context::SessionContext;
+use parking_lot::Mutex;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use crate::context::CommandTables;
+use crate::remote::Remote;
+use crate::script::is_complete;
+
+const KEYWORDS: &[&str] = &[
+    "select", "from", "where", "group", "by", "order", "having", "limit", "offset", "join",
+    "left", "right", "inner", "outer", "full", "cross", "on", "using", "as", "and", "or",
+    "not", "in", "is", "null", "like", "ilike", "between", "case", "when", "then", "else",
+    "end", "distinct", "union", "all", "except", "intersect", "with", "asc", "desc", "count",
+    "sum", "avg", "min", "max", "cast", "interval", "true", "false", "create", "table", "view",
+    "external", "stored", "location", "drop", "insert", "into", "values", "explain", "describe",
+    "show", "tables", "columns", "set", "over", "partition",
+];
+
+const META_COMMANDS: &[&str] = &[
+    "\\dt", "\\d", "\\hosts", "\\format", "\\set", "\\unset", "\\timing", "\\help",
+];
+
+const RESET: &str = "\x1b[0m";
+const KEYWORD: &str = "\x1b[1;34m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[2m";
+
+/// Completion, highlighting and multi-line statements for the REPL.
+pub struct ZqueryHelper {
+    ctx: SessionContext,
+    /// Columns of the tables created in the session, as of the last `refresh`.
+    session_columns: Mutex<BTreeSet<String>>,
+}
+
+impl ZqueryHelper {
+    pub fn new(ctx: SessionContext) -> Self {
+        Self {
+            ctx,
+            session_columns: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Reads the columns of the session's tables for completion. Looking a table up
+    /// can wait on I/O, which rustyline's callbacks can't, so the REPL calls this
+    /// after each line instead.
+    pub async fn refresh(&self) {
+        let mut names = BTreeSet::new();
+        for catalog in self.ctx.catalog_names() {
+            let catalog = match self.ctx.catalog(&catalog) {
+                Some(catalog) => catalog,
+                None => continue,
+            };
+            for schema in catalog.schema_names() {
+                let schema = match catalog.schema(&schema) {
+                    Some(schema) => schema,
+                    None => continue,
+                };
+                for table in schema.table_names() {
+                    if let Ok(Some(table)) = schema.table(&table).await {
+                        names.extend(table.schema().fields().iter().map(|f| f.name().clone()));
+                    }
+                }
+            }
+        }
+        *self.session_columns.lock() = names;
+    }
+
+    /// Table functions like `ps` and `stream`, and tables created in the session.
+    fn table_names(&self) -> BTreeSet<String> {
+        let state = self.ctx.state();
+        let mut names: BTreeSet<String> = state.table_functions().keys().cloned().collect();
+        for catalog in self.ctx.catalog_names() {
+            let catalog = match self.ctx.catalog(&catalog) {
+                Some(catalog) => catalog,
+                None => continue,
+            };
+            for schema in catalog.schema_names() {
+                if let Some(schema) = catalog.schema(&schema) {
+                    names.extend(schema.table_names());
+                }
+            }
+        }
+        names
+    }
+
+    /// Columns of the command tables and of tables created in the session.
+    fn column_names(&self) -> BTreeSet<String> {
+        let mut names = self.session_columns.lock().clone();
+        for (_, func) in CommandTables::of(self.ctx.state().config()).0 {
+            names.extend(func.schema.fields().iter().map(|f| f.name().clone()));
+        }
+        names
+    }
+}
+
+/// Keeps the case the user started typing in, so `SEL` completes to `SELECT`.
+fn match_case(word: &str, candidate: &str) -> String {
+    if !word.is_empty() && word.chars().all(|c| !c.is_lowercase()) && word.chars().any(|c| c.is_alphabetic()) {
+        candidate.to_uppercase()
+    } else {
+        candidate.to_string()
+    }
+}
+
+impl Completer for ZqueryHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '\\'))
+            .map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let prefix = &before[..start];
+        let lower = word.to_lowercase();
+
+        let candidates: Vec<String> = if prefix.trim().is_empty() && word.starts_with('\\') {
+            META_COMMANDS.iter().map(|c| c.to_string()).collect()
+        } else if prefix.ends_with("host('") || prefix.ends_with("hosts('") {
+            Remote::of(&self.ctx.state()).inventory.hosts().unwrap_or_default()
+        } else if prefix.trim_start().starts_with("\\d") {
+            self.table_names().into_iter().collect()
+        } else {
+            let mut candidates: Vec<String> = self.table_names().into_iter().collect();
+            candidates.extend(self.column_names());
+            candidates.extend(KEYWORDS.iter().map(|k| match_case(word, k)));
+            candidates
+        };
+
+        let mut seen = BTreeSet::new();
+        let pairs = candidates
+            .into_iter()
+            .filter(|c| c.to_lowercase().starts_with(&lower) && seen.insert(c.clone()))
+            .map(|c| Pair {
+                display: c.clone(),
+                replacement: c,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ZqueryHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ZqueryHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if line.trim_start().starts_with('\\') {
+            return Cow::Borrowed(line);
+        }
+        let mut out = String::with_capacity(line.len() * 2);
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c == '-' && line[i..].starts_with("--") {
+                let end = line[i..].find('\n').map_or(line.len(), |n| i + n);
+                out.push_str(COMMENT);
+                out.push_str(&line[i..end]);
+                out.push_str(RESET);
+                while chars.peek().is_some_and(|(j, _)| *j < end) {
+                    chars.next();
+                }
+            } else if c == '\'' {
+                let end = line[i + 1..].find('\'').map_or(line.len(), |n| i + n + 2);
+                out.push_str(STRING);
+                out.push_str(&line[i..end]);
+                out.push_str(RESET);
+                while chars.peek().is_some_and(|(j, _)| *j < end) {
+                    chars.next();
+                }
+            } else if c.is_alphanumeric() || c == '_' {
+                let mut end = i + c.len_utf8();
+                while let Some((j, n)) = chars.peek() {
+                    if !(n.is_alphanumeric() || *n == '_' || *n == '.') {
+                        break;
+                    }
+                    end = j + n.len_utf8();
+                    chars.next();
+                }
+                let word = &line[i..end];
+                if word.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                    out.push_str(NUMBER);
+                    out.push_str(word);
+                    out.push_str(RESET);
+                } else if KEYWORDS.contains(&word.to_lowercase().as_str()) {
+                    out.push_str(KEYWORD);
+                    out.push_str(word);
+                    out.push_str(RESET);
+                } else {
+                    out.push_str(word);
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    /// Only brackets and quotes change how the rest of the line is drawn, so other
+    /// characters are echoed as typed and picked up by the next full redraw.
+    fn highlight_char(&self, line: &str, pos: usize, _forced: bool) -> bool {
+        let before = line[..pos].chars().next_back();
+        let at = line[pos..].chars().next();
+        before.into_iter().chain(at).any(|c| "()[]{}'\"".contains(c))
+    }
+}
+
+/// Statements run once they end in `;`, so they can span lines. Meta commands run
+/// as soon as they're entered.
+impl Validator for ZqueryHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_entered(ctx.input()) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+fn is_entered(input: &str) -> bool {
+    input.trim().is_empty() || input.trim_start().starts_with('\\') || is_complete(input)
+}
+
+impl Helper for ZqueryHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ZqueryContext;
+    use rustyline::history::MemHistory;
+
+    fn complete(helper: &ZqueryHelper, line: &str) -> (usize, Vec<String>) {
+        let history = MemHistory::new();
+        let (start, pairs) = helper.complete(line, line.len(), &Context::new(&history)).unwrap();
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[tokio::test]
+    async fn keywords_complete_in_the_case_they_were_started_in() {
+        let helper = ZqueryHelper::new(ZqueryContext::new().build());
+        assert_eq!(complete(&helper, "sel"), (0, vec!["select".to_string()]));
+        assert_eq!(complete(&helper, "SEL"), (0, vec!["SELECT".to_string()]));
+        let (start, names) = complete(&helper, "select * fr");
+        assert_eq!(start, 9);
+        assert!(names.contains(&"from".to_string()), "{:?}", names);
+    }
+
+    #[tokio::test]
+    async fn meta_commands_and_their_tables_complete() {
+        let helper = ZqueryHelper::new(ZqueryContext::new().build());
+        assert_eq!(complete(&helper, "\\ti"), (0, vec!["\\timing".to_string()]));
+        assert_eq!(complete(&helper, "\\d"), (0, vec!["\\dt".to_string(), "\\d".to_string()]));
+        let (start, names) = complete(&helper, "\\d p");
+        assert_eq!(start, 3);
+        assert!(names.contains(&"ps".to_string()), "{:?}", names);
+        assert!(names.iter().all(|n| n.starts_with('p')), "{:?}", names);
+    }
+
+    #[tokio::test]
+    async fn columns_of_session_tables_complete_after_a_refresh() {
+        let ctx = ZqueryContext::new().build();
+        let helper = ZqueryHelper::new(ctx.clone());
+        let (_, names) = complete(&helper, "select pi");
+        assert!(names.contains(&"pid".to_string()), "{:?}", names);
+
+        ctx.sql("create table scores as select 1 as zebra_count").await.unwrap();
+        assert!(complete(&helper, "select zebra").1.is_empty());
+        helper.refresh().await;
+        assert_eq!(complete(&helper, "select zebra").1, vec!["zebra_count".to_string()]);
+        assert!(complete(&helper, "select * from sco").1.contains(&"scores".to_string()));
+    }
+
+    #[test]
+    fn statements_are_entered_once_they_end_in_a_semicolon() {
+        let cases = [
+            ("", true),
+            ("select 1;", true),
+            ("select 1\nfrom t;", true),
+            ("\\dt", true),
+            ("  \\d ps", true),
+            ("select 1", false),
+            ("select 1\nfrom t", false),
+            ("select 'a;\n", false),
+            ("select 1 /* ; */", false),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(is_entered(input), expected, "{:?}", input);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_brackets_and_quotes_redraw_the_line() {
+        let helper = ZqueryHelper::new(ZqueryContext::new().build());
+        let cases = [
+            ("count(", 6, true),
+            ("count(x", 5, true),
+            ("select 'a", 8, true),
+            ("select \"a\"", 10, true),
+            ("select", 6, false),
+            ("select a", 7, false),
+            ("", 0, false),
+        ];
+        for (line, pos, expected) in cases {
+            assert_eq!(helper.highlight_char(line, pos, false), expected, "{:?} at {}", line, pos);
+        }
+    }
+}
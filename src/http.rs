@@ -0,0 +1,250 @@
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+use crate::context::{CommandTables, ZqueryContext};
+use crate::engine::{bearer_token_matches, sql_to_df};
+use crate::format::{write_batches, Format, WriteOptions};
+
+struct HttpState {
+    token: Option<String>,
+    timeout: Duration,
+    context: ZqueryContext,
+}
+
+/// Serves `POST /query` and `GET /tables` on `addr`, each request in a session set up
+/// from `context`.
+pub async fn serve_http(
+    addr: &str,
+    token: Option<String>,
+    timeout: Duration,
+    context: ZqueryContext,
+) -> std::io::Result<()> {
+    let state = Arc::new(HttpState { token, timeout, context });
+    let app = Router::new()
+        .route("/query", post(query))
+        .route("/tables", get(tables))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state);
+
+    let listener = TcpListener::bind(addr).await?;
+    eprintln!("Listening for HTTP requests on {}", addr);
+    axum::serve(listener, app).await
+}
+
+fn error(status: StatusCode, message: impl ToString) -> Response {
+    (status, Json(json!({ "error": message.to_string() }))).into_response()
+}
+
+async fn require_token(State(state): State<Arc<HttpState>>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let given = request.headers().get(header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+        if !bearer_token_matches(given, token) {
+            return error(StatusCode::UNAUTHORIZED, "Missing or wrong bearer token");
+        }
+    }
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+struct QueryRequest {
+    sql: String,
+}
+
+/// Runs the SQL in the body, either as plain text or as `{"sql": "..."}`, and returns
+/// the results in the format asked for by the `Accept` header.
+async fn query(State(state): State<Arc<HttpState>>, headers: HeaderMap, body: String) -> Response {
+    let format = match accepted_format(&headers) {
+        Some(format) => format,
+        None => {
+            return error(
+                StatusCode::NOT_ACCEPTABLE,
+                "Supported formats are application/json, application/x-ndjson, text/csv, text/tab-separated-values, text/markdown, text/plain, application/vnd.apache.parquet and application/vnd.apache.arrow.stream",
+            )
+        }
+    };
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let sql = if is_json {
+        match serde_json::from_str::<QueryRequest>(&body) {
+            Ok(request) => request.sql,
+            Err(e) => return error(StatusCode::BAD_REQUEST, e),
+        }
+    } else {
+        body
+    };
+
+    // Command tables block while they run, so the query gets a thread of its own rather
+    // than holding up the runtime's workers, and the timeout can answer while it's still
+    // going. A command that has already started finishes in the background.
+    let runtime = tokio::runtime::Handle::current();
+    let context = state.context.clone();
+    let task = tokio::task::spawn_blocking(move || runtime.block_on(run_query(context, &sql)));
+    let (schema, batches) = match tokio::time::timeout(state.timeout, task).await {
+        Ok(Ok(Ok(results))) => results,
+        Ok(Ok(Err(e))) => return error(StatusCode::BAD_REQUEST, e),
+        Ok(Err(e)) => return error(StatusCode::INTERNAL_SERVER_ERROR, e),
+        Err(_) => {
+            return error(
+                StatusCode::GATEWAY_TIMEOUT,
+                format!("Query took longer than {:?}", state.timeout),
+            );
+        }
+    };
+
+    let mut out = Vec::new();
+    if let Err(e) = write_batches(&mut out, &schema, &batches, format, &WriteOptions::default()) {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, e);
+    }
+    ([(header::CONTENT_TYPE, format.mime())], Body::from(out)).into_response()
+}
+
+async fn run_query(context: ZqueryContext, sql: &str) -> datafusion::error::Result<(Schema, Vec<RecordBatch>)> {
+    let ctx = context.build();
+    let df = sql_to_df(&ctx, sql).await?;
+    let schema = df.schema().as_arrow().clone();
+    Ok((schema, df.collect().await?))
+}
+
+fn accepted_format(headers: &HeaderMap) -> Option<Format> {
+    let accept = match headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) {
+        Some(accept) => accept,
+        None => return Some(Format::Json),
+    };
+    accept.split(',').find_map(Format::from_mime)
+}
+
+/// Lists the session's command tables with how to call them and the columns they return.
+async fn tables(State(state): State<Arc<HttpState>>) -> Json<Value> {
+    let ctx = state.context.clone().build();
+    let tables: Vec<Value> = CommandTables::of(ctx.state().config())
+        .0
+        .iter()
+        .map(|(name, func)| {
+            let columns: Vec<Value> = func
+                .schema
+                .fields()
+                .iter()
+                .map(|f| {
+                    json!({
+                        "name": f.name(),
+                        "type": f.data_type().to_string(),
+                        "nullable": f.is_nullable(),
+                    })
+                })
+                .collect();
+            json!({
+                "name": name,
+                "signature": func.signature(name),
+                "columns": columns,
+            })
+        })
+        .collect();
+    Json(Value::Array(tables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commandtable::{CommandTableFunc, Parser};
+    use datafusion::arrow::datatypes::{DataType, Field};
+
+    fn greetings() -> CommandTableFunc {
+        CommandTableFunc {
+            command: vec!["sh", "-c", "echo hello; echo world", "greetings"],
+            parser: Parser::Native(|output| output.lines().map(|line| json!({ "line": line })).collect()),
+            args: "",
+            description: "Greetings.",
+            platforms: &["linux"],
+            schema: Arc::new(Schema::new(vec![Field::new("line", DataType::Utf8, true)])),
+            is_result_array: false,
+        }
+    }
+
+    /// Serves a session whose only command table is `greetings()`, returning its URL.
+    async fn serve() -> String {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let addr = format!("127.0.0.1:{}", port);
+        let context = ZqueryContext::new().without_builtin_tables().with_command_table("greetings", greetings());
+        let token = Some("s3cret".to_string());
+        tokio::spawn({
+            let addr = addr.clone();
+            async move { serve_http(&addr, token, Duration::from_secs(10), context).await }
+        });
+        for _ in 0..50 {
+            if tokio::net::TcpStream::connect(&addr).await.is_ok() {
+                return format!("http://{}", addr);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("couldn't connect to the HTTP server on {}", addr);
+    }
+
+    /// Sends a request the way a client would, returning the status and body.
+    async fn send(method: &'static str, url: String, authorization: Option<&'static str>, body: &'static str) -> (u16, String) {
+        tokio::task::spawn_blocking(move || {
+            let mut request = ureq::request(method, &url);
+            if let Some(authorization) = authorization {
+                request = request.set("Authorization", authorization);
+            }
+            let response = match request.send_string(body) {
+                Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+                Err(e) => panic!("{}", e),
+            };
+            (response.status(), response.into_string().unwrap())
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_bearer_token() {
+        let url = serve().await;
+        for authorization in [None, Some("Bearer wrong"), Some("Bearer s3cre"), Some("s3cret")] {
+            let (status, body) = send("GET", format!("{}/tables", url), authorization, "").await;
+            assert_eq!(status, 401, "{:?}", authorization);
+            assert_eq!(body, r#"{"error":"Missing or wrong bearer token"}"#);
+        }
+        let (status, _) = send("GET", format!("{}/tables", url), Some("Bearer s3cret"), "").await;
+        assert_eq!(status, 200);
+    }
+
+    #[tokio::test]
+    async fn tables_lists_the_sessions_command_tables() {
+        let url = serve().await;
+        let (status, body) = send("GET", format!("{}/tables", url), Some("Bearer s3cret"), "").await;
+        assert_eq!(status, 200);
+        let tables: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            tables,
+            json!([{
+                "name": "greetings",
+                "signature": greetings().signature("greetings"),
+                "columns": [{ "name": "line", "type": "Utf8", "nullable": true }],
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn queries_run_in_the_session() {
+        let url = serve().await;
+        let sql = "select line from greetings() order by line";
+        let (status, body) = send("POST", format!("{}/query", url), Some("Bearer s3cret"), sql).await;
+        assert_eq!((status, body.as_str()), (200, r#"[{"line":"hello"},{"line":"world"}]"#));
+        let (status, body) = send("POST", format!("{}/query", url), Some("Bearer s3cret"), "select * from ps()").await;
+        assert_eq!(status, 400, "{}", body);
+    }
+}
@@ -0,0 +1,329 @@
+use chrono::DateTime;
+use serde_json::{json, Map, Value};
+
+/// Runs `kubectl get <resource> -o json` for the resource named by `sh -c`'s `$0`, with
+/// the optional context and namespace arguments as `$1` and `$2`. Without a namespace
+/// it looks at all of them.
+pub const KUBECTL_GET: &str = r#"exec kubectl get "$0" ${1:+--context="$1"} ${2:+--namespace="$2"} $([ -z "$2" ] && echo --all-namespaces) -o json"#;
+
+/// `kubectl top` the same way. It has no JSON output, so `parse_top_pods` reads the table.
+pub const KUBECTL_TOP: &str = r#"exec kubectl top "$0" ${1:+--context="$1"} ${2:+--namespace="$2"} $([ -z "$2" ] && echo --all-namespaces) --no-headers"#;
+
+/// The `items` of a `kubectl get -o json` list.
+fn items(output: &str) -> Vec<Value> {
+    match serde_json::from_str::<Value>(output) {
+        Ok(Value::Object(mut list)) => match list.remove("items") {
+            Some(Value::Array(items)) => items,
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Parses a Kubernetes quantity like `250m`, `1.5`, `128Mi` or `2G` into base units.
+fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| !c.is_ascii_digit() && c != '.' && c != '-')
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let multiplier = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "Ki" => 1024f64,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        _ => return None,
+    };
+    Some(number.parse::<f64>().ok()? * multiplier)
+}
+
+fn millicores(quantity: &Value) -> Value {
+    json!(quantity.as_str().and_then(parse_quantity).map(|cores| (cores * 1000.0).round() as i64))
+}
+
+fn bytes(quantity: &Value) -> Value {
+    json!(quantity.as_str().and_then(parse_quantity).map(|bytes| bytes.round() as i64))
+}
+
+/// A plain count, like a node's pod capacity.
+fn count(quantity: &Value) -> Value {
+    json!(quantity.as_str().and_then(|q| q.trim().parse::<i64>().ok()))
+}
+
+/// An RFC 3339 time as microseconds since the epoch.
+fn time(value: &Value) -> Value {
+    value
+        .as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map_or(Value::Null, |t| json!(t.timestamp_micros()))
+}
+
+/// The metadata every object has, which starts each row.
+fn metadata(item: &Value) -> Map<String, Value> {
+    let metadata = &item["metadata"];
+    let mut row = Map::new();
+    row.insert("namespace".into(), metadata["namespace"].clone());
+    row.insert("name".into(), metadata["name"].clone());
+    row.insert("uid".into(), metadata["uid"].clone());
+    row.insert("created".into(), time(&metadata["creationTimestamp"]));
+    row.insert("labels".into(), metadata["labels"].clone());
+    row.insert("annotations".into(), metadata["annotations"].clone());
+    row
+}
+
+/// Parses `kubectl get pods -o json`, one row per pod with its containers' statuses as
+/// a list.
+pub fn parse_pods(output: &str) -> Vec<Value> {
+    items(output)
+        .iter()
+        .map(|pod| {
+            let (spec, status) = (&pod["spec"], &pod["status"]);
+            let containers: Vec<Value> = status["containerStatuses"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|c| {
+                    // The state is an object with one key, running, waiting or terminated.
+                    let (state, details) = c["state"]
+                        .as_object()
+                        .and_then(|s| s.iter().next())
+                        .map(|(state, details)| (json!(state), details.clone()))
+                        .unwrap_or((Value::Null, Value::Null));
+                    json!({
+                        "name": c["name"],
+                        "image": c["image"],
+                        "ready": c["ready"],
+                        "restart_count": c["restartCount"],
+                        "state": state,
+                        "reason": details["reason"],
+                        "started_at": time(&details["startedAt"]),
+                        "exit_code": details["exitCode"],
+                    })
+                })
+                .collect();
+            let owner = &pod["metadata"]["ownerReferences"][0];
+            let mut row = metadata(pod);
+            row.insert("node".into(), spec["nodeName"].clone());
+            row.insert("phase".into(), status["phase"].clone());
+            row.insert("pod_ip".into(), status["podIP"].clone());
+            row.insert("host_ip".into(), status["hostIP"].clone());
+            row.insert("qos_class".into(), status["qosClass"].clone());
+            row.insert("start_time".into(), time(&status["startTime"]));
+            row.insert(
+                "ready_containers".into(),
+                json!(containers.iter().filter(|c| c["ready"] == true).count()),
+            );
+            row.insert("total_containers".into(), json!(containers.len()));
+            row.insert(
+                "restarts".into(),
+                json!(containers.iter().filter_map(|c| c["restart_count"].as_i64()).sum::<i64>()),
+            );
+            row.insert("owner_kind".into(), owner["kind"].clone());
+            row.insert("owner_name".into(), owner["name"].clone());
+            row.insert("containers".into(), json!(containers));
+            Value::Object(row)
+        })
+        .collect()
+}
+
+/// Parses `kubectl get nodes -o json`, with CPU in millicores and memory in bytes.
+pub fn parse_nodes(output: &str) -> Vec<Value> {
+    items(output)
+        .iter()
+        .map(|node| {
+            let (spec, status) = (&node["spec"], &node["status"]);
+            let info = &status["nodeInfo"];
+            let conditions = status["conditions"].as_array().cloned().unwrap_or_default();
+            let ready = conditions
+                .iter()
+                .find(|c| c["type"] == "Ready")
+                .map(|c| c["status"] == "True");
+            let roles: Vec<&str> = node["metadata"]["labels"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter_map(|(label, _)| label.strip_prefix("node-role.kubernetes.io/"))
+                .collect();
+            let internal_ip = status["addresses"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|a| a["type"] == "InternalIP")
+                .map(|a| a["address"].clone());
+            let mut row = metadata(node);
+            row.remove("namespace");
+            row.insert("ready".into(), json!(ready));
+            row.insert("unschedulable".into(), json!(spec["unschedulable"].as_bool().unwrap_or(false)));
+            row.insert("roles".into(), json!(if roles.is_empty() { None } else { Some(roles.join(",")) }));
+            row.insert("internal_ip".into(), json!(internal_ip));
+            row.insert("kubelet_version".into(), info["kubeletVersion"].clone());
+            row.insert("os_image".into(), info["osImage"].clone());
+            row.insert("kernel_version".into(), info["kernelVersion"].clone());
+            row.insert("container_runtime".into(), info["containerRuntimeVersion"].clone());
+            row.insert("cpu_capacity_millicores".into(), millicores(&status["capacity"]["cpu"]));
+            row.insert("memory_capacity_bytes".into(), bytes(&status["capacity"]["memory"]));
+            row.insert("pods_capacity".into(), count(&status["capacity"]["pods"]));
+            row.insert("cpu_allocatable_millicores".into(), millicores(&status["allocatable"]["cpu"]));
+            row.insert("memory_allocatable_bytes".into(), bytes(&status["allocatable"]["memory"]));
+            row.insert(
+                "conditions".into(),
+                json!(conditions
+                    .iter()
+                    .map(|c| json!({
+                        "type": c["type"],
+                        "status": c["status"],
+                        "reason": c["reason"],
+                        "message": c["message"],
+                        "last_transition": time(&c["lastTransitionTime"]),
+                    }))
+                    .collect::<Vec<_>>()),
+            );
+            row.insert("taints".into(), spec["taints"].clone());
+            Value::Object(row)
+        })
+        .collect()
+}
+
+/// Parses `kubectl get events -o json`. Newer events only set `eventTime`, older ones
+/// only the first and last timestamps, so `last_seen` takes whichever there is.
+pub fn parse_events(output: &str) -> Vec<Value> {
+    items(output)
+        .iter()
+        .map(|event| {
+            let object = &event["involvedObject"];
+            let last_seen = match &event["lastTimestamp"] {
+                Value::Null => time(&event["eventTime"]),
+                last => time(last),
+            };
+            json!({
+                "namespace": event["metadata"]["namespace"],
+                "name": event["metadata"]["name"],
+                "type": event["type"],
+                "reason": event["reason"],
+                "message": event["message"],
+                "object_kind": object["kind"],
+                "object_name": object["name"],
+                "object_namespace": object["namespace"],
+                "count": event["count"].as_i64().unwrap_or(1),
+                "first_seen": time(&event["firstTimestamp"]),
+                "last_seen": last_seen,
+                "source": event["source"]["component"]
+                    .as_str()
+                    .or(event["reportingComponent"].as_str()),
+            })
+        })
+        .collect()
+}
+
+/// Parses `kubectl top pods --all-namespaces --no-headers`. With one namespace kubectl
+/// leaves the namespace column out.
+pub fn parse_top_pods(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (namespace, fields) = match fields.len() {
+                4 => (Some(fields[0]), &fields[1..]),
+                3 => (None, &fields[..]),
+                _ => return None,
+            };
+            Some(json!({
+                "namespace": namespace,
+                "name": fields[0],
+                "cpu_millicores": millicores(&json!(fields[1])),
+                "memory_bytes": bytes(&json!(fields[2])),
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantities_are_parsed_into_base_units() {
+        assert_eq!(parse_quantity("250m"), Some(0.25));
+        assert_eq!(parse_quantity("1.5"), Some(1.5));
+        assert_eq!(parse_quantity("128Mi"), Some(128.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_quantity("2G"), Some(2e9));
+        assert_eq!(parse_quantity("2X"), None);
+        assert_eq!(parse_quantity("Mi"), None);
+        assert_eq!(millicores(&json!("250m")), json!(250));
+        assert_eq!(millicores(&json!("2")), json!(2000));
+        assert_eq!(bytes(&json!("128Mi")), json!(134217728));
+        assert_eq!(bytes(&json!("2X")), Value::Null);
+    }
+
+    #[test]
+    fn pod_capacity_is_a_count() {
+        let output = json!({ "items": [{
+            "metadata": { "name": "node-1" },
+            "status": { "capacity": { "cpu": "4", "memory": "16Gi", "pods": "110" } },
+        }] });
+        let nodes = parse_nodes(&output.to_string());
+        assert_eq!(nodes[0]["pods_capacity"], json!(110));
+        assert_eq!(nodes[0]["cpu_capacity_millicores"], json!(4000));
+        assert_eq!(nodes[0]["memory_capacity_bytes"], json!(16 * 1024 * 1024 * 1024i64));
+    }
+
+    #[test]
+    fn events_are_last_seen_at_whichever_time_they_have() {
+        let output = json!({ "items": [
+            {
+                "metadata": { "namespace": "default", "name": "old" },
+                "firstTimestamp": "2026-10-19T10:00:00Z",
+                "lastTimestamp": "2026-10-19T10:05:00Z",
+                "count": 3,
+            },
+            {
+                "metadata": { "namespace": "default", "name": "new" },
+                "eventTime": "2026-10-19T10:06:00.000000Z",
+                "firstTimestamp": null,
+                "lastTimestamp": null,
+            },
+        ] });
+        let events = parse_events(&output.to_string());
+        let micros = |t: &str| json!(DateTime::parse_from_rfc3339(t).unwrap().timestamp_micros());
+        assert_eq!(events[0]["last_seen"], micros("2026-10-19T10:05:00Z"));
+        assert_eq!(events[0]["first_seen"], micros("2026-10-19T10:00:00Z"));
+        assert_eq!(events[0]["count"], json!(3));
+        assert_eq!(events[1]["last_seen"], micros("2026-10-19T10:06:00Z"));
+        assert_eq!(events[1]["first_seen"], Value::Null);
+        assert_eq!(events[1]["count"], json!(1));
+    }
+
+    #[test]
+    fn top_pods_has_a_namespace_only_across_namespaces() {
+        let all = parse_top_pods("kube-system   coredns-5d78c9869d-abcde   3m   12Mi\n");
+        assert_eq!(
+            all,
+            vec![json!({
+                "namespace": "kube-system",
+                "name": "coredns-5d78c9869d-abcde",
+                "cpu_millicores": 3,
+                "memory_bytes": 12 * 1024 * 1024,
+            })]
+        );
+        let one = parse_top_pods("web-0   250m   1Gi\nerror: metrics not available yet\n");
+        assert_eq!(
+            one,
+            vec![json!({
+                "namespace": null,
+                "name": "web-0",
+                "cpu_millicores": 250,
+                "memory_bytes": 1024 * 1024 * 1024,
+            })]
+        );
+    }
+}
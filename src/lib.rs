@@ -0,0 +1,37 @@
+//! SQL over the output of everyday commands, run locally or over ssh.
+//!
+//! `ZqueryContext` builds a DataFusion `SessionContext` with zquery's tables and
+//! functions registered, for embedding in another program. The `zquery` binary is a
+//! thin wrapper around the same pieces.
+
+mod accounts;
+pub mod cli;
+pub mod commands;
+pub mod commandtable;
+pub mod context;
+mod cron;
+mod diff;
+mod docker;
+pub mod engine;
+pub mod error;
+mod flight;
+pub mod format;
+mod git;
+mod hardware;
+mod helper;
+mod http;
+mod k8s;
+mod net;
+mod packages;
+mod perf;
+mod pg;
+mod procfs;
+pub mod remote;
+pub mod script;
+mod stream;
+mod systemd;
+mod tail;
+mod tui;
+mod watch;
+
+pub use context::ZqueryContext;
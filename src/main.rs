@@ -1,11 +1,13 @@
-mod engine;
-mod commandtable;
-mod commands;
-
 use dotenv::dotenv;
-use engine::{cli_eval, cli_repl};
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use zquery::cli::{cli_repl, cli_script};
+use zquery::engine::{cli_serve, cli_tui, cli_watch};
+use zquery::error::ZqueryError;
+use zquery::format::{Format, Output, WriteOptions};
+use zquery::script::parse_var;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -16,23 +18,130 @@ use clap::Parser;
     about = "An experiment with sql and bpf."
 )]
 struct Args {
+    /// SQL to run, which may be several statements separated by `;`.
+    #[arg(short, long, conflicts_with = "file")]
+    eval: Option<String>,
+
+    /// A file of SQL statements to run, or `-` for stdin. SQL piped into zquery is run
+    /// the same way.
     #[arg(short, long)]
-    demo: bool,
+    file: Option<PathBuf>,
+
+    /// Set a variable for `:name` substitution, like `\set name value`.
+    #[arg(short = 'v', long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+    vars: Vec<(String, String)>,
+
+    /// Keep running a script's statements after one fails.
+    #[arg(long)]
+    continue_on_error: bool,
 
+    /// How to print results: table, csv, tsv, json, ndjson, markdown, parquet or arrow.
+    #[arg(long, default_value = "table")]
+    format: Format,
+
+    /// Write results to this file instead of stdout.
     #[arg(short, long)]
-    eval: Option<String>,
+    output: Option<PathBuf>,
+
+    /// Cut off table and markdown cells longer than this many characters.
+    #[arg(long)]
+    max_width: Option<usize>,
+
+    /// Fail when any host in a hosts() fan-out fails, instead of leaving its rows out.
+    #[arg(long)]
+    strict: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Run alerting rules on a schedule, firing actions when they change state.
+    Watch {
+        /// A toml file of rules, see the readme for the format.
+        #[arg(long)]
+        rules: PathBuf,
+    },
+    /// A full-screen dashboard of queries that refresh on their own.
+    Tui {
+        /// A toml file of queries to show in panes, see the readme for the format.
+        #[arg(long)]
+        dashboard: Option<PathBuf>,
+    },
+    /// Serve the tables to other clients.
+    Serve {
+        /// Address to speak the Postgres wire protocol on, e.g. 127.0.0.1:5433. Addresses
+        /// like :5433 listen on every interface.
+        #[arg(long, requires = "users")]
+        pg: Option<String>,
+        /// A toml file of users and the table functions they may call.
+        #[arg(long)]
+        users: Option<PathBuf>,
+        /// Address to serve the HTTP API on, e.g. :8080.
+        #[arg(long)]
+        http: Option<String>,
+        /// Address to serve Arrow Flight SQL on, e.g. :50051.
+        #[arg(long)]
+        flight: Option<String>,
+        /// Bearer token HTTP and Flight SQL requests must send. Without one they only
+        /// listen on loopback.
+        #[arg(long)]
+        token: Option<String>,
+        /// How long an HTTP query may run, e.g. 30s.
+        #[arg(long, default_value = "30s")]
+        timeout: String,
+    },
 }
 
+/// Exits with the error's code, see `ZqueryError::exit_code`.
 #[tokio::main(flavor = "multi_thread", worker_threads = 10)]
-async fn main() -> std::io::Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run() -> Result<(), ZqueryError> {
     let args = Args::parse();
     dotenv().ok();
+    let output = Output {
+        format: args.format,
+        options: WriteOptions {
+            max_width: args.max_width,
+            ..Default::default()
+        },
+        path: args.output,
+    };
 
-    if args.eval.is_some() {
-        cli_eval(args.eval.unwrap()).await.unwrap();
+    if let Some(Commands::Watch { rules }) = args.command {
+        cli_watch(rules).await?;
+    } else if let Some(Commands::Tui { dashboard }) = args.command {
+        cli_tui(dashboard).await?;
+    } else if let Some(Commands::Serve { pg, users, http, flight, token, timeout }) = args.command {
+        cli_serve(pg, users, http, flight, token, timeout).await?;
+    } else if let Some(script) = script(args.eval, args.file)? {
+        cli_script(script, output, args.strict, args.vars, args.continue_on_error).await?;
     } else {
-        cli_repl().await.unwrap();
+        cli_repl(output, args.strict, args.vars).await?;
     }
 
     Ok(())
 }
+
+/// The SQL to run without a REPL, from `--eval`, `--file` or piped into stdin.
+fn script(eval: Option<String>, file: Option<PathBuf>) -> std::io::Result<Option<String>> {
+    let from_stdin = || -> std::io::Result<String> {
+        let mut script = String::new();
+        std::io::stdin().read_to_string(&mut script)?;
+        Ok(script)
+    };
+    match (eval, file) {
+        (Some(sql), _) => Ok(Some(sql)),
+        (None, Some(path)) if path.as_os_str() == "-" => Ok(Some(from_stdin()?)),
+        (None, Some(path)) => Ok(Some(std::fs::read_to_string(path)?)),
+        (None, None) if !std::io::stdin().is_terminal() => Ok(Some(from_stdin()?)),
+        (None, None) => Ok(None),
+    }
+}
@@ -0,0 +1,407 @@
+use datafusion::arrow::array::{new_empty_array, Array, ArrayRef, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::Result;
+use datafusion::scalar::ScalarValue;
+use datafusion_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::any::Any;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::OnceLock;
+
+/// Parses `ss -tunapH`, one socket per line. The owning process is only there when ss
+/// can see it, which for other users' sockets means running as root.
+pub fn parse_ss(output: &str) -> Vec<Value> {
+    static USERS: OnceLock<Regex> = OnceLock::new();
+    let users = USERS.get_or_init(|| Regex::new(r#"\("([^"]*)",pid=(\d+)"#).unwrap());
+
+    let mut rows = Vec::new();
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 6 {
+            continue;
+        }
+        let mut row = Map::new();
+        row.insert("netid".into(), json!(fields[0]));
+        row.insert("state".into(), json!(fields[1]));
+        row.insert("recv_q".into(), json!(fields[2].parse::<i64>().ok()));
+        row.insert("send_q".into(), json!(fields[3].parse::<i64>().ok()));
+        for (side, endpoint) in [("local", fields[4]), ("peer", fields[5])] {
+            let (address, interface, port) = split_endpoint(endpoint);
+            row.insert(format!("{}_address", side), json!(address));
+            row.insert(format!("{}_port", side), json!(port));
+            if side == "local" {
+                row.insert("interface".into(), json!(interface));
+            }
+        }
+        if let Some(caps) = users.captures(&fields[6..].join(" ")) {
+            row.insert("process".into(), json!(&caps[1]));
+            row.insert("pid".into(), json!(caps[2].parse::<i64>().ok()));
+        }
+        rows.push(Value::Object(row));
+    }
+    rows
+}
+
+/// Splits `127.0.0.53%lo:53`, `[fe80::1]%eth0:546` or `[::]:22` into the address, interface and port. A port
+/// of `*` means any, and comes back as `None`.
+fn split_endpoint(endpoint: &str) -> (String, Option<String>, Option<i64>) {
+    let (address, port) = endpoint.rsplit_once(':').unwrap_or((endpoint, "*"));
+    let (address, interface) = match address.split_once('%') {
+        Some((address, interface)) => (address, Some(interface.to_string())),
+        None => (address, None),
+    };
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    (address.to_string(), interface, port.parse().ok())
+}
+
+/// Parses an address the way commands print them: `10.0.0.1`, `[::1]`, `[fe80::1]%eth0`,
+/// or `*` for any address.
+fn parse_addr(s: &str) -> Option<IpAddr> {
+    let s = s.trim().split('%').next()?;
+    let s = s.trim_start_matches('[').trim_end_matches(']');
+    if s == "*" {
+        return Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    }
+    s.parse().ok()
+}
+
+/// Parses `10.0.0.0/8`. A bare address is a network of just itself.
+fn parse_cidr(s: &str) -> Option<(IpAddr, u32)> {
+    let (addr, prefix) = match s.split_once('/') {
+        Some((addr, prefix)) => (parse_addr(addr)?, Some(prefix.trim().parse::<u32>().ok()?)),
+        None => (parse_addr(s)?, None),
+    };
+    let bits = if addr.is_ipv4() { 32 } else { 128 };
+    match prefix {
+        Some(prefix) if prefix > bits => None,
+        Some(prefix) => Some((addr, prefix)),
+        None => Some((addr, bits)),
+    }
+}
+
+/// The first address of the network `prefix` bits long that `addr` is in.
+fn network(addr: IpAddr, prefix: u32) -> IpAddr {
+    match addr {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+    }
+}
+
+fn contains(cidr: &str, address: &str) -> Option<bool> {
+    let (net, prefix) = parse_cidr(cidr)?;
+    let addr = parse_addr(address)?;
+    let addr = match (net, addr) {
+        (IpAddr::V4(_), IpAddr::V6(v6)) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+        _ => addr,
+    };
+    if net.is_ipv4() != addr.is_ipv4() {
+        return Some(false);
+    }
+    Some(network(net, prefix) == network(addr, prefix))
+}
+
+fn scope(addr: IpAddr) -> &'static str {
+    match addr {
+        IpAddr::V4(v4) if v4.is_unspecified() => "unspecified",
+        IpAddr::V4(v4) if v4.is_loopback() => "loopback",
+        IpAddr::V4(v4) if v4.is_private() => "private",
+        IpAddr::V4(v4) if v4.is_link_local() => "link-local",
+        IpAddr::V4(v4) if v4.is_multicast() => "multicast",
+        IpAddr::V4(_) => "global",
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => scope(IpAddr::V4(v4)),
+            None if v6.is_unspecified() => "unspecified",
+            None if v6.is_loopback() => "loopback",
+            None if v6.is_multicast() => "multicast",
+            None if v6.segments()[0] & 0xffc0 == 0xfe80 => "link-local",
+            // Unique local addresses, fc00::/7, are IPv6's private ranges.
+            None if v6.segments()[0] & 0xfe00 == 0xfc00 => "private",
+            None => "global",
+        },
+    }
+}
+
+/// Runs `f` over each row of string arguments, returning a scalar if they all were.
+pub(crate) fn map_strings(
+    args: &[ColumnarValue],
+    return_type: &DataType,
+    f: impl Fn(&[Option<&str>]) -> ScalarValue,
+) -> Result<ColumnarValue> {
+    let is_scalar = args.iter().all(|a| matches!(a, ColumnarValue::Scalar(_)));
+    let arrays = ColumnarValue::values_to_arrays(args)?
+        .iter()
+        .map(|array| cast(array, &DataType::Utf8))
+        .collect::<std::result::Result<Vec<ArrayRef>, _>>()?;
+    let strings: Vec<&StringArray> = arrays
+        .iter()
+        .map(|array| array.as_any().downcast_ref::<StringArray>().unwrap())
+        .collect();
+    let rows = arrays.first().map_or(0, |a| a.len());
+    let values = (0..rows)
+        .map(|row| {
+            let row: Vec<Option<&str>> = strings
+                .iter()
+                .map(|s| if s.is_null(row) { None } else { Some(s.value(row)) })
+                .collect();
+            f(&row)
+        })
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return Ok(ColumnarValue::Array(new_empty_array(return_type)));
+    }
+    if is_scalar {
+        return Ok(ColumnarValue::Scalar(values.into_iter().next().unwrap()));
+    }
+    Ok(ColumnarValue::Array(ScalarValue::iter_to_array(values)?))
+}
+
+/// `cidr_contains('10.0.0.0/8', local_address)` is true when the address is in the
+/// network. IPv4 addresses written as IPv6, like `::ffff:10.0.0.1`, count too.
+#[derive(Debug)]
+pub struct CidrContains {
+    signature: Signature,
+}
+
+impl CidrContains {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::any(2, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for CidrContains {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "cidr_contains"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        map_strings(args, &DataType::Boolean, |row| match row {
+            [Some(cidr), Some(address)] => ScalarValue::Boolean(contains(cidr, address)),
+            _ => ScalarValue::Boolean(None),
+        })
+    }
+}
+
+/// `cidr_network('10.1.2.3/16')` is the network an address is in, `10.1.0.0/16`.
+#[derive(Debug)]
+pub struct CidrNetwork {
+    signature: Signature,
+}
+
+impl CidrNetwork {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::any(1, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for CidrNetwork {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "cidr_network"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        map_strings(args, &DataType::Utf8, |row| {
+            let network = row[0]
+                .and_then(parse_cidr)
+                .map(|(addr, prefix)| format!("{}/{}", network(addr, prefix), prefix));
+            ScalarValue::Utf8(network)
+        })
+    }
+}
+
+/// `ip_scope(local_address)` says what kind of address it is: `unspecified` (listening
+/// on every interface, `0.0.0.0`, `::` or `*`), `loopback`, `private`, `link-local`,
+/// `multicast` or `global`.
+#[derive(Debug)]
+pub struct IpScope {
+    signature: Signature,
+}
+
+impl IpScope {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::any(1, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for IpScope {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "ip_scope"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        map_strings(args, &DataType::Utf8, |row| {
+            ScalarValue::Utf8(row[0].and_then(parse_addr).map(|addr| scope(addr).to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ZqueryContext;
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+
+    const SS: &str = "\
+tcp   LISTEN 0      4096   127.0.0.53%lo:53       0.0.0.0:*     users:((\"systemd-resolve\",pid=612,fd=14))
+tcp   LISTEN 0      128    [::]:22                [::]:*        users:((\"sshd\",pid=900,fd=4),(\"sshd\",pid=901,fd=4))
+udp   UNCONN 0      0      [fe80::1c2:3ff:fe4:5]%eth0:546 [::]:*
+tcp   ESTAB  0      36     [::ffff:10.0.0.5]:22   [::ffff:10.0.0.9]:51234
+udp   UNCONN 0      0      *:5353                 *:*
+truncated line
+";
+
+    #[test]
+    fn ss_sockets_parse_with_their_endpoints_and_owners() {
+        let rows = parse_ss(SS);
+        assert_eq!(rows.len(), 5);
+        let endpoints: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r["local_address"].as_str().unwrap(),
+                    r["interface"].as_str(),
+                    r["local_port"].as_i64(),
+                    r["peer_address"].as_str().unwrap(),
+                    r["peer_port"].as_i64(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            endpoints,
+            vec![
+                ("127.0.0.53", Some("lo"), Some(53), "0.0.0.0", None),
+                ("::", None, Some(22), "::", None),
+                ("fe80::1c2:3ff:fe4:5", Some("eth0"), Some(546), "::", None),
+                ("::ffff:10.0.0.5", None, Some(22), "::ffff:10.0.0.9", Some(51234)),
+                ("*", None, Some(5353), "*", None),
+            ]
+        );
+        assert_eq!(rows[0]["netid"], "tcp");
+        assert_eq!(rows[0]["state"], "LISTEN");
+        assert_eq!(rows[0]["send_q"], 4096);
+        assert_eq!(rows[0]["process"], "systemd-resolve");
+        assert_eq!(rows[0]["pid"], 612);
+        assert_eq!(rows[1]["process"], "sshd");
+        assert_eq!(rows[1]["pid"], 900);
+        assert!(rows[2].get("process").is_none());
+    }
+
+    #[test]
+    fn addresses_are_scoped_by_kind() {
+        let cases = [
+            ("0.0.0.0", "unspecified"),
+            ("*", "unspecified"),
+            ("::", "unspecified"),
+            ("[::]", "unspecified"),
+            ("127.0.0.53", "loopback"),
+            ("::1", "loopback"),
+            ("10.1.2.3", "private"),
+            ("192.168.1.1", "private"),
+            ("::ffff:172.16.0.1", "private"),
+            ("fd12:3456::1", "private"),
+            ("169.254.0.1", "link-local"),
+            ("fe80::1%eth0", "link-local"),
+            ("[fe80::1]%eth0", "link-local"),
+            ("224.0.0.251", "multicast"),
+            ("ff02::fb", "multicast"),
+            ("8.8.8.8", "global"),
+            ("2001:db8::1", "global"),
+        ];
+        for (address, expected) in cases {
+            assert_eq!(parse_addr(address).map(scope), Some(expected), "{}", address);
+        }
+        assert_eq!(parse_addr("not an address"), None);
+    }
+
+    #[test]
+    fn networks_contain_addresses_of_the_same_family() {
+        let cases = [
+            ("10.0.0.0/8", "10.200.0.1", Some(true)),
+            ("10.0.0.0/8", "11.0.0.1", Some(false)),
+            ("192.168.1.0/24", "192.168.1.255", Some(true)),
+            ("192.168.1.0/24", "192.168.2.0", Some(false)),
+            ("0.0.0.0/0", "203.0.113.7", Some(true)),
+            ("10.0.0.5", "10.0.0.5", Some(true)),
+            ("10.0.0.5/32", "10.0.0.6", Some(false)),
+            ("10.0.0.0/8", "::ffff:10.0.0.1", Some(true)),
+            ("10.0.0.0/8", "[::ffff:10.0.0.1]", Some(true)),
+            ("10.0.0.0/8", "fe80::1", Some(false)),
+            ("fe80::/10", "fe80::1c2:3ff:fe4:5%eth0", Some(true)),
+            ("fe80::/10", "fec0::1", Some(false)),
+            ("2001:db8::/32", "2001:db8:ffff::1", Some(true)),
+            ("2001:db8::/32", "2001:db9::1", Some(false)),
+            ("::/0", "::1", Some(true)),
+            ("2001:db8::/32", "10.0.0.1", Some(false)),
+            ("10.0.0.0/33", "10.0.0.1", None),
+            ("10.0.0.0/x", "10.0.0.1", None),
+            ("10.0.0.0/8", "nope", None),
+        ];
+        for (cidr, address, expected) in cases {
+            assert_eq!(contains(cidr, address), expected, "{} {}", cidr, address);
+        }
+    }
+
+    #[tokio::test]
+    async fn cidr_functions_run_over_columns() {
+        let ctx = ZqueryContext::new().without_builtin_tables().build();
+        let batches = ctx
+            .sql(
+                "select address, cidr_contains('10.0.0.0/8', address) as inside, \
+                 cidr_network(address || '/16') as network, ip_scope(address) as scope \
+                 from (values ('10.1.2.3'), ('fe80::1'), (null)) as t(address)",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            pretty_format_batches(&batches).unwrap().to_string(),
+            "\
++----------+--------+-------------+------------+
+| address  | inside | network     | scope      |
++----------+--------+-------------+------------+
+| 10.1.2.3 | true   | 10.1.0.0/16 | private    |
+| fe80::1  | false  | fe80::/16   | link-local |
+|          |        |             |            |
++----------+--------+-------------+------------+"
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{exec_err, Result};
+use datafusion::scalar::ScalarValue;
+use datafusion_expr::{ColumnarValue, ScalarUDFImpl, Signature, TypeSignature, Volatility};
+use serde_json::{json, Value};
+use std::any::Any;
+use std::cmp::Ordering;
+
+use crate::net::map_strings;
+
+/// `rpm -qa` with a tab separated `--qf`, since rpm's default output runs the name and
+/// version together. A macro so `DETECT_PACKAGES` can `concat!` it.
+macro_rules! rpm_query_format {
+    () => {
+        r"%{NAME}\t%{EPOCH}\t%{VERSION}\t%{RELEASE}\t%{ARCH}\t%{INSTALLTIME}\t%{SOURCERPM}\t%{SUMMARY}\n"
+    };
+}
+
+pub const RPM_QUERY_FORMAT: &str = rpm_query_format!();
+
+/// Prints the name of the first package manager it finds and then its package list, for
+/// `parse_packages` to normalize.
+pub const DETECT_PACKAGES: &str = concat!(
+    r"
+if command -v dpkg-query >/dev/null; then
+  echo dpkg; dpkg-query -W -f='${db:Status-Abbrev}\t${Package}\t${Version}\t${Architecture}\t${source:Package}\n'
+elif command -v rpm >/dev/null; then
+  echo rpm; rpm -qa --qf '",
+    rpm_query_format!(),
+    r"'
+elif command -v apk >/dev/null; then
+  echo apk; apk list --installed
+elif command -v pacman >/dev/null; then
+  echo pacman; pacman -Q
+else
+  echo none
+fi"
+);
+
+/// Parses `rpm -qa --qf RPM_QUERY_FORMAT`.
+pub fn parse_rpm_qa(output: &str) -> Vec<Value> {
+    let mut rows = Vec::new();
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 8 {
+            continue;
+        }
+        let text = |s: &str| if s == "(none)" { None } else { Some(s.to_string()) };
+        rows.push(json!({
+            "name": fields[0],
+            "epoch": fields[1].parse::<i64>().ok(),
+            "version": fields[2],
+            "release": fields[3],
+            "architecture": text(fields[4]),
+            "installed": fields[5].parse::<i64>().ok(),
+            "source_rpm": text(fields[6]),
+            "summary": fields[7],
+        }));
+    }
+    rows
+}
+
+/// Splits apk's `musl-1.2.4-r2` into the name and the `version-rN` on the end.
+fn split_apk_name(package: &str) -> Option<(&str, &str)> {
+    let release = package.rfind('-')?;
+    let version = package[..release].rfind('-')?;
+    Some((&package[..version], &package[version + 1..]))
+}
+
+/// Parses `apk list --installed`, e.g. `musl-1.2.4-r2 x86_64 {musl} (MIT) [installed]`.
+pub fn parse_apk_list(output: &str) -> Vec<Value> {
+    let mut rows = Vec::new();
+    for line in output.lines() {
+        let (package, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let Some((name, version)) = split_apk_name(package) else {
+            continue;
+        };
+        // Licenses can have spaces, like `(MPL-2.0 AND MIT)`.
+        let between = |open: char, close: char| {
+            let start = rest.find(open)? + 1;
+            let end = start + rest[start..].find(close)?;
+            Some(rest[start..end].to_string())
+        };
+        rows.push(json!({
+            "name": name,
+            "version": version,
+            "architecture": rest.split_whitespace().next(),
+            "origin": between('{', '}'),
+            "license": between('(', ')'),
+        }));
+    }
+    rows
+}
+
+/// Parses `pacman -Q`, a name and version per line.
+pub fn parse_pacman_q(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, version)| json!({ "name": name, "version": version.trim() }))
+        .collect()
+}
+
+/// Parses `cargo install --list`, where each `name v1.2.3 (source):` line is followed
+/// by the binaries it installed, indented.
+pub fn parse_cargo_install_list(output: &str) -> Vec<Value> {
+    let mut rows: Vec<Value> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(Value::Array(binaries)) = rows.last_mut().and_then(|row| row.get_mut("binaries")) {
+                binaries.push(json!(line.trim()));
+            }
+            continue;
+        }
+        let line = line.trim_end_matches(':');
+        let (package, source) = match line.split_once(" (") {
+            Some((package, source)) => (package, Some(source.trim_end_matches(')'))),
+            None => (line, None),
+        };
+        let Some((name, version)) = package.split_once(' ') else {
+            continue;
+        };
+        rows.push(json!({
+            "name": name,
+            "version": version.trim_start_matches('v'),
+            "source": source,
+            "binaries": [],
+        }));
+    }
+    rows
+}
+
+/// Parses `DETECT_PACKAGES` into the same columns whichever package manager the host
+/// has: name, version, architecture, the source package or origin, and the manager.
+pub fn parse_packages(output: &str) -> Vec<Value> {
+    let mut lines = output.lines();
+    let manager = lines.next().unwrap_or("none").trim();
+    let rest = lines.collect::<Vec<_>>().join("\n");
+    let row = |name: &Value, version: Value, architecture: &Value, source: &Value| {
+        json!({
+            "name": name,
+            "version": version,
+            "architecture": architecture,
+            "source": source,
+            "manager": manager,
+        })
+    };
+    match manager {
+        "dpkg" => rest
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                // Only installed packages, not removed ones whose config is left behind.
+                if fields.len() < 5 || !fields[0].starts_with("ii") {
+                    return None;
+                }
+                Some(row(&json!(fields[1]), json!(fields[2]), &json!(fields[3]), &json!(fields[4])))
+            })
+            .collect(),
+        "rpm" => parse_rpm_qa(&rest)
+            .iter()
+            .map(|p| {
+                let text = |key: &str| p[key].as_str().unwrap_or("").to_string();
+                let version = match p["epoch"].as_i64() {
+                    Some(epoch) => format!("{}:{}-{}", epoch, text("version"), text("release")),
+                    None => format!("{}-{}", text("version"), text("release")),
+                };
+                row(&p["name"], json!(version), &p["architecture"], &p["source_rpm"])
+            })
+            .collect(),
+        "apk" => parse_apk_list(&rest)
+            .iter()
+            .map(|p| row(&p["name"], p["version"].clone(), &p["architecture"], &p["origin"]))
+            .collect(),
+        "pacman" => parse_pacman_q(&rest)
+            .iter()
+            .map(|p| row(&p["name"], p["version"].clone(), &Value::Null, &Value::Null))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// dpkg's ordering of a character within a non-digit run: letters first, then other
+/// characters, and `~` before even the end of the string.
+fn dpkg_order(c: Option<u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(c) => c as i32 + 256,
+    }
+}
+
+/// dpkg's `verrevcmp`, for the upstream version or the revision on their own.
+fn dpkg_compare_part(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let (ac, bc) = (dpkg_order(a.get(i).copied()), dpkg_order(b.get(j).copied()));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while i < a.len() && a[i].is_ascii_digit() && j < b.len() && b[j].is_ascii_digit() {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if i < a.len() && a[i].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Splits `epoch:version-release` into its parts, with a missing epoch as 0.
+fn split_evr(version: &str) -> (u64, &str, &str) {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => (epoch.parse().unwrap_or(0), rest),
+        _ => (0, version),
+    };
+    match rest.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, release),
+        None => (epoch, rest, ""),
+    }
+}
+
+fn dpkg_compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (split_evr(a), split_evr(b));
+    a.0.cmp(&b.0)
+        .then_with(|| dpkg_compare_part(a.1, b.1))
+        .then_with(|| dpkg_compare_part(a.2, b.2))
+}
+
+/// rpm's `rpmvercmp`, comparing runs of digits numerically and runs of letters
+/// alphabetically, with `~` sorting before anything and `^` after the end.
+fn rpm_compare_part(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let separator = |c: &u8| !c.is_ascii_alphanumeric() && *c != b'~' && *c != b'^';
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    while !a.is_empty() || !b.is_empty() {
+        while a.first().is_some_and(separator) {
+            a = &a[1..];
+        }
+        while b.first().is_some_and(separator) {
+            b = &b[1..];
+        }
+        match (a.first(), b.first()) {
+            (Some(b'~'), Some(b'~')) => {
+                a = &a[1..];
+                b = &b[1..];
+                continue;
+            }
+            (Some(b'~'), _) => return Ordering::Less,
+            (_, Some(b'~')) => return Ordering::Greater,
+            (Some(b'^'), Some(b'^')) => {
+                a = &a[1..];
+                b = &b[1..];
+                continue;
+            }
+            (None, Some(b'^')) => return Ordering::Less,
+            (Some(b'^'), None) => return Ordering::Greater,
+            (Some(b'^'), _) => return Ordering::Less,
+            (_, Some(b'^')) => return Ordering::Greater,
+            (None, _) | (_, None) => break,
+            _ => {}
+        }
+        let is_num = a[0].is_ascii_digit();
+        let same_kind = |c: &u8| if is_num { c.is_ascii_digit() } else { c.is_ascii_alphabetic() };
+        let a_len = a.iter().take_while(|c| same_kind(c)).count();
+        let b_len = b.iter().take_while(|c| same_kind(c)).count();
+        let (a_seg, b_seg) = (&a[..a_len], &b[..b_len]);
+        a = &a[a_len..];
+        b = &b[b_len..];
+        // A number is newer than letters in the same place.
+        if b_seg.is_empty() {
+            return if is_num { Ordering::Greater } else { Ordering::Less };
+        }
+        let order = if is_num {
+            let trim = |s: &[u8]| -> Vec<u8> { s.iter().copied().skip_while(|c| *c == b'0').collect() };
+            let (a_seg, b_seg) = (trim(a_seg), trim(b_seg));
+            a_seg.len().cmp(&b_seg.len()).then_with(|| a_seg.cmp(&b_seg))
+        } else {
+            a_seg.cmp(b_seg)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (false, _) => Ordering::Greater,
+        (_, false) => Ordering::Less,
+    }
+}
+
+fn rpm_compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (split_evr(a), split_evr(b));
+    a.0.cmp(&b.0)
+        .then_with(|| rpm_compare_part(a.1, b.1))
+        .then_with(|| rpm_compare_part(a.2, b.2))
+}
+
+/// Semantic versioning's precedence: numeric major.minor.patch, a pre-release sorting
+/// before the release, and build metadata ignored. A leading `v` is allowed.
+fn semver_compare(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> (Vec<u64>, Option<String>) {
+        let v = v.trim().trim_start_matches('v');
+        let v = v.split('+').next().unwrap_or(v);
+        let (core, pre) = match v.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (v, None),
+        };
+        (core.split('.').map(|n| n.parse().unwrap_or(0)).collect(), pre)
+    };
+    let ((a_core, a_pre), (b_core, b_pre)) = (split(a), split(b));
+    for i in 0..a_core.len().max(b_core.len()) {
+        let order = a_core.get(i).unwrap_or(&0).cmp(b_core.get(i).unwrap_or(&0));
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => {
+            let (a, b): (Vec<&str>, Vec<&str>) = (a.split('.').collect(), b.split('.').collect());
+            for (x, y) in a.iter().zip(&b) {
+                let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => x.cmp(y),
+                };
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+    }
+}
+
+/// The comparison for a `version_cmp` scheme. The `manager` names from `packages()`
+/// work too, with pacman ordering versions the way rpm does.
+fn comparison(scheme: &str) -> Option<fn(&str, &str) -> Ordering> {
+    match scheme {
+        "deb" | "dpkg" => Some(dpkg_compare),
+        "rpm" | "pacman" => Some(rpm_compare),
+        "semver" => Some(semver_compare),
+        _ => None,
+    }
+}
+
+/// `version_cmp(a, b, scheme)` is -1, 0 or 1 as version `a` is older than, the same as
+/// or newer than `b`. The scheme is `deb` (the default), `rpm` or `semver`.
+#[derive(Debug)]
+pub struct VersionCmp {
+    signature: Signature,
+}
+
+impl VersionCmp {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::one_of(
+                vec![TypeSignature::Any(2), TypeSignature::Any(3)],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl ScalarUDFImpl for VersionCmp {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "version_cmp"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        if let Some(ColumnarValue::Scalar(ScalarValue::Utf8(Some(scheme)))) = args.get(2) {
+            if comparison(scheme).is_none() {
+                return exec_err!("Unknown version scheme '{}', expected deb, rpm or semver", scheme);
+            }
+        }
+        map_strings(args, &DataType::Int64, |row| {
+            let (Some(a), Some(b)) = (row[0], row[1]) else {
+                return ScalarValue::Int64(None);
+            };
+            let Some(compare) = comparison(row.get(2).copied().flatten().unwrap_or("deb")) else {
+                return ScalarValue::Int64(None);
+            };
+            let order = compare(a, b);
+            ScalarValue::Int64(Some(order as i64))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::Int64Type;
+
+    /// Checks each version is older than the next, both ways round, and equal to itself.
+    fn assert_ascending(compare: fn(&str, &str) -> Ordering, versions: &[&str]) {
+        for pair in versions.windows(2) {
+            assert_eq!(compare(pair[0], pair[1]), Ordering::Less, "{} < {}", pair[0], pair[1]);
+            assert_eq!(compare(pair[1], pair[0]), Ordering::Greater, "{} > {}", pair[1], pair[0]);
+        }
+        for version in versions {
+            assert_eq!(compare(version, version), Ordering::Equal, "{}", version);
+        }
+    }
+
+    #[test]
+    fn dpkg_versions() {
+        assert_ascending(
+            dpkg_compare,
+            &["1.0~~", "1.0~rc1", "1.0~rc2", "1.0", "1.0-1", "1.0-2", "1.0a", "1.0+b1", "1.9", "1.10", "1:0.1"],
+        );
+        assert_eq!(dpkg_compare("0:1.0-1", "1.0-1"), Ordering::Equal);
+        assert_eq!(dpkg_compare("1.00", "1.0"), Ordering::Equal);
+        assert_eq!(dpkg_compare("2:1.0", "1:9.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn rpm_versions() {
+        assert_ascending(
+            rpm_compare,
+            &["1.0~rc1", "1.0~rc2", "1.0", "1.0^git1", "1.0a", "1.0.1", "1.0.1-1.el8", "1.0.1-1.el9", "1.0.1-2", "10", "1:0.1"],
+        );
+        assert_eq!(rpm_compare("2.0.1a", "2.0.1b"), Ordering::Less);
+        assert_eq!(rpm_compare("1.001", "1.1"), Ordering::Equal);
+        assert_eq!(rpm_compare("1.0_1", "1.0.1"), Ordering::Equal);
+        assert_eq!(rpm_compare("1.0^", "1.0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn semver_versions() {
+        assert_ascending(
+            semver_compare,
+            &[
+                "1.0.0-alpha",
+                "1.0.0-alpha.1",
+                "1.0.0-alpha.beta",
+                "1.0.0-beta",
+                "1.0.0-beta.2",
+                "1.0.0-beta.11",
+                "1.0.0-rc.1",
+                "1.0.0",
+                "1.9.0",
+                "1.10.0",
+            ],
+        );
+        assert_eq!(semver_compare("v1.2.3", "1.2.3+build.5"), Ordering::Equal);
+    }
+
+    #[tokio::test]
+    async fn version_cmp_takes_a_scheme() {
+        let ctx = ZqueryContext::new().build();
+        let sql = "select version_cmp('1:1.0', '2.0'), version_cmp('1.0~rc1', '1.0', 'rpm'), \
+                   version_cmp('1.0.0-rc.1', '1.0.0-beta.11', 'semver'), version_cmp('1.0', null)";
+        let batches = sql_to_df(&ctx, sql).await.unwrap().collect().await.unwrap();
+        let row: Vec<Option<i64>> = batches[0]
+            .columns()
+            .iter()
+            .map(|c| c.as_primitive::<Int64Type>().iter().next().flatten())
+            .collect();
+        assert_eq!(row, [Some(1), Some(-1), Some(1), None]);
+
+        let sql = "select version_cmp('1.0', '2.0', 'msi')";
+        let error = sql_to_df(&ctx, sql).await.unwrap().collect().await.unwrap_err();
+        assert!(error.to_string().contains("Unknown version scheme 'msi'"), "{}", error);
+    }
+
+    #[test]
+    fn detect_packages_queries_rpm_with_the_same_format() {
+        assert!(DETECT_PACKAGES.contains(&format!("rpm -qa --qf '{}'\n", RPM_QUERY_FORMAT)));
+    }
+}
@@ -0,0 +1,316 @@
+use serde_json::{json, Map, Value};
+
+/// Checks the interval and count, `$1` and `$2`, are whole numbers, then prints the time
+/// and interval so the parser can tell when each sample was taken.
+macro_rules! sampled {
+    ($command:literal) => {
+        concat!(
+            r#"for n in "${1:-1}" "${2:-5}"; do case $n in *[!0-9]*|0) echo "$0: interval and count must be positive whole numbers" >&2; exit 1;; esac; done; echo "$(date +%s) ${1:-1}"; "#,
+            $command
+        )
+    };
+}
+
+/// One sample more than asked for, since vmstat's first is the average since boot.
+pub const VMSTAT: &str = sampled!(r#"exec env LC_ALL=C vmstat -n "${1:-1}" $((${2:-5} + 1))"#);
+
+/// `-y` leaves out the report since boot.
+pub const IOSTAT: &str = sampled!(r#"exec env LC_ALL=C iostat -d -x -k -y "${1:-1}" "${2:-5}""#);
+
+pub const MPSTAT: &str = sampled!(r#"exec env LC_ALL=C S_TIME_FORMAT=ISO mpstat -P ALL "${1:-1}" "${2:-5}""#);
+
+/// `-h` puts each process on one line, with the time of the sample.
+pub const PIDSTAT: &str = sampled!(r#"exec env LC_ALL=C pidstat -u -r -h "${1:-1}" "${2:-5}""#);
+
+/// One frame more than asked for, since top's first has nothing to measure CPU use over.
+pub const TOP: &str = sampled!(r#"exec env LC_ALL=C top -b -w 512 -d "${1:-1}" -n $((${2:-5} + 1))"#);
+
+/// Reads the time and interval the `sampled!` scripts print first, returning a function
+/// from a sample's number, starting at 1, to when it was taken.
+fn sample_times(output: &str) -> Option<(impl Fn(usize) -> i64, &str)> {
+    let (first, rest) = output.split_once('\n')?;
+    let (start, interval) = first.split_once(' ')?;
+    let (start, interval) = (start.parse::<i64>().ok()?, interval.trim().parse::<i64>().ok()?);
+    Some((move |sample: usize| start + sample as i64 * interval, rest))
+}
+
+/// A number from a stats column, as an integer where it is one.
+fn number(field: &str) -> Value {
+    match field.parse::<i64>() {
+        Ok(n) => json!(n),
+        Err(_) => json!(field.parse::<f64>().ok()),
+    }
+}
+
+/// Parses `VMSTAT`, naming each of vmstat's columns.
+pub fn parse_vmstat(output: &str) -> Vec<Value> {
+    const COLUMNS: &[(&str, &str)] = &[
+        ("r", "runnable"),
+        ("b", "blocked"),
+        ("swpd", "swap_used_kb"),
+        ("free", "free_kb"),
+        ("buff", "buffers_kb"),
+        ("cache", "cache_kb"),
+        ("si", "swap_in_kb_per_sec"),
+        ("so", "swap_out_kb_per_sec"),
+        ("bi", "blocks_in_per_sec"),
+        ("bo", "blocks_out_per_sec"),
+        ("in", "interrupts_per_sec"),
+        ("cs", "context_switches_per_sec"),
+        ("us", "user_percent"),
+        ("sy", "system_percent"),
+        ("id", "idle_percent"),
+        ("wa", "iowait_percent"),
+        ("st", "steal_percent"),
+        ("gu", "guest_percent"),
+    ];
+    let Some((time, output)) = sample_times(output) else {
+        return Vec::new();
+    };
+    let mut header: Vec<&str> = Vec::new();
+    let mut rows = Vec::new();
+    let mut sample = 0;
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first() == Some(&"r") {
+            header = fields;
+            continue;
+        }
+        if header.is_empty() || fields.len() != header.len() || fields[0].parse::<i64>().is_err() {
+            continue;
+        }
+        // The first line is the average since boot.
+        if sample > 0 {
+            let mut row = Map::new();
+            row.insert("sample_ts".into(), json!(time(sample)));
+            for (name, field) in header.iter().zip(&fields) {
+                if let Some((_, column)) = COLUMNS.iter().find(|(n, _)| n == name) {
+                    row.insert(column.to_string(), number(field));
+                }
+            }
+            rows.push(Value::Object(row));
+        }
+        sample += 1;
+    }
+    rows
+}
+
+/// Parses `IOSTAT`, one row per device per report. Each report starts with a `Device`
+/// header, whose columns vary between sysstat versions.
+pub fn parse_iostat(output: &str) -> Vec<Value> {
+    const COLUMNS: &[(&str, &str)] = &[
+        ("r/s", "reads_per_sec"),
+        ("w/s", "writes_per_sec"),
+        ("rkB/s", "read_kb_per_sec"),
+        ("wkB/s", "write_kb_per_sec"),
+        ("rrqm/s", "read_merges_per_sec"),
+        ("wrqm/s", "write_merges_per_sec"),
+        ("r_await", "read_await_ms"),
+        ("w_await", "write_await_ms"),
+        ("aqu-sz", "queue_size"),
+        ("avgqu-sz", "queue_size"),
+        ("%util", "util_percent"),
+    ];
+    let Some((time, output)) = sample_times(output) else {
+        return Vec::new();
+    };
+    let mut header: Vec<&str> = Vec::new();
+    let mut rows = Vec::new();
+    let mut sample = 0;
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.first() {
+            Some(&"Device") | Some(&"Device:") => {
+                header = fields;
+                sample += 1;
+            }
+            Some(device) if sample > 0 && fields.len() == header.len() => {
+                let mut row = Map::new();
+                row.insert("sample_ts".into(), json!(time(sample)));
+                row.insert("device".into(), json!(device));
+                for (name, field) in header.iter().zip(&fields).skip(1) {
+                    if let Some((_, column)) = COLUMNS.iter().find(|(n, _)| n == name) {
+                        row.insert(column.to_string(), number(field));
+                    }
+                }
+                rows.push(Value::Object(row));
+            }
+            _ => {}
+        }
+    }
+    rows
+}
+
+/// Parses `MPSTAT`, one row per CPU, plus one for `all`, per report. The columns are
+/// matched from the right since the time before them can be one field or two.
+pub fn parse_mpstat(output: &str) -> Vec<Value> {
+    let Some((time, output)) = sample_times(output) else {
+        return Vec::new();
+    };
+    let mut header: Vec<&str> = Vec::new();
+    let mut rows = Vec::new();
+    let mut sample = 0;
+    for line in output.lines() {
+        if line.starts_with("Average") {
+            break;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let Some(cpu) = fields.iter().position(|f| *f == "CPU") {
+            header = fields[cpu..].to_vec();
+            sample += 1;
+            continue;
+        }
+        if header.is_empty() || fields.len() <= header.len() {
+            continue;
+        }
+        let values = &fields[fields.len() - header.len()..];
+        let mut row = Map::new();
+        row.insert("sample_ts".into(), json!(time(sample)));
+        row.insert("cpu".into(), json!(values[0]));
+        for (name, field) in header.iter().zip(values).skip(1) {
+            if let Some(name) = name.strip_prefix('%') {
+                row.insert(format!("{}_percent", name), number(field));
+            }
+        }
+        rows.push(Value::Object(row));
+    }
+    rows
+}
+
+/// Parses `PIDSTAT`, one row per process per sample, using the time pidstat gives each.
+pub fn parse_pidstat(output: &str) -> Vec<Value> {
+    const COLUMNS: &[(&str, &str)] = &[
+        ("UID", "uid"),
+        ("PID", "pid"),
+        ("%usr", "user_percent"),
+        ("%system", "system_percent"),
+        ("%guest", "guest_percent"),
+        ("%wait", "wait_percent"),
+        ("%CPU", "cpu_percent"),
+        ("CPU", "cpu"),
+        ("minflt/s", "minor_faults_per_sec"),
+        ("majflt/s", "major_faults_per_sec"),
+        ("VSZ", "vsz_kb"),
+        ("RSS", "rss_kb"),
+        ("%MEM", "mem_percent"),
+    ];
+    let Some((_, output)) = sample_times(output) else {
+        return Vec::new();
+    };
+    let mut header: Vec<&str> = Vec::new();
+    let mut rows = Vec::new();
+    for line in output.lines() {
+        if let Some(names) = line.strip_prefix('#') {
+            header = names.split_whitespace().collect();
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // The command is last, and with `-l` can have spaces in it.
+        if header.last() != Some(&"Command") || fields.len() < header.len() {
+            continue;
+        }
+        let mut row = Map::new();
+        for (name, field) in header.iter().zip(&fields) {
+            match *name {
+                "Time" => row.insert("sample_ts".into(), json!(field.parse::<i64>().ok())),
+                "Command" => row.insert("command".into(), json!(fields[header.len() - 1..].join(" "))),
+                name => COLUMNS
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .and_then(|(_, column)| row.insert(column.to_string(), number(field))),
+            };
+        }
+        rows.push(Value::Object(row));
+    }
+    rows
+}
+
+/// Parses top's memory columns, which are KiB unless they end in `m`, `g` and so on.
+fn kib(field: &str) -> Option<i64> {
+    let (number, scale) = match field.char_indices().last()? {
+        (i, 'm') => (&field[..i], 1024.0),
+        (i, 'g') => (&field[..i], 1024.0 * 1024.0),
+        (i, 't') => (&field[..i], 1024.0 * 1024.0 * 1024.0),
+        (i, 'p') => (&field[..i], 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        _ => (field, 1.0),
+    };
+    Some((number.parse::<f64>().ok()? * scale).round() as i64)
+}
+
+/// Parses `TOP`, one row per process per frame, skipping the first frame.
+pub fn parse_top(output: &str) -> Vec<Value> {
+    let Some((time, output)) = sample_times(output) else {
+        return Vec::new();
+    };
+    let mut rows = Vec::new();
+    let (mut frame, mut in_processes) = (0, false);
+    for line in output.lines() {
+        if line.starts_with("top - ") {
+            frame += 1;
+            in_processes = false;
+            continue;
+        }
+        if line.trim_start().starts_with("PID") {
+            in_processes = true;
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if !in_processes || frame < 2 || fields.len() < 12 {
+            continue;
+        }
+        rows.push(json!({
+            "sample_ts": time(frame - 1),
+            "pid": fields[0].parse::<i64>().ok(),
+            "user": fields[1],
+            "priority": fields[2],
+            "nice": fields[3].parse::<i64>().ok(),
+            "virt_kb": kib(fields[4]),
+            "res_kb": kib(fields[5]),
+            "shr_kb": kib(fields[6]),
+            "state": fields[7],
+            "cpu_percent": fields[8].parse::<f64>().ok(),
+            "mem_percent": fields[9].parse::<f64>().ok(),
+            "time": fields[10],
+            "command": fields[11..].join(" "),
+        }));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::iostat_table_func;
+    use crate::commandtable::CommandTableFunc;
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use crate::error::ZqueryError;
+
+    async fn query(ctx: &datafusion::prelude::SessionContext, sql: &str) -> Result<usize, ZqueryError> {
+        let batches = sql_to_df(ctx, sql).await?.collect().await?;
+        Ok(batches.iter().map(|b| b.num_rows()).sum())
+    }
+
+    #[tokio::test]
+    async fn a_bad_argument_is_an_error() {
+        let ctx = ZqueryContext::new().build();
+        for sql in ["select * from vmstat(count => 'abc')", "select * from mpstat(0)", "select * from top(1, -1)"] {
+            let error = query(&ctx, sql).await.unwrap_err();
+            assert_eq!(error.exit_code(), 4, "{}: {}", sql, error);
+            assert!(error.to_string().contains("must be positive whole numbers"), "{}: {}", sql, error);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_missing_tool_is_an_error() {
+        // Like a host without sysstat.
+        let iostat = CommandTableFunc {
+            command: vec!["env", "PATH=/nonexistent", "/bin/sh", "-c", IOSTAT, "iostat"],
+            ..iostat_table_func().as_ref().clone()
+        };
+        let ctx = ZqueryContext::new().with_command_table("iostat", iostat).build();
+        let error = query(&ctx, "select * from iostat(1, 1)").await.unwrap_err();
+        assert_eq!(error.exit_code(), 4, "{}", error);
+        assert!(error.to_string().contains("not found"), "{}", error);
+    }
+}
@@ -0,0 +1,710 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use datafusion::arrow::array::{Array, ArrayRef, AsArray, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{
+    DataType, Date32Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Schema, TimeUnit,
+    TimestampMicrosecondType,
+};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use datafusion::error::DataFusionError;
+use datafusion::execution::context::SessionContext;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::scalar::ScalarValue;
+use datafusion::sql::parser::Statement as DFStatement;
+use datafusion::sql::sqlparser::ast::{
+    Expr as SQLExpr, Statement, TableFactor, Value, Visit, Visitor,
+};
+use futures::{stream, StreamExt};
+use pgwire::api::auth::md5pass::{hash_md5_password, Md5PasswordAuthStartupHandler};
+use pgwire::api::auth::{AuthSource, DefaultServerParameterProvider, LoginInfo, Password};
+use pgwire::api::copy::NoopCopyHandler;
+use pgwire::api::portal::{Format, Portal};
+use pgwire::api::query::{ExtendedQueryHandler, SimpleQueryHandler};
+use pgwire::api::results::{
+    DataRowEncoder, DescribePortalResponse, DescribeStatementResponse, FieldFormat, FieldInfo,
+    QueryResponse, Response, Tag,
+};
+use pgwire::api::stmt::{NoopQueryParser, StoredStatement};
+use pgwire::api::{ClientInfo, PgWireHandlerFactory, Type, METADATA_USER};
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::data::DataRow;
+use pgwire::tokio::process_socket;
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use crate::engine::{
+    execute_streaming, normalize_ident, set_up, spawn_query, sql_to_statements, statement_to_plan,
+};
+
+/// A `users.toml` file for `zquery serve --pg`.
+///
+/// ```toml
+/// [[user]]
+/// name = "admin"
+/// password = "hunter2"
+///
+/// [[user]]
+/// name = "grafana"
+/// password = "correct horse"
+/// tables = ["ps", "df", "uptime"]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct UsersFile {
+    #[serde(rename = "user")]
+    pub users: Vec<User>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub name: String,
+    pub password: String,
+    /// The table functions this user may call. Users without a list can call any of
+    /// them and run DDL; users with one can only run queries.
+    pub tables: Option<Vec<String>>,
+}
+
+impl UsersFile {
+    fn get(&self, name: &str) -> Option<&User> {
+        self.users.iter().find(|u| u.name == name)
+    }
+}
+
+pub fn load_users(path: &Path) -> std::io::Result<UsersFile> {
+    let text = std::fs::read_to_string(path)?;
+    toml::from_str(&text).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Serves the Postgres wire protocol on `addr`, with a fresh session per connection so
+/// tables one client creates aren't visible to the others.
+pub async fn serve_pg(addr: &str, users: UsersFile) -> std::io::Result<()> {
+    let users = Arc::new(users);
+    let listener = TcpListener::bind(addr).await?;
+    eprintln!("Listening for Postgres connections on {}", addr);
+    loop {
+        let (socket, _) = listener.accept().await?;
+        let ctx = set_up().await?;
+        let factory = Arc::new(PgHandlerFactory {
+            session: Arc::new(PgSession {
+                ctx,
+                users: users.clone(),
+                parser: Arc::new(NoopQueryParser::new()),
+            }),
+            users: users.clone(),
+        });
+        tokio::spawn(async move {
+            if let Err(e) = process_socket(socket, None, factory).await {
+                eprintln!("Postgres connection failed: {}", e);
+            }
+        });
+    }
+}
+
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+struct UserAuth(Arc<UsersFile>);
+
+#[async_trait]
+impl AuthSource for UserAuth {
+    async fn get_password(&self, login: &LoginInfo) -> PgWireResult<Password> {
+        let salt = random_u64().to_le_bytes()[..4].to_vec();
+        let name = login.user().unwrap_or_default();
+        // Unknown users get a password nobody knows, so they fail the same way a
+        // wrong password does.
+        let password = match self.0.get(name) {
+            Some(user) => user.password.clone(),
+            None => format!("{:x}{:x}", random_u64(), random_u64()),
+        };
+        let hash = hash_md5_password(name, &password, &salt);
+        Ok(Password::new(Some(salt), hash.as_bytes().to_vec()))
+    }
+}
+
+struct PgHandlerFactory {
+    session: Arc<PgSession>,
+    users: Arc<UsersFile>,
+}
+
+impl PgWireHandlerFactory for PgHandlerFactory {
+    type StartupHandler = Md5PasswordAuthStartupHandler<UserAuth, DefaultServerParameterProvider>;
+    type SimpleQueryHandler = PgSession;
+    type ExtendedQueryHandler = PgSession;
+    type CopyHandler = NoopCopyHandler;
+
+    fn simple_query_handler(&self) -> Arc<Self::SimpleQueryHandler> {
+        self.session.clone()
+    }
+
+    fn extended_query_handler(&self) -> Arc<Self::ExtendedQueryHandler> {
+        self.session.clone()
+    }
+
+    fn startup_handler(&self) -> Arc<Self::StartupHandler> {
+        // Clients pick which catalog queries to send from the version, so claim a
+        // modern Postgres rather than pgwire's own version number.
+        let mut parameters = DefaultServerParameterProvider::default();
+        parameters.server_version = "16.0".to_string();
+        Arc::new(Md5PasswordAuthStartupHandler::new(
+            Arc::new(UserAuth(self.users.clone())),
+            Arc::new(parameters),
+        ))
+    }
+
+    fn copy_handler(&self) -> Arc<Self::CopyHandler> {
+        Arc::new(NoopCopyHandler)
+    }
+}
+
+/// One client connection and its own SessionContext.
+struct PgSession {
+    ctx: SessionContext,
+    users: Arc<UsersFile>,
+    parser: Arc<NoopQueryParser>,
+}
+
+impl PgSession {
+    /// Checks the statement only calls table functions the user is allowed, including
+    /// ones nested inside others like `stream(ps())`.
+    fn authorize<C: ClientInfo>(&self, client: &C, statement: &DFStatement) -> PgWireResult<()> {
+        let name = client.metadata().get(METADATA_USER).cloned().unwrap_or_default();
+        let allowed = match self.users.get(&name).map(|u| u.tables.as_ref()) {
+            Some(None) => return Ok(()),
+            Some(Some(tables)) => tables,
+            None => return Err(user_error("28000", format!("Unknown user {}", name))),
+        };
+        let statement = match statement {
+            DFStatement::Statement(statement) => statement,
+            DFStatement::Explain(explain) => return self.authorize(client, &explain.statement),
+            _ => return Err(permission_denied(&name, "run this statement")),
+        };
+        let table_functions = self.ctx.state().table_functions().clone();
+        if let Statement::ExplainTable { table_name, .. } = statement.as_ref() {
+            let table = match table_name.0.as_slice() {
+                [name] => normalize_ident(name),
+                _ => table_name.to_string(),
+            };
+            if table_functions.contains_key(&table) && !allowed.contains(&table) {
+                return Err(permission_denied(&name, &format!("describe {}", table)));
+            }
+            return Ok(());
+        }
+        if !matches!(
+            statement.as_ref(),
+            Statement::Query(_)
+                | Statement::SetVariable { .. }
+                | Statement::ShowVariable { .. }
+                | Statement::ShowTables { .. }
+                | Statement::ShowColumns { .. }
+        ) {
+            return Err(permission_denied(&name, "run this statement"));
+        }
+
+        let mut called = CalledFunctions::default();
+        let _ = statement.visit(&mut called);
+        for function in called.0 {
+            if table_functions.contains_key(&function) && !allowed.contains(&function) {
+                return Err(permission_denied(&name, &format!("call {}()", function)));
+            }
+        }
+        Ok(())
+    }
+
+    async fn execute<C: ClientInfo>(
+        &self,
+        client: &C,
+        statement: DFStatement,
+    ) -> PgWireResult<Response<'static>> {
+        self.authorize(client, &statement)?;
+        if is_client_setting(&statement) {
+            return Ok(Response::Execution(Tag::new("SET")));
+        }
+        let plan = self.plan(statement).await?;
+        self.respond(plan, &Format::UnifiedText).await
+    }
+
+    /// Plans on a thread of its own, since table functions may run commands to find
+    /// their columns.
+    async fn plan(&self, statement: DFStatement) -> PgWireResult<LogicalPlan> {
+        let ctx = self.ctx.clone();
+        spawn_query(async move { statement_to_plan(&ctx, statement).await })
+            .await
+            .map_err(|e| PgWireError::ApiError(Box::new(e)))?
+            .map_err(pg_error)
+    }
+
+    async fn respond(&self, plan: LogicalPlan, format: &Format) -> PgWireResult<Response<'static>> {
+        let (schema, batches) = execute_streaming(self.ctx.clone(), plan).await.map_err(pg_error)?;
+        // DDL has already run by this point and has nothing to send back.
+        if schema.fields().is_empty() {
+            return Ok(Response::Execution(Tag::new("OK")));
+        }
+        let fields = Arc::new(field_infos(&schema, format));
+
+        let row_fields = fields.clone();
+        let rows = batches.flat_map(move |batch| {
+            let rows = match batch {
+                Ok(batch) => encode_batch(&batch, &row_fields),
+                Err(e) => vec![Err(pg_error(e))],
+            };
+            stream::iter(rows)
+        });
+        Ok(Response::Query(QueryResponse::new(fields, rows)))
+    }
+
+    /// Parses and plans a prepared statement, returning its inferred parameter types.
+    async fn prepare<C: ClientInfo>(
+        &self,
+        client: &C,
+        sql: &str,
+        given: &[Type],
+    ) -> PgWireResult<(Option<LogicalPlan>, Vec<Type>)> {
+        let mut statements = sql_to_statements(&self.ctx, sql).map_err(pg_error)?;
+        let statement = match statements.len() {
+            0 => return Ok((None, Vec::new())),
+            1 => statements.remove(0),
+            _ => {
+                return Err(user_error(
+                    "42601",
+                    "Prepared statements can only hold a single statement".to_string(),
+                ))
+            }
+        };
+        self.authorize(client, &statement)?;
+        if is_client_setting(&statement) {
+            return Ok((None, Vec::new()));
+        }
+        let mut placeholders = Placeholders::default();
+        if let DFStatement::Statement(statement) = &statement {
+            let _ = statement.visit(&mut placeholders);
+        }
+        let plan = self.plan(statement).await?;
+        // Only placeholders DataFusion could infer a type for show up here.
+        let types = plan.get_parameter_types().map_err(pg_error)?;
+        let parameter_types = (0..placeholders.0)
+            .map(|i| match given.get(i) {
+                Some(ty) if *ty != Type::UNKNOWN => ty.clone(),
+                _ => match types.get(&format!("${}", i + 1)) {
+                    Some(Some(data_type)) => pg_type(data_type),
+                    _ => Type::TEXT,
+                },
+            })
+            .collect();
+        Ok((Some(plan), parameter_types))
+    }
+}
+
+#[async_trait]
+impl SimpleQueryHandler for PgSession {
+    async fn do_query<'a, 'b: 'a, C>(
+        &'b self,
+        client: &mut C,
+        query: &'a str,
+    ) -> PgWireResult<Vec<Response<'a>>>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let statements = sql_to_statements(&self.ctx, query).map_err(pg_error)?;
+        let mut responses = Vec::new();
+        for statement in statements {
+            match self.execute(client, statement).await {
+                Ok(response) => responses.push(response),
+                // Like Postgres, stop at the first statement that fails.
+                Err(PgWireError::UserError(e)) => {
+                    responses.push(Response::Error(e));
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(responses)
+    }
+}
+
+#[async_trait]
+impl ExtendedQueryHandler for PgSession {
+    type Statement = String;
+    type QueryParser = NoopQueryParser;
+
+    fn query_parser(&self) -> Arc<Self::QueryParser> {
+        self.parser.clone()
+    }
+
+    async fn do_query<'a, 'b: 'a, C>(
+        &'b self,
+        client: &mut C,
+        portal: &'a Portal<Self::Statement>,
+        _max_rows: usize,
+    ) -> PgWireResult<Response<'a>>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let sql = &portal.statement.statement;
+        let (plan, types) = self.prepare(client, sql, &portal.statement.parameter_types).await?;
+        let plan = match plan {
+            Some(plan) => plan,
+            None if sql.trim().trim_matches(';').is_empty() => return Ok(Response::EmptyQuery),
+            None => return Ok(Response::Execution(Tag::new("SET"))),
+        };
+        let values = types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| parameter(portal, i, ty))
+            .collect::<PgWireResult<Vec<_>>>()?;
+        let plan = plan.with_param_values(values).map_err(pg_error)?;
+        self.respond(plan, &portal.result_column_format).await
+    }
+
+    async fn do_describe_statement<C>(
+        &self,
+        client: &mut C,
+        target: &StoredStatement<Self::Statement>,
+    ) -> PgWireResult<DescribeStatementResponse>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let (plan, types) = self.prepare(client, &target.statement, &target.parameter_types).await?;
+        let fields = match plan {
+            Some(plan) => field_infos(plan.schema().as_arrow(), &Format::UnifiedText),
+            None => Vec::new(),
+        };
+        Ok(DescribeStatementResponse::new(types, fields))
+    }
+
+    async fn do_describe_portal<C>(
+        &self,
+        client: &mut C,
+        target: &Portal<Self::Statement>,
+    ) -> PgWireResult<DescribePortalResponse>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let statement = &target.statement;
+        let (plan, _) = self.prepare(client, &statement.statement, &statement.parameter_types).await?;
+        let fields = match plan {
+            Some(plan) => field_infos(plan.schema().as_arrow(), &target.result_column_format),
+            None => Vec::new(),
+        };
+        Ok(DescribePortalResponse::new(fields))
+    }
+}
+
+/// Names of every table factor and function a statement calls, as DataFusion will look
+/// them up, so quoting a name like `"ps"()` can't get it past the allowlist.
+#[derive(Default)]
+struct CalledFunctions(HashSet<String>);
+
+impl Visitor for CalledFunctions {
+    type Break = ();
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<()> {
+        // A table function is looked up by the first part of its name as written, and a
+        // table by its normalized name, so both are checked.
+        if let TableFactor::Table { name, .. } = table_factor {
+            if let Some(first) = name.0.first() {
+                self.0.insert(first.value.clone());
+                self.0.insert(normalize_ident(first));
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &SQLExpr) -> ControlFlow<()> {
+        if let SQLExpr::Function(function) = expr {
+            let name = match function.name.0.as_slice() {
+                [name] => normalize_ident(name),
+                _ => function.name.to_string(),
+            };
+            self.0.insert(name);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// The highest `$n` placeholder in a statement.
+#[derive(Default)]
+struct Placeholders(usize);
+
+impl Visitor for Placeholders {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &SQLExpr) -> ControlFlow<()> {
+        if let SQLExpr::Value(Value::Placeholder(id)) = expr {
+            if let Some(n) = id.strip_prefix('$').and_then(|n| n.parse().ok()) {
+                self.0 = self.0.max(n);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Clients like JDBC and DBeaver set Postgres options such as `extra_float_digits`
+/// when they connect. DataFusion would reject them, so they're accepted and ignored.
+fn is_client_setting(statement: &DFStatement) -> bool {
+    match statement {
+        DFStatement::Statement(statement) => match statement.as_ref() {
+            Statement::SetVariable { variables, .. } => {
+                !variables.to_string().to_lowercase().starts_with("datafusion.")
+            }
+            Statement::SetTimeZone { .. } | Statement::SetNames { .. } => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn user_error(code: &str, message: String) -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_string(),
+        code.to_string(),
+        message,
+    )))
+}
+
+fn permission_denied(user: &str, action: &str) -> PgWireError {
+    user_error("42501", format!("Permission denied: {} may not {}", user, action))
+}
+
+fn pg_error(e: DataFusionError) -> PgWireError {
+    let code = match e {
+        DataFusionError::SQL(..) => "42601",
+        DataFusionError::Plan(_) | DataFusionError::SchemaError(..) => "42000",
+        _ => "XX000",
+    };
+    user_error(code, e.to_string())
+}
+
+/// The Postgres type a column is sent as. Anything without a close match, like
+/// lists and structs, is sent as its text representation.
+fn pg_type(data_type: &DataType) -> Type {
+    match data_type {
+        DataType::Boolean => Type::BOOL,
+        DataType::Int8 | DataType::Int16 | DataType::UInt8 => Type::INT2,
+        DataType::Int32 | DataType::UInt16 => Type::INT4,
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => Type::INT8,
+        DataType::Float16 | DataType::Float32 => Type::FLOAT4,
+        DataType::Float64 => Type::FLOAT8,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => Type::VARCHAR,
+        DataType::Binary | DataType::LargeBinary => Type::BYTEA,
+        DataType::Date32 | DataType::Date64 => Type::DATE,
+        DataType::Timestamp(_, None) => Type::TIMESTAMP,
+        DataType::Timestamp(_, Some(_)) => Type::TIMESTAMPTZ,
+        _ => Type::TEXT,
+    }
+}
+
+/// The Arrow type each Postgres type is encoded from.
+fn arrow_type(ty: &Type) -> DataType {
+    match *ty {
+        Type::BOOL => DataType::Boolean,
+        Type::INT2 => DataType::Int16,
+        Type::INT4 => DataType::Int32,
+        Type::INT8 => DataType::Int64,
+        Type::FLOAT4 => DataType::Float32,
+        Type::FLOAT8 => DataType::Float64,
+        Type::BYTEA => DataType::Binary,
+        Type::DATE => DataType::Date32,
+        Type::TIMESTAMP => DataType::Timestamp(TimeUnit::Microsecond, None),
+        Type::TIMESTAMPTZ => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        _ => DataType::Utf8,
+    }
+}
+
+fn field_infos(schema: &Schema, format: &Format) -> Vec<FieldInfo> {
+    schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            FieldInfo::new(f.name().clone(), None, None, pg_type(f.data_type()), format.format_for(i))
+        })
+        .collect()
+}
+
+/// Casts a column to the Arrow type its Postgres type is encoded from.
+fn pg_column(array: &ArrayRef, ty: &Type) -> Result<ArrayRef, DataFusionError> {
+    let target = arrow_type(ty);
+    if *ty != Type::TEXT && *ty != Type::VARCHAR {
+        return Ok(cast(array, &target)?);
+    }
+    if array.data_type() == &DataType::Utf8 {
+        return Ok(array.clone());
+    }
+    let formatter = ArrayFormatter::try_new(array.as_ref(), &FormatOptions::default())?;
+    let strings: StringArray = (0..array.len())
+        .map(|i| (!array.is_null(i)).then(|| formatter.value(i).to_string()))
+        .collect();
+    Ok(Arc::new(strings))
+}
+
+fn encode_batch(batch: &RecordBatch, fields: &Arc<Vec<FieldInfo>>) -> Vec<PgWireResult<DataRow>> {
+    let columns = match batch
+        .columns()
+        .iter()
+        .zip(fields.iter())
+        .map(|(column, field)| pg_column(column, field.datatype()))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(columns) => columns,
+        Err(e) => return vec![Err(pg_error(e))],
+    };
+    (0..batch.num_rows())
+        .map(|row| {
+            let mut encoder = DataRowEncoder::new(fields.clone());
+            for column in &columns {
+                encode_value(&mut encoder, column, row)?;
+            }
+            encoder.finish()
+        })
+        .collect()
+}
+
+fn encode_value(encoder: &mut DataRowEncoder, column: &ArrayRef, row: usize) -> PgWireResult<()> {
+    if column.is_null(row) {
+        return encoder.encode_field(&None::<i8>);
+    }
+    match column.data_type() {
+        DataType::Boolean => encoder.encode_field(&column.as_boolean().value(row)),
+        DataType::Int16 => encoder.encode_field(&column.as_primitive::<Int16Type>().value(row)),
+        DataType::Int32 => encoder.encode_field(&column.as_primitive::<Int32Type>().value(row)),
+        DataType::Int64 => encoder.encode_field(&column.as_primitive::<Int64Type>().value(row)),
+        DataType::Float32 => encoder.encode_field(&column.as_primitive::<Float32Type>().value(row)),
+        DataType::Float64 => encoder.encode_field(&column.as_primitive::<Float64Type>().value(row)),
+        DataType::Binary => encoder.encode_field(&column.as_binary::<i32>().value(row)),
+        DataType::Date32 => {
+            let days = column.as_primitive::<Date32Type>().value(row);
+            encoder.encode_field(&NaiveDate::from_num_days_from_ce_opt(days + 719_163))
+        }
+        DataType::Timestamp(_, tz) => {
+            let micros = column.as_primitive::<TimestampMicrosecondType>().value(row);
+            let datetime = DateTime::from_timestamp_micros(micros);
+            match tz {
+                Some(_) => encoder.encode_field(&datetime),
+                None => encoder.encode_field(&datetime.map(|d| d.naive_utc())),
+            }
+        }
+        _ => encoder.encode_field(&column.as_string::<i32>().value(row)),
+    }
+}
+
+/// Reads a bound parameter as the ScalarValue for its type.
+fn parameter(portal: &Portal<String>, i: usize, ty: &Type) -> PgWireResult<ScalarValue> {
+    let data_type = arrow_type(ty);
+    let bytes = match portal.parameters.get(i) {
+        Some(Some(bytes)) => bytes,
+        Some(None) => return ScalarValue::try_from(&data_type).map_err(pg_error),
+        None => return Err(PgWireError::ParameterIndexOutOfBound(i)),
+    };
+    if portal.parameter_format.format_for(i) == FieldFormat::Text {
+        let text = String::from_utf8_lossy(bytes).to_string();
+        return ScalarValue::try_from_string(text, &data_type).map_err(pg_error);
+    }
+    Ok(match *ty {
+        Type::BOOL => ScalarValue::Boolean(portal.parameter(i, ty)?),
+        Type::INT2 => ScalarValue::Int16(portal.parameter(i, ty)?),
+        Type::INT4 => ScalarValue::Int32(portal.parameter(i, ty)?),
+        Type::INT8 => ScalarValue::Int64(portal.parameter(i, ty)?),
+        Type::FLOAT4 => ScalarValue::Float32(portal.parameter(i, ty)?),
+        Type::FLOAT8 => ScalarValue::Float64(portal.parameter(i, ty)?),
+        Type::BYTEA => ScalarValue::Binary(portal.parameter(i, ty)?),
+        Type::DATE => ScalarValue::Date32(
+            portal
+                .parameter::<NaiveDate>(i, ty)?
+                .map(|d| d.num_days_from_ce() - 719_163),
+        ),
+        Type::TIMESTAMP => ScalarValue::TimestampMicrosecond(
+            portal
+                .parameter::<NaiveDateTime>(i, ty)?
+                .map(|d| d.and_utc().timestamp_micros()),
+            None,
+        ),
+        _ => ScalarValue::Utf8(Some(String::from_utf8_lossy(bytes).to_string())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ZqueryContext;
+    use tokio_postgres::error::SqlState;
+    use tokio_postgres::NoTls;
+
+    fn called(sql: &str) -> HashSet<String> {
+        let ctx = ZqueryContext::new().build();
+        let mut statements = sql_to_statements(&ctx, sql).unwrap();
+        let DFStatement::Statement(statement) = statements.remove(0) else {
+            panic!("not a SQL statement: {}", sql);
+        };
+        let mut called = CalledFunctions::default();
+        let _ = statement.visit(&mut called);
+        called.0
+    }
+
+    #[test]
+    fn quoted_names_are_checked_as_datafusion_resolves_them() {
+        assert!(called(r#"select * from "ps"()"#).contains("ps"));
+        assert!(called("select * from PS()").contains("ps"));
+        assert!(called(r#"select * from stream("ps"())"#).contains("ps"));
+        assert!(called(r#"select * from stream("PS"())"#).contains("PS"));
+        assert!(!called(r#"select * from stream("PS"())"#).contains("ps"));
+    }
+
+    async fn connect(port: u16, user: &str) -> tokio_postgres::Client {
+        let config = format!("host=127.0.0.1 port={} user={} password=secret", port, user);
+        for _ in 0..50 {
+            if let Ok((client, connection)) = tokio_postgres::connect(&config, NoTls).await {
+                tokio::spawn(connection);
+                return client;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("couldn't connect to the Postgres server on port {}", port);
+    }
+
+    #[tokio::test]
+    async fn users_can_only_call_the_tables_they_are_allowed() {
+        let users: UsersFile = toml::from_str(
+            r#"
+            [[user]]
+            name = "admin"
+            password = "secret"
+
+            [[user]]
+            name = "monitor"
+            password = "secret"
+            tables = ["uptime"]
+            "#,
+        )
+        .unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        tokio::spawn(async move { serve_pg(&format!("127.0.0.1:{}", port), users).await });
+
+        let monitor = connect(port, "monitor").await;
+        let row = monitor.query_one("select 1 + 1", &[]).await.unwrap();
+        assert_eq!(row.get::<_, i64>(0), 2);
+        for sql in [
+            "select * from ps()",
+            r#"select * from "ps"()"#,
+            r#"select * from stream("ps"())"#,
+            "describe ps",
+            r#"describe "ps""#,
+            "create table t as select 1",
+        ] {
+            let e = monitor.simple_query(sql).await.unwrap_err();
+            assert_eq!(e.code(), Some(&SqlState::INSUFFICIENT_PRIVILEGE), "{}", sql);
+        }
+
+        let admin = connect(port, "admin").await;
+        admin.simple_query("create table t as select 1 as x").await.unwrap();
+        let row = admin.query_one("select x from t", &[]).await.unwrap();
+        assert_eq!(row.get::<_, i64>(0), 1);
+    }
+}
@@ -0,0 +1,321 @@
+use serde_json::{json, Map, Value};
+
+/// Runs a command from `/proc` over the pid given as `$1`, or every process when there's
+/// none, where `${1:-[0-9]*}` expands to each process's directory. Listing every process,
+/// those that exit or can't be read are skipped, but the one asked for has to be there.
+macro_rules! for_pids {
+    ($command:literal) => {
+        concat!(
+            r#"case "$1" in *[!0-9]*) echo "$0: pid must be a number" >&2; exit 1;; esac; cd /proc || exit 1; "#,
+            r#"[ -z "$1" ] || [ -d "$1" ] || { echo "$0: no process with pid $1" >&2; exit 1; }; { "#,
+            $command,
+            r#"; } || [ -z "$1" ]"#
+        )
+    };
+}
+
+/// `lsof`'s field output, one field per line tagged with its letter. lsof exits 1 when
+/// some files couldn't be listed, which only fails the table for one process.
+pub const LSOF: &str = concat!(
+    r#"case "$1" in *[!0-9]*) echo "$0: pid must be a number" >&2; exit 1;; esac; "#,
+    r#"[ -z "$1" ] || ps -p "$1" >/dev/null || { echo "$0: no process with pid $1" >&2; exit 1; }; "#,
+    r#"lsof -n -P -w -F pcuLfatsin ${1:+-p "$1"}; status=$?; [ $status -eq 1 ] && [ -z "$1" ] || exit $status"#
+);
+
+/// A `/proc/<pid>` file, named by `$0` without its `proc_`, each line prefixed with
+/// `<pid>/<file>:`. grep exits 1 for an empty file, like a kernel thread's `maps`.
+pub const PROC_FILE: &str = for_pids!(r#"grep -H '' ${1:-[0-9]*}/"${0#proc_}" || [ $? -eq 1 ]"#);
+
+/// A file of NUL separated strings named like `PROC_FILE`'s, such as `cmdline`, each
+/// process's starting with a record separator, its pid and a unit separator.
+pub const PROC_STRINGS: &str =
+    for_pids!(r#"for p in ${1:-[0-9]*}; do printf '\036%s\037' "$p"; cat "$p/${0#proc_}"; done"#);
+
+/// Where each open file descriptor points.
+pub const PROC_FDS: &str = for_pids!(r#"find ${1:-[0-9]*}/fd -mindepth 1 -maxdepth 1 -printf '%h/%f\t%l\n'"#);
+
+/// Splits `123/status:Name:\tbash` into the pid and the line.
+fn pid_and_line(line: &str) -> Option<(i64, &str)> {
+    let (pid, rest) = line.split_once('/')?;
+    let (_, line) = rest.split_once(':')?;
+    Some((pid.parse().ok()?, line))
+}
+
+/// Groups `PROC_FILE` output by process, in the order they came.
+fn by_pid(output: &str) -> Vec<(i64, Vec<&str>)> {
+    let mut processes: Vec<(i64, Vec<&str>)> = Vec::new();
+    for (pid, line) in output.lines().filter_map(pid_and_line) {
+        match processes.last_mut() {
+            Some((last, lines)) if *last == pid => lines.push(line),
+            _ => processes.push((pid, vec![line])),
+        }
+    }
+    processes
+}
+
+/// Parses `lsof -F`, one row per open file. Each process's fields come first, then each
+/// of its files starting with its `f` descriptor field.
+pub fn parse_lsof(output: &str) -> Vec<Value> {
+    let mut rows = Vec::new();
+    let mut process = Map::new();
+    let mut file: Option<Map<String, Value>> = None;
+    for line in output.lines() {
+        let Some(tag) = line.chars().next() else {
+            continue;
+        };
+        let value = &line[tag.len_utf8()..];
+        match tag {
+            'p' => {
+                rows.extend(file.take().map(Value::Object));
+                process = Map::new();
+                process.insert("pid".into(), json!(value.parse::<i64>().ok()));
+            }
+            'c' => {
+                process.insert("command".into(), json!(value));
+            }
+            'u' => {
+                process.insert("uid".into(), json!(value.parse::<i64>().ok()));
+            }
+            'L' => {
+                process.insert("user".into(), json!(value));
+            }
+            'f' => {
+                rows.extend(file.take().map(Value::Object));
+                let mut new = process.clone();
+                new.insert("fd".into(), json!(value));
+                file = Some(new);
+            }
+            _ => {
+                let Some(file) = file.as_mut() else { continue };
+                let (column, value) = match tag {
+                    'a' => ("access", json!(Some(value.trim()).filter(|a| !a.is_empty()))),
+                    't' => ("type", json!(value)),
+                    's' => ("size", json!(value.parse::<i64>().ok())),
+                    'i' => ("inode", json!(value.parse::<i64>().ok())),
+                    'n' => ("name", json!(value)),
+                    _ => continue,
+                };
+                file.insert(column.into(), value);
+            }
+        }
+    }
+    rows.extend(file.map(Value::Object));
+    rows
+}
+
+/// Parses `/proc/<pid>/status`, one row per process. Sizes are in kB, as the kernel
+/// writes them, and the ids are the real ones, with `euid` the effective user.
+pub fn parse_proc_status(output: &str) -> Vec<Value> {
+    const COLUMNS: &[(&str, &str)] = &[
+        ("Tgid", "tgid"),
+        ("PPid", "ppid"),
+        ("Threads", "threads"),
+        ("FDSize", "fd_size"),
+        ("VmPeak", "vm_peak_kb"),
+        ("VmSize", "vm_size_kb"),
+        ("VmRSS", "vm_rss_kb"),
+        ("VmSwap", "vm_swap_kb"),
+        ("voluntary_ctxt_switches", "voluntary_ctxt_switches"),
+        ("nonvoluntary_ctxt_switches", "nonvoluntary_ctxt_switches"),
+    ];
+    by_pid(output)
+        .into_iter()
+        .map(|(pid, lines)| {
+            let mut row = Map::new();
+            row.insert("pid".into(), json!(pid));
+            for line in lines {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let value = value.trim();
+                let ids = || -> Vec<Option<i64>> { value.split_whitespace().map(|id| id.parse().ok()).collect() };
+                match key {
+                    "Name" => row.insert("name".into(), json!(value)),
+                    // `S (sleeping)`
+                    "State" => row.insert("state".into(), json!(value.split_whitespace().next())),
+                    // Real, effective, saved and filesystem ids.
+                    "Uid" => {
+                        let ids = ids();
+                        row.insert("uid".into(), json!(ids.first().copied().flatten()));
+                        row.insert("euid".into(), json!(ids.get(1).copied().flatten()))
+                    }
+                    "Gid" => {
+                        let ids = ids();
+                        row.insert("gid".into(), json!(ids.first().copied().flatten()));
+                        row.insert("egid".into(), json!(ids.get(1).copied().flatten()))
+                    }
+                    "Umask" => row.insert("umask".into(), json!(value)),
+                    "CapEff" => row.insert("cap_eff".into(), json!(value)),
+                    key => COLUMNS.iter().find(|(name, _)| *name == key).and_then(|(_, column)| {
+                        let number = value.split_whitespace().next().and_then(|n| n.parse::<i64>().ok());
+                        row.insert(column.to_string(), json!(number))
+                    }),
+                };
+            }
+            Value::Object(row)
+        })
+        .collect()
+}
+
+/// Splits `PROC_STRINGS` output into each process's pid and strings.
+fn strings(output: &str) -> impl Iterator<Item = (i64, Vec<&str>)> {
+    output.split('\x1e').filter_map(|record| {
+        let (pid, strings) = record.split_once('\x1f')?;
+        let strings = strings.strip_suffix('\0').unwrap_or(strings);
+        let strings = if strings.is_empty() { Vec::new() } else { strings.split('\0').collect() };
+        Some((pid.parse().ok()?, strings))
+    })
+}
+
+/// Parses `/proc/<pid>/cmdline`. Kernel threads have no command line.
+pub fn parse_proc_cmdline(output: &str) -> Vec<Value> {
+    strings(output)
+        .map(|(pid, argv)| {
+            json!({
+                "pid": pid,
+                "command": Some(argv.join(" ")).filter(|c| !c.is_empty()),
+                "argv": argv,
+            })
+        })
+        .collect()
+}
+
+/// Parses `/proc/<pid>/environ`, one row per variable.
+pub fn parse_proc_environ(output: &str) -> Vec<Value> {
+    strings(output)
+        .flat_map(|(pid, variables)| {
+            variables.into_iter().filter_map(move |variable| {
+                let (name, value) = variable.split_once('=')?;
+                Some(json!({ "pid": pid, "name": name, "value": value }))
+            })
+        })
+        .collect()
+}
+
+/// Parses `/proc/<pid>/limits`, whose columns are fixed width. Unlimited is null.
+pub fn parse_proc_limits(output: &str) -> Vec<Value> {
+    let limit = |s: Option<&str>| s.and_then(|s| s.trim().parse::<i64>().ok());
+    output
+        .lines()
+        .filter_map(pid_and_line)
+        .filter(|(_, line)| !line.starts_with("Limit "))
+        .map(|(pid, line)| {
+            let column = |from: usize, to: usize| line.get(from..to.min(line.len()));
+            json!({
+                "pid": pid,
+                "limit": column(0, 26).map(str::trim),
+                "soft": limit(column(26, 47)),
+                "hard": limit(column(47, 68)),
+                "units": column(68, line.len()).map(str::trim).filter(|u| !u.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// Parses `PROC_FDS`: `123/fd/4\tsocket:[5678]`. Sockets, pipes and the like have an
+/// inode and a `type`, and everything else is a path.
+pub fn parse_proc_fds(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            // The target is empty when the link can't be read.
+            let (path, target) = line.split_once('\t').filter(|(_, target)| !target.is_empty())?;
+            let mut parts = path.split('/');
+            let (pid, _, fd) = (parts.next()?, parts.next()?, parts.next()?);
+            // `socket:[5678]`, or `anon_inode:[eventfd]`
+            let (kind, inode) = match target.split_once(":[") {
+                Some((kind, rest)) => (kind, rest.trim_end_matches(']').parse::<i64>().ok()),
+                None if target.starts_with('/') => ("file", None),
+                None => (target.split(':').next().unwrap_or(target), None),
+            };
+            Some(json!({
+                "pid": pid.parse::<i64>().ok(),
+                "fd": fd.parse::<i64>().ok(),
+                "type": kind,
+                "inode": inode,
+                "target": target,
+            }))
+        })
+        .collect()
+}
+
+/// Parses `/proc/<pid>/maps`, one row per mapping.
+pub fn parse_proc_maps(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(pid_and_line)
+        .filter_map(|(pid, line)| {
+            let mut fields = line.splitn(6, ' ');
+            let (range, perms, offset, device, inode) =
+                (fields.next()?, fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+            let (start, end) = range.split_once('-')?;
+            let size = u64::from_str_radix(end, 16).ok()?.checked_sub(u64::from_str_radix(start, 16).ok()?);
+            Some(json!({
+                "pid": pid,
+                "start": start,
+                "end": end,
+                "size_bytes": size,
+                "perms": perms,
+                "offset": i64::from_str_radix(offset, 16).ok(),
+                "device": device,
+                "inode": inode.parse::<i64>().ok(),
+                "path": fields.next().map(str::trim).filter(|p| !p.is_empty()),
+            }))
+        })
+        .collect()
+}
+
+/// Parses `/proc/<pid>/io`, one row per process. Other users' processes need root.
+pub fn parse_proc_io(output: &str) -> Vec<Value> {
+    by_pid(output)
+        .into_iter()
+        .map(|(pid, lines)| {
+            let mut row = Map::new();
+            row.insert("pid".into(), json!(pid));
+            for (key, value) in lines.iter().filter_map(|line| line.split_once(": ")) {
+                row.insert(key.to_string(), json!(value.trim().parse::<i64>().ok()));
+            }
+            Value::Object(row)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use crate::error::ZqueryError;
+
+    async fn query(sql: &str) -> Result<usize, ZqueryError> {
+        let ctx = ZqueryContext::new().build();
+        let batches = sql_to_df(&ctx, sql).await?.collect().await?;
+        Ok(batches.iter().map(|b| b.num_rows()).sum())
+    }
+
+    #[tokio::test]
+    async fn a_pid_that_is_not_a_number_is_an_error() {
+        for table in ["lsof", "proc_status", "proc_cmdline", "proc_environ", "proc_limits", "proc_fds", "proc_maps", "proc_io"] {
+            let error = query(&format!("select * from {}('1; true')", table)).await.unwrap_err();
+            assert_eq!(error.to_string(), format!("Command failed: {}: pid must be a number", table));
+            assert_eq!(error.exit_code(), 4);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_missing_process_is_an_error() {
+        // Above the largest pid Linux allows.
+        for table in ["proc_status", "proc_environ", "proc_fds"] {
+            let error = query(&format!("select * from {}(999999999)", table)).await.unwrap_err();
+            assert_eq!(error.to_string(), format!("Command failed: {}: no process with pid 999999999", table));
+        }
+    }
+
+    #[tokio::test]
+    async fn a_process_can_be_read() {
+        let pid = std::process::id();
+        assert_eq!(query(&format!("select * from proc_status({}) where pid = {}", pid, pid)).await.unwrap(), 1);
+        assert_eq!(query(&format!("select * from proc_fds({}) where fd = 0", pid)).await.unwrap(), 1);
+        assert!(query(&format!("select * from proc_maps({})", pid)).await.unwrap() > 0);
+        assert!(query("select * from proc_cmdline()").await.unwrap() > 0);
+    }
+}
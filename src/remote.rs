@@ -0,0 +1,282 @@
+use datafusion::catalog::Session;
+use datafusion::execution::context::SessionConfig;
+use ssh2_config::{HostClause, ParseRule, SshConfig};
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::ZqueryError;
+
+/// How commands reach a host other than `localhost`. zquery uses ssh unless a session
+/// is built with `ZqueryContext::with_transport`.
+pub trait Transport: Debug + Send + Sync {
+    /// Runs a shell command on a host and returns what it printed once it exits, or a
+    /// `ZqueryError::Command` with what it wrote to stderr if it exited non-zero.
+    fn exec(&self, host: &str, command: &str) -> Result<String, ZqueryError>;
+
+    /// Runs a shell command that may never exit, like `tail -F`, returning its output
+    /// as it's printed. The command is stopped when the reader is dropped. Reads may fail
+    /// with `TimedOut` while it's quiet, and can be retried.
+    fn follow(&self, host: &str, command: &str) -> Result<Box<dyn Read + Send>, ZqueryError>;
+}
+
+/// How long a read of a followed command waits before timing out, so whoever's reading
+/// it can check whether it's still wanted.
+const FOLLOW_POLL_MS: u32 = 1000;
+
+/// How long to wait before looking again when neither stdout nor stderr of a remote
+/// command has anything new.
+const EXEC_POLL: Duration = Duration::from_millis(5);
+
+/// How much of a remote command's stderr is kept for its error. The rest is still read
+/// so the command isn't held up, just dropped.
+const MAX_STDERR_BYTES: usize = 64 * 1024;
+
+/// The hosts `hosts('prod-*')` picks from, and that `\hosts`, completion and the TUI's
+/// host picker list.
+pub trait Inventory: Debug + Send + Sync {
+    fn hosts(&self) -> Result<Vec<String>, ZqueryError>;
+}
+
+/// A fixed list of hosts.
+impl Inventory for Vec<String> {
+    fn hosts(&self) -> Result<Vec<String>, ZqueryError> {
+        Ok(self.clone())
+    }
+}
+
+/// The transport and inventory a session uses, kept on its `SessionConfig` so command
+/// tables can find them when they run.
+#[derive(Debug, Clone)]
+pub struct Remote {
+    pub transport: Arc<dyn Transport>,
+    pub inventory: Arc<dyn Inventory>,
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        let ssh = Arc::new(Ssh::default());
+        Self {
+            transport: ssh.clone(),
+            inventory: ssh,
+        }
+    }
+}
+
+impl Remote {
+    /// The session's remote, or plain ssh if it was set up without one.
+    pub fn of(state: &dyn Session) -> Self {
+        Self::from_config(state.config())
+    }
+
+    pub fn from_config(config: &SessionConfig) -> Self {
+        config.get_extension::<Remote>().map(|r| r.as_ref().clone()).unwrap_or_default()
+    }
+
+    /// Hosts in the inventory matching a glob like `prod-*`.
+    pub fn hosts_matching(&self, pattern: &str) -> Result<Vec<String>, ZqueryError> {
+        let glob = HostClause::new(pattern.to_string(), false);
+        Ok(self.inventory.hosts()?.into_iter().filter(|host| glob.intersects(host)).collect())
+    }
+}
+
+/// Reaches hosts over ssh with the addresses, ports and users in an ssh config, and
+/// lists the hosts named in it.
+#[derive(Debug, Clone, Default)]
+pub struct Ssh {
+    /// Defaults to `~/.ssh/config`.
+    pub config: Option<PathBuf>,
+    /// The private key to log in with, `~/.ssh/id_rsa` by default.
+    pub identity: Option<PathBuf>,
+    /// Who to log in as when the config doesn't say, `root` by default.
+    pub user: Option<String>,
+}
+
+fn home() -> Result<PathBuf, ZqueryError> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| ZqueryError::Ssh("HOME is not set".to_string()))
+}
+
+impl Ssh {
+    fn config_path(&self) -> Result<PathBuf, ZqueryError> {
+        match &self.config {
+            Some(path) => Ok(path.clone()),
+            None => Ok(home()?.join(".ssh/config")),
+        }
+    }
+
+    fn ssh_config(&self) -> Result<SshConfig, ZqueryError> {
+        let path = self.config_path()?;
+        let file = File::open(&path)
+            .map_err(|e| ZqueryError::Ssh(format!("Could not open {}: {}", path.display(), e)))?;
+        SshConfig::default()
+            .parse(&mut BufReader::new(file), ParseRule::STRICT)
+            .map_err(|e| ZqueryError::Ssh(format!("Failed to parse {}: {}", path.display(), e)))
+    }
+
+    /// Opens an authenticated ssh session to a host from the config.
+    pub fn session(&self, host: &str) -> Result<ssh2::Session, ZqueryError> {
+        let config = self.ssh_config()?;
+
+        // Query parameters for your host
+        // If there's no rule for your host, default params are returned
+        let params = config.query(host);
+
+        let mut session = ssh2::Session::new()?;
+
+        let hostname = params.host_name.unwrap_or_else(|| host.to_string());
+        let port = params.port.unwrap_or(22);
+        let user = params
+            .user
+            .or_else(|| self.user.clone())
+            .unwrap_or("root".to_string());
+        let tcp = TcpStream::connect(format!("{}:{}", hostname, port))
+            .map_err(|e| ZqueryError::Ssh(format!("Could not connect to {}:{}: {}", hostname, port, e)))?;
+
+        session.set_tcp_stream(tcp);
+        session
+            .handshake()
+            .map_err(|e| ZqueryError::Ssh(format!("Handshake with {} failed: {}", host, e)))?;
+        let identity = match &self.identity {
+            Some(identity) => identity.clone(),
+            None => home()?.join(".ssh/id_rsa"),
+        };
+        session
+            .userauth_pubkey_file(&user, None, Path::new(&identity), None)
+            .map_err(|e| ZqueryError::Ssh(format!("Could not authenticate to {} as {}: {}", host, user, e)))?;
+        Ok(session)
+    }
+
+    fn channel(&self, session: &ssh2::Session, host: &str, command: &str) -> Result<ssh2::Channel, ZqueryError> {
+        let mut channel = session.channel_session()?;
+        channel
+            .exec(command)
+            .map_err(|e| ZqueryError::Command(format!("{} on {}: {}", command, host, e)))?;
+        Ok(channel)
+    }
+}
+
+impl Transport for Ssh {
+    fn exec(&self, host: &str, command: &str) -> Result<String, ZqueryError> {
+        let session = self.session(host)?;
+        let mut channel = self.channel(&session, host, command)?;
+        let (output, stderr) = read_output(&session, &mut channel)
+            .map_err(|e| ZqueryError::Ssh(format!("Failed to read from {}: {}", host, e)))?;
+        // Ensure the channel closes cleanly
+        channel.wait_close()?;
+        let status = channel.exit_status()?;
+        if status != 0 {
+            return Err(ZqueryError::Command(match String::from_utf8_lossy(&stderr).trim() {
+                "" => format!("Command on {} exited with status {}", host, status),
+                stderr => format!("{}: {}", host, stderr),
+            }));
+        }
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    fn follow(&self, host: &str, command: &str) -> Result<Box<dyn Read + Send>, ZqueryError> {
+        // Without a terminal, closing the channel only stops the command the next time
+        // it writes, which for a quiet log may be never. So it runs in the background
+        // next to a watcher that kills it once the channel's stdin closes.
+        let command = format!(
+            "exec 3<&0 </dev/null; {} 3<&- & pid=$!; {{ cat <&3 >/dev/null; kill $pid; }} >/dev/null 2>&1 & wait $pid",
+            command
+        );
+        let session = self.session(host)?;
+        let channel = self.channel(&session, host, &command)?;
+        session.set_timeout(FOLLOW_POLL_MS);
+        Ok(Box::new(channel))
+    }
+}
+
+/// Reads a channel's stdout and stderr as they come. stdout and stderr share the
+/// channel's window, so reading one to the end before the other would hang on a command
+/// that writes enough to the second.
+fn read_output(session: &ssh2::Session, channel: &mut ssh2::Channel) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    session.set_blocking(false);
+    let (mut output, mut stderr) = (Vec::new(), Vec::new());
+    let mut buf = [0; 32 * 1024];
+    let result = loop {
+        let read = match channel.read(&mut buf) {
+            Ok(n) => {
+                output.extend_from_slice(&buf[..n]);
+                n
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => 0,
+            Err(e) => break Err(e),
+        };
+        let read_stderr = match channel.stderr().read(&mut buf) {
+            Ok(n) => {
+                let kept = n.min(MAX_STDERR_BYTES.saturating_sub(stderr.len()));
+                stderr.extend_from_slice(&buf[..kept]);
+                n
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => 0,
+            Err(e) => break Err(e),
+        };
+        if read == 0 && read_stderr == 0 {
+            if channel.eof() {
+                break Ok(());
+            }
+            std::thread::sleep(EXEC_POLL);
+        }
+    };
+    session.set_blocking(true);
+    result.map(|_| (output, stderr))
+}
+
+/// Every host named outright (no wildcards) in the config, or none without a config.
+impl Inventory for Ssh {
+    fn hosts(&self) -> Result<Vec<String>, ZqueryError> {
+        let mut hosts = Vec::new();
+        if !self.config_path()?.exists() {
+            return Ok(hosts);
+        }
+        for host in self.ssh_config()?.get_hosts() {
+            for clause in &host.pattern {
+                let literal = !clause.pattern.contains(['*', '?']);
+                if literal && !clause.negated && !hosts.contains(&clause.pattern) {
+                    hosts.push(clause.pattern.clone());
+                }
+            }
+        }
+        Ok(hosts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_inventory_is_the_hosts_named_in_the_config() {
+        let path = std::env::temp_dir().join(format!("zquery-ssh-config-{}", std::process::id()));
+        std::fs::write(&path, "Host web1 web2 !web3\n  User deploy\n\nHost *.internal db?\n  Port 2222\n\nHost web1 db1\n").unwrap();
+        let ssh = Ssh {
+            config: Some(path.clone()),
+            ..Default::default()
+        };
+        let hosts = ssh.hosts();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(hosts.unwrap(), ["web1", "web2", "db1"]);
+    }
+
+    #[test]
+    fn without_a_config_the_inventory_is_empty() {
+        let ssh = Ssh {
+            config: Some(PathBuf::from("/nonexistent/.ssh/config")),
+            ..Default::default()
+        };
+        assert!(ssh.hosts().unwrap().is_empty());
+        let remote = Remote {
+            transport: Arc::new(ssh.clone()),
+            inventory: Arc::new(ssh),
+        };
+        assert!(remote.hosts_matching("prod-*").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+/// One step of a script: a `\` command on its own line, or a SQL statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptItem {
+    Meta(String),
+    Sql(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Normal,
+    SingleQuote,
+    DoubleQuote,
+    LineComment,
+    BlockComment,
+}
+
+/// Splits a script into statements on `;`, dropping `--` and `/* */` comments. Lines
+/// starting with `\` between statements are meta commands like `\set name value`.
+pub fn split_script(script: &str) -> Vec<ScriptItem> {
+    let (mut items, rest, _) = scan(script);
+    push_sql(&mut items, &rest);
+    items
+}
+
+/// Whether the input ends with a finished statement or meta command, rather than
+/// partway through one or inside a string or comment. The REPL keeps reading lines
+/// until it does.
+pub fn is_complete(script: &str) -> bool {
+    let (_, rest, state) = scan(script);
+    rest.trim().is_empty() && matches!(state, State::Normal | State::LineComment)
+}
+
+/// Splits off every finished item, returning them along with the unfinished text
+/// after the last one and the state the scanner ended in.
+fn scan(script: &str) -> (Vec<ScriptItem>, String, State) {
+    let mut items = Vec::new();
+    let mut statement = String::new();
+    let mut state = State::Normal;
+    let mut chars = script.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        match state {
+            State::Normal => match c {
+                '\\' if line_start && statement.trim().is_empty() => {
+                    let mut line = String::from(c);
+                    while let Some(&next) = chars.peek() {
+                        if next == '\n' {
+                            break;
+                        }
+                        line.push(next);
+                        chars.next();
+                    }
+                    items.push(ScriptItem::Meta(line.trim().to_string()));
+                    statement.clear();
+                }
+                ';' => {
+                    push_sql(&mut items, &statement);
+                    statement.clear();
+                }
+                '-' if chars.peek() == Some(&'-') => {
+                    chars.next();
+                    state = State::LineComment;
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    state = State::BlockComment;
+                }
+                '\'' => {
+                    statement.push(c);
+                    state = State::SingleQuote;
+                }
+                '"' => {
+                    statement.push(c);
+                    state = State::DoubleQuote;
+                }
+                _ => statement.push(c),
+            },
+            // A doubled quote inside a string is an escaped quote, which this handles
+            // for free by leaving and re-entering the string.
+            State::SingleQuote => {
+                statement.push(c);
+                if c == '\'' {
+                    state = State::Normal;
+                }
+            }
+            State::DoubleQuote => {
+                statement.push(c);
+                if c == '"' {
+                    state = State::Normal;
+                }
+            }
+            State::LineComment => {
+                if c == '\n' {
+                    statement.push(c);
+                    state = State::Normal;
+                }
+            }
+            State::BlockComment => {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    statement.push(' ');
+                    state = State::Normal;
+                }
+            }
+        }
+        if c == '\n' {
+            line_start = true;
+        } else if !c.is_whitespace() {
+            line_start = false;
+        }
+    }
+    (items, statement, state)
+}
+
+fn push_sql(items: &mut Vec<ScriptItem>, statement: &str) {
+    let statement = statement.trim();
+    if !statement.is_empty() {
+        items.push(ScriptItem::Sql(statement.to_string()));
+    }
+}
+
+/// Replaces `:name` with the variable's value, `:'name'` with it as a string literal
+/// and `:"name"` with it as a quoted identifier, like psql. Casts like `x::int`, text
+/// inside quotes and unknown names are left alone.
+pub fn substitute(sql: &str, vars: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            out.push(c);
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => {
+                quote = Some(c);
+                out.push(c);
+            }
+            ':' if chars.peek() == Some(&':') => {
+                chars.next();
+                out.push_str("::");
+            }
+            ':' => {
+                let wrap = match chars.peek() {
+                    Some(&q) if q == '\'' || q == '"' => Some(q),
+                    _ => None,
+                };
+                let mut rest = chars.clone();
+                if wrap.is_some() {
+                    rest.next();
+                }
+                let mut name = String::new();
+                while let Some(&n) = rest.peek() {
+                    if !(n.is_alphanumeric() || n == '_') {
+                        break;
+                    }
+                    name.push(n);
+                    rest.next();
+                }
+                let closed = match wrap {
+                    Some(q) => rest.peek() == Some(&q),
+                    None => true,
+                };
+                match vars.get(&name) {
+                    Some(value) if !name.is_empty() && closed => {
+                        match wrap {
+                            Some('\'') => out.push_str(&format!("'{}'", value.replace('\'', "''"))),
+                            Some(_) => out.push_str(&format!("\"{}\"", value.replace('"', "\"\""))),
+                            None => out.push_str(value),
+                        }
+                        if wrap.is_some() {
+                            rest.next();
+                        }
+                        chars = rest;
+                    }
+                    _ => out.push(c),
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Parses `name=value` from `-v`.
+pub fn parse_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("Expected name=value, got '{}'", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql(s: &str) -> ScriptItem {
+        ScriptItem::Sql(s.to_string())
+    }
+
+    fn meta(s: &str) -> ScriptItem {
+        ScriptItem::Meta(s.to_string())
+    }
+
+    #[test]
+    fn scripts_split_on_semicolons_outside_quotes_and_comments() {
+        let cases = [
+            ("select 1; select 2", vec![sql("select 1"), sql("select 2")]),
+            ("select ';' as a; select 2;", vec![sql("select ';' as a"), sql("select 2")]),
+            ("select 'it''s; fine';", vec![sql("select 'it''s; fine'")]),
+            ("select \"a;b\" from t;", vec![sql("select \"a;b\" from t")]),
+            ("select 1 -- not; here\n;", vec![sql("select 1")]),
+            ("select /* ; */ 1;", vec![sql("select   1")]),
+            ("\\set x 1\nselect :x;", vec![meta("\\set x 1"), sql("select :x")]),
+            ("select 1;\n  \\d ps\n", vec![sql("select 1"), meta("\\d ps")]),
+            ("select 1\n\\d ps", vec![sql("select 1\n\\d ps")]),
+            (" ; ;\n-- only a comment\n", vec![]),
+        ];
+        for (script, expected) in cases {
+            assert_eq!(split_script(script), expected, "{:?}", script);
+        }
+    }
+
+    #[test]
+    fn input_is_complete_only_after_a_finished_statement() {
+        let cases = [
+            ("", true),
+            ("select 1;", true),
+            ("select 1; -- done", true),
+            ("\\d ps", true),
+            ("select 1", false),
+            ("select ';", false),
+            ("select \"a;", false),
+            ("select 1; /* open", false),
+            ("select 1 -- ;", false),
+        ];
+        for (script, expected) in cases {
+            assert_eq!(is_complete(script), expected, "{:?}", script);
+        }
+    }
+
+    #[test]
+    fn variables_are_substituted_outside_quotes() {
+        let vars: HashMap<String, String> = [("x", "5"), ("name", "o'brien"), ("col", "a\"b")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let cases = [
+            ("select :x", "select 5"),
+            ("select :x+1, :x", "select 5+1, 5"),
+            ("select :'name'", "select 'o''brien'"),
+            ("select :\"col\" from t", "select \"a\"\"b\" from t"),
+            ("select x::int", "select x::int"),
+            ("select ':x', \":x\"", "select ':x', \":x\""),
+            ("select :missing", "select :missing"),
+            ("select :'missing'", "select :'missing'"),
+            ("select :'x", "select :'x"),
+            ("select :", "select :"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(substitute(input, &vars), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn vars_parse_as_name_equals_value() {
+        let ok = |name: &str, value: &str| Ok((name.to_string(), value.to_string()));
+        assert_eq!(parse_var("a=b"), ok("a", "b"));
+        assert_eq!(parse_var("a=b=c"), ok("a", "b=c"));
+        assert_eq!(parse_var("a="), ok("a", ""));
+        assert_eq!(parse_var("=b"), Err("Expected name=value, got '=b'".to_string()));
+        assert_eq!(parse_var("ab"), Err("Expected name=value, got 'ab'".to_string()));
+    }
+}
@@ -0,0 +1,377 @@
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, ArrayRef, Int64Array, TimestampNanosecondArray};
+use datafusion::arrow::compute::{cast, concat_batches, SortOptions};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::Session;
+use datafusion::common::{exec_err, plan_err, Result};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::TableProvider;
+use datafusion::error::DataFusionError;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::{LexOrdering, PhysicalSortExpr};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::scalar::ScalarValue;
+use datafusion_expr::sort_properties::{ExprProperties, SortProperties};
+use datafusion_expr::{ColumnarValue, Expr, ScalarUDFImpl, Signature, TableType, Volatility};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::commandtable::{CommandTable, CommandTableFunc};
+
+/// How often `stream()` samples when no interval is given.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Parses durations like `500ms`, `5s`, `1m`, `2h` or `1 day`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value.parse().ok()?;
+    let seconds = match unit.trim() {
+        "ms" | "millisecond" | "milliseconds" => value / 1000.0,
+        "s" | "sec" | "secs" | "second" | "seconds" => value,
+        "m" | "min" | "mins" | "minute" | "minutes" => value * 60.0,
+        "h" | "hour" | "hours" => value * 3600.0,
+        "d" | "day" | "days" => value * 86400.0,
+        _ => return None,
+    };
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// Turns an `interval '5s'` or `'5s'` literal into a Duration.
+pub fn duration_from_scalar(value: &ScalarValue) -> Result<Duration> {
+    let nanos = match value {
+        ScalarValue::IntervalMonthDayNano(Some(v)) => {
+            if v.months != 0 {
+                return plan_err!("Intervals measured in months are not supported");
+            }
+            v.days as i64 * 86_400_000_000_000 + v.nanoseconds
+        }
+        ScalarValue::IntervalDayTime(Some(v)) => {
+            v.days as i64 * 86_400_000_000_000 + v.milliseconds as i64 * 1_000_000
+        }
+        ScalarValue::Utf8(Some(s)) | ScalarValue::LargeUtf8(Some(s)) => {
+            match parse_duration(s) {
+                Some(d) => return Ok(d),
+                None => return plan_err!("Could not parse '{}' as a duration", s),
+            }
+        }
+        _ => return plan_err!("Expected an interval like interval '5s', got {}", value),
+    };
+    if nanos <= 0 {
+        return plan_err!("Interval must be positive, got {}", value);
+    }
+    Ok(Duration::from_nanos(nanos as u64))
+}
+
+/// Ordering for an unbounded table whose first column is the time each row arrived.
+/// Knowing rows come in time order lets windowed aggregates emit each window as
+/// soon as it closes instead of waiting forever.
+pub fn time_ordering(name: &str, projection: Option<&Vec<usize>>) -> Option<LexOrdering> {
+    let index = match projection {
+        Some(p) => p.iter().position(|i| *i == 0)?,
+        None => 0,
+    };
+    Some(vec![PhysicalSortExpr {
+        expr: Arc::new(Column::new(name, index)),
+        options: SortOptions::default(),
+    }])
+}
+
+/// Table function for `stream(ps(), interval '5s')`: re-runs a command table on an
+/// interval and emits each sample as a new batch tagged with `sample_ts`.
+#[derive(Debug)]
+pub struct StreamTableFunc {
+    tables: HashMap<String, Arc<CommandTableFunc>>,
+}
+
+impl StreamTableFunc {
+    pub fn new(tables: &[(String, Arc<CommandTableFunc>)]) -> Self {
+        Self {
+            tables: tables
+                .iter()
+                .map(|(name, func)| (name.clone(), func.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl TableFunctionImpl for StreamTableFunc {
+    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let (func, rest) = match exprs {
+            [Expr::ScalarFunction(func), rest @ ..] => (func, rest),
+            _ => return plan_err!("stream expects a command table, e.g. stream(ps(), interval '5s')"),
+        };
+        let table_func = match self.tables.get(func.name()) {
+            Some(table_func) => table_func,
+            None => return plan_err!("{} is not a command table", func.name()),
+        };
+        let interval = match rest.first() {
+            Some(Expr::Literal(lit)) => duration_from_scalar(lit)?,
+            Some(other) => return plan_err!("stream interval must be a literal, got {}", other),
+            None => DEFAULT_INTERVAL,
+        };
+        Ok(Arc::new(StreamTable::new(table_func.table(&func.args)?, interval)))
+    }
+}
+
+/// An unbounded table that samples a CommandTable forever.
+#[derive(Debug)]
+pub struct StreamTable {
+    sampler: Arc<SampledCommand>,
+}
+
+impl StreamTable {
+    pub fn new(table: CommandTable, interval: Duration) -> Self {
+        let mut fields = vec![Field::new(
+            "sample_ts",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )];
+        fields.extend(table.schema().fields().iter().map(|f| f.as_ref().clone()));
+        Self {
+            sampler: Arc::new(SampledCommand {
+                table,
+                interval,
+                schema: Arc::new(Schema::new(fields)),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for StreamTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.sampler.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.sampler.schema.clone(),
+            vec![self.sampler.clone()],
+            projection,
+            time_ordering("sample_ts", projection),
+            true,
+            limit,
+        )?))
+    }
+}
+
+#[derive(Debug)]
+struct SampledCommand {
+    table: CommandTable,
+    interval: Duration,
+    schema: SchemaRef,
+}
+
+impl SampledCommand {
+    fn sample(table: &CommandTable, schema: &SchemaRef, ctx: &TaskContext) -> Result<RecordBatch> {
+        let sample_ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| DataFusionError::Execution(e.to_string()))?
+            .as_nanos() as i64;
+        let batches = table.run_in(ctx.session_config())?;
+        let batch = concat_batches(&table.schema(), &batches)?;
+
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(TimestampNanosecondArray::from(
+            vec![sample_ts; batch.num_rows()],
+        ))];
+        columns.extend(batch.columns().iter().cloned());
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+}
+
+impl PartitionStream for SampledCommand {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let table = self.table.clone();
+        let schema = self.schema.clone();
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        let samples = futures::stream::unfold(ticker, move |mut ticker| {
+            let table = table.clone();
+            let schema = schema.clone();
+            let ctx = ctx.clone();
+            async move {
+                ticker.tick().await;
+                let batch = tokio::task::spawn_blocking(move || Self::sample(&table, &schema, &ctx))
+                    .await
+                    .map_err(|e| DataFusionError::Execution(e.to_string()))
+                    .and_then(|batch| batch);
+                Some((batch, ticker))
+            }
+        });
+        Box::pin(RecordBatchStreamAdapter::new(self.schema.clone(), samples))
+    }
+}
+
+/// `tumble(sample_ts, '1m')` floors a timestamp to the start of its window. It keeps
+/// the ordering of its input so grouping by it over a stream stays incremental.
+#[derive(Debug)]
+pub struct Tumble {
+    signature: Signature,
+}
+
+impl Tumble {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::any(2, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for Tumble {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "tumble"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, args: &[DataType]) -> Result<DataType> {
+        match &args[0] {
+            DataType::Timestamp(unit, tz) => Ok(DataType::Timestamp(*unit, tz.clone())),
+            other => plan_err!("tumble expects a timestamp, got {}", other),
+        }
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let width = match &args[1] {
+            ColumnarValue::Scalar(value) => duration_from_scalar(value)?,
+            ColumnarValue::Array(_) => return exec_err!("tumble width must be a literal"),
+        };
+        let (ts, is_scalar) = match &args[0] {
+            ColumnarValue::Array(array) => (array.clone(), false),
+            ColumnarValue::Scalar(value) => (value.to_array()?, true),
+        };
+        let unit = match ts.data_type() {
+            DataType::Timestamp(unit, _) => *unit,
+            other => return exec_err!("tumble expects a timestamp, got {}", other),
+        };
+        let width = match unit {
+            TimeUnit::Second => width.as_secs() as i64,
+            TimeUnit::Millisecond => width.as_millis() as i64,
+            TimeUnit::Microsecond => width.as_micros() as i64,
+            TimeUnit::Nanosecond => width.as_nanos() as i64,
+        }
+        .max(1);
+
+        let raw = cast(&ts, &DataType::Int64)?;
+        let raw = raw.as_any().downcast_ref::<Int64Array>().unwrap();
+        let floored: Int64Array = raw
+            .iter()
+            .map(|v| v.map(|v| v.div_euclid(width) * width))
+            .collect();
+        let result = cast(&floored, ts.data_type())?;
+
+        if is_scalar {
+            Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(&result, 0)?))
+        } else {
+            Ok(ColumnarValue::Array(result))
+        }
+    }
+    fn output_ordering(&self, input: &[ExprProperties]) -> Result<SortProperties> {
+        if input[1].sort_properties == SortProperties::Singleton {
+            Ok(input[0].sort_properties)
+        } else {
+            Ok(SortProperties::Unordered)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commandtable::Parser;
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::{Int64Type, TimestampNanosecondType};
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[test]
+    fn durations_parse_with_their_units() {
+        for (text, expected) in [
+            ("500ms", Some(Duration::from_millis(500))),
+            ("5s", Some(Duration::from_secs(5))),
+            ("1.5 seconds", Some(Duration::from_millis(1500))),
+            ("1m", Some(Duration::from_secs(60))),
+            ("2h", Some(Duration::from_secs(7200))),
+            (" 1 day ", Some(Duration::from_secs(86400))),
+            ("5", None),
+            ("5 weeks", None),
+            ("s", None),
+            ("", None),
+        ] {
+            assert_eq!(parse_duration(text), expected, "{:?}", text);
+        }
+    }
+
+    /// A `ps` whose output is always the same two processes.
+    fn ps() -> CommandTableFunc {
+        CommandTableFunc {
+            command: vec!["sh", "-c", "echo 1; echo 2", "ps"],
+            parser: Parser::Native(|output| output.lines().map(|pid| json!({ "pid": pid.parse::<i64>().unwrap() })).collect()),
+            args: "",
+            description: "",
+            platforms: &["linux"],
+            schema: Arc::new(Schema::new(vec![Field::new("pid", DataType::Int64, true)])),
+            is_result_array: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_samples_the_table_on_every_interval() {
+        let ctx = ZqueryContext::new().with_command_table("ps", ps()).build();
+        let sql = "select sample_ts, pid from stream(ps(), interval '10 milliseconds')";
+        let mut stream = sql_to_df(&ctx, sql).await.unwrap().execute_stream().await.unwrap();
+        let mut samples = Vec::new();
+        for _ in 0..3 {
+            let batch = tokio::time::timeout(Duration::from_secs(10), stream.next())
+                .await
+                .expect("stream stopped sampling")
+                .unwrap()
+                .unwrap();
+            let pids: Vec<i64> = batch.column(1).as_primitive::<Int64Type>().values().to_vec();
+            assert_eq!(pids, [1, 2]);
+            let sample_ts = batch.column(0).as_primitive::<TimestampNanosecondType>();
+            assert_eq!(sample_ts.value(0), sample_ts.value(1));
+            samples.push(sample_ts.value(0));
+        }
+        assert!(samples.windows(2).all(|w| w[0] < w[1]), "{:?}", samples);
+    }
+
+    #[test]
+    fn intervals_must_be_positive() {
+        let error = duration_from_scalar(&ScalarValue::new_interval_mdn(0, 0, 0)).unwrap_err();
+        assert!(error.to_string().contains("Interval must be positive"), "{}", error);
+        assert!(duration_from_scalar(&ScalarValue::new_interval_mdn(1, 0, 0)).is_err());
+        assert_eq!(duration_from_scalar(&ScalarValue::from("2m")).unwrap(), Duration::from_secs(120));
+    }
+}
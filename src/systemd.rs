@@ -0,0 +1,452 @@
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::{Schema, SchemaRef};
+use datafusion::arrow::json::ReaderBuilder;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, Result};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::TableProvider;
+use datafusion::error::DataFusionError;
+use datafusion::logical_expr::{BinaryExpr, Operator, TableProviderFilterPushDown};
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::scalar::ScalarValue;
+use datafusion_expr::expr::InList;
+use datafusion_expr::{Expr, TableType};
+use serde_json::{json, Value};
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::commandtable::{host_and_args, split_named_args};
+use crate::remote::Remote;
+use crate::tail::{journal_fields, parse_journal, read_all};
+
+/// Journal entries per batch, the JSON decoder's own batch size.
+const BATCH_ROWS: usize = 1024;
+
+/// Parses `systemctl show`, one row per property. Several units come out as blocks
+/// separated by blank lines, each with its own `Id`.
+pub fn parse_systemctl_show(output: &str) -> Vec<Value> {
+    let mut rows = Vec::new();
+    for block in output.split("\n\n") {
+        let properties: Vec<(&str, &str)> =
+            block.lines().filter_map(|line| line.split_once('=')).collect();
+        let unit = properties.iter().find(|(key, _)| *key == "Id").map(|(_, id)| *id);
+        for (property, value) in properties {
+            rows.push(json!({ "unit": unit, "property": property, "value": value }));
+        }
+    }
+    rows
+}
+
+/// Table function for `journal(host('web1'), since, until, unit)`, reading the systemd
+/// journal up to now. Each argument can also be named, e.g. `journal(unit => 'nginx')`.
+#[derive(Debug)]
+pub struct JournalTableFunc;
+
+impl TableFunctionImpl for JournalTableFunc {
+    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        if matches!(exprs.first(), Some(Expr::ScalarFunction(func)) if func.name() == "hosts") {
+            return plan_err!("journal reads one host at a time, use host('name')");
+        }
+        let (host, args) = host_and_args(exprs);
+        let (_, named) = split_named_args(exprs);
+        let arg = |name: &str, position: usize| match named.get(name) {
+            Some(ScalarValue::Utf8(Some(value))) => Some(value.clone()),
+            _ => args.get(position).filter(|value| !value.is_empty()).cloned(),
+        };
+        Ok(Arc::new(JournalTable {
+            host,
+            since: arg("since", 0),
+            until: arg("until", 1),
+            unit: arg("unit", 2),
+            schema: Arc::new(Schema::new(journal_fields())),
+        }))
+    }
+}
+
+/// The journal between `since` and `until`, which take anything `journalctl --since`
+/// does, like `-1h` or `yesterday`.
+#[derive(Debug)]
+pub struct JournalTable {
+    host: String,
+    since: Option<String>,
+    until: Option<String>,
+    unit: Option<String>,
+    schema: SchemaRef,
+}
+
+/// What `where` clauses on `unit`, `priority` and `timestamp` narrow the journal to,
+/// so journalctl does the filtering instead of sending everything over.
+#[derive(Debug, Default)]
+struct JournalFilters {
+    units: Vec<String>,
+    min_priority: Option<i64>,
+    max_priority: Option<i64>,
+    /// Microseconds since the epoch, like the `timestamp` column.
+    since: Option<i64>,
+    until: Option<i64>,
+}
+
+impl JournalFilters {
+    /// Takes in what it can of `filter`, returning whether it could.
+    fn add(&mut self, filter: &Expr) -> bool {
+        match filter {
+            // DataFusion turns short `unit in (...)` lists into `or`s.
+            Expr::BinaryExpr(BinaryExpr { op: Operator::Or, .. }) | Expr::InList(_) => {
+                match units(filter) {
+                    Some(units) => {
+                        self.units.extend(units);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (column, op, value) = match (column_name(left), literal(right)) {
+                    (Some(column), Some(value)) => (column, *op, value),
+                    _ => match (column_name(right), literal(left), op.swap()) {
+                        (Some(column), Some(value), Some(op)) => (column, op, value),
+                        _ => return false,
+                    },
+                };
+                self.add_comparison(column, op, &value)
+            }
+            _ => false,
+        }
+    }
+
+    fn add_comparison(&mut self, column: &str, op: Operator, value: &ScalarValue) -> bool {
+        match (column, op, value) {
+            ("unit", Operator::Eq, ScalarValue::Utf8(Some(unit))) => {
+                self.units.push(unit.clone());
+                true
+            }
+            ("priority", op, ScalarValue::Int64(Some(priority))) => {
+                let (min, max) = match op {
+                    Operator::Eq => (Some(*priority), Some(*priority)),
+                    Operator::Lt => (None, Some(priority - 1)),
+                    Operator::LtEq => (None, Some(*priority)),
+                    Operator::Gt => (Some(priority + 1), None),
+                    Operator::GtEq => (Some(*priority), None),
+                    _ => return false,
+                };
+                if let Some(min) = min {
+                    self.min_priority = Some(self.min_priority.map_or(min, |m| m.max(min)));
+                }
+                if let Some(max) = max {
+                    self.max_priority = Some(self.max_priority.map_or(max, |m| m.min(max)));
+                }
+                true
+            }
+            ("timestamp", op, value) => {
+                let Some(micros) = epoch_micros(value) else {
+                    return false;
+                };
+                match op {
+                    Operator::Gt | Operator::GtEq => {
+                        self.since = Some(self.since.map_or(micros, |s| s.max(micros)));
+                    }
+                    Operator::Lt | Operator::LtEq => {
+                        self.until = Some(self.until.map_or(micros, |u| u.min(micros)));
+                    }
+                    Operator::Eq => {
+                        self.since = Some(micros);
+                        self.until = Some(micros);
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The journalctl flags for these filters. They're all inexact, so DataFusion still
+    /// checks every row and seconds are rounded outwards.
+    fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        for unit in &self.units {
+            flags.extend(["-u".to_string(), unit.clone()]);
+        }
+        if self.min_priority.is_some() || self.max_priority.is_some() {
+            let min = self.min_priority.unwrap_or(0).clamp(0, 7);
+            let max = self.max_priority.unwrap_or(7).clamp(0, 7);
+            // An empty range can't be asked for, so leave it to DataFusion to return nothing.
+            if min <= max {
+                flags.extend(["-p".to_string(), format!("{}..{}", min, max)]);
+            }
+        }
+        if let Some(since) = self.since {
+            flags.extend(["--since".to_string(), format!("@{}", since.div_euclid(1_000_000))]);
+        }
+        if let Some(until) = self.until {
+            let seconds = until.div_euclid(1_000_000) + i64::from(until.rem_euclid(1_000_000) > 0);
+            flags.extend(["--until".to_string(), format!("@{}", seconds)]);
+        }
+        flags
+    }
+}
+
+/// The units `unit = 'a' or unit = 'b'` or `unit in ('a', 'b')` allow.
+fn units(filter: &Expr) -> Option<Vec<String>> {
+    match filter {
+        Expr::BinaryExpr(BinaryExpr { left, op: Operator::Or, right }) => {
+            let mut allowed = units(left)?;
+            allowed.extend(units(right)?);
+            Some(allowed)
+        }
+        Expr::BinaryExpr(BinaryExpr { left, op: Operator::Eq, right }) => {
+            let value = match (column_name(left), column_name(right)) {
+                (Some("unit"), _) => literal(right),
+                (_, Some("unit")) => literal(left),
+                _ => None,
+            };
+            match value {
+                Some(ScalarValue::Utf8(Some(unit))) => Some(vec![unit]),
+                _ => None,
+            }
+        }
+        Expr::InList(InList { expr, list, negated: false }) if column_name(expr) == Some("unit") => list
+            .iter()
+            .map(|item| match literal(item) {
+                Some(ScalarValue::Utf8(Some(unit))) => Some(unit),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+fn column_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Column(column) => Some(&column.name),
+        Expr::Cast(cast) => column_name(&cast.expr),
+        Expr::TryCast(cast) => column_name(&cast.expr),
+        _ => None,
+    }
+}
+
+fn literal(expr: &Expr) -> Option<ScalarValue> {
+    match expr {
+        Expr::Literal(value) => Some(value.clone()),
+        Expr::Cast(cast) => literal(&cast.expr)?.cast_to(&cast.data_type).ok(),
+        _ => None,
+    }
+}
+
+fn epoch_micros(value: &ScalarValue) -> Option<i64> {
+    match value {
+        ScalarValue::TimestampSecond(Some(s), _) => s.checked_mul(1_000_000),
+        ScalarValue::TimestampMillisecond(Some(ms), _) => ms.checked_mul(1_000),
+        ScalarValue::TimestampMicrosecond(Some(us), _) => Some(*us),
+        ScalarValue::TimestampNanosecond(Some(ns), _) => Some(ns.div_euclid(1_000)),
+        _ => None,
+    }
+}
+
+impl JournalTable {
+    fn command(&self, filters: &[Expr]) -> Vec<String> {
+        let mut command = ["journalctl", "-o", "json", "-q", "--no-pager"].map(String::from).to_vec();
+        for (flag, value) in [("--since", &self.since), ("--until", &self.until), ("-u", &self.unit)] {
+            if let Some(value) = value {
+                command.extend([flag.to_string(), value.clone()]);
+            }
+        }
+        let mut pushed = JournalFilters::default();
+        for filter in filters {
+            pushed.add(filter);
+        }
+        // journalctl keeps the last --since or --until and any of several units, so pushed
+        // filters would widen what the arguments asked for.
+        if self.since.is_some() {
+            pushed.since = None;
+        }
+        if self.until.is_some() {
+            pushed.until = None;
+        }
+        if self.unit.is_some() {
+            pushed.units.clear();
+        }
+        command.extend(pushed.flags());
+        command
+    }
+}
+
+#[async_trait]
+impl TableProvider for JournalTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(&self, filters: &[&Expr]) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|filter| match JournalFilters::default().add(filter) {
+                true => TableProviderFilterPushDown::Inexact,
+                false => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let command = self.command(filters);
+        let remote = Remote::from_config(state.config());
+        let output = read_all(&remote, &self.host, &command).map_err(|e| {
+            DataFusionError::Execution(format!("Failed to read the journal on {}: {}", self.host, e))
+        })?;
+        let rows: Vec<Value> = output.lines().map(parse_journal).filter(|row| !row.is_null()).collect();
+
+        let mut decoder = ReaderBuilder::new(self.schema.clone()).build_decoder()?;
+        let mut batches = Vec::new();
+        for chunk in rows.chunks(BATCH_ROWS) {
+            decoder.serialize(chunk)?;
+            batches.extend(decoder.flush()?);
+        }
+        Ok(Arc::new(MemoryExec::try_new(
+            &[batches],
+            self.schema.clone(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+    use datafusion::datasource::source_as_provider;
+    use datafusion::logical_expr::LogicalPlan;
+    use datafusion_expr::{col, lit};
+
+    /// 2024-01-02 03:04:05.5 UTC.
+    const TS: i64 = 1_704_164_645_500_000;
+
+    fn ts() -> Expr {
+        lit(ScalarValue::TimestampMicrosecond(Some(TS), None))
+    }
+
+    fn flags(filters: &[Expr]) -> Vec<String> {
+        let mut pushed = JournalFilters::default();
+        for filter in filters {
+            assert!(pushed.add(filter), "{}", filter);
+        }
+        pushed.flags()
+    }
+
+    #[test]
+    fn filters_narrow_journalctl() {
+        let cases = [
+            (vec![col("unit").in_list(vec![lit("a"), lit("b")], false)], "-u a -u b"),
+            (vec![col("unit").eq(lit("a")).or(lit("b").eq(col("unit")))], "-u a -u b"),
+            (vec![col("priority").lt_eq(lit(3i64))], "-p 0..3"),
+            (vec![lit(3i64).gt_eq(col("priority"))], "-p 0..3"),
+            (vec![col("priority").gt(lit(2i64)), col("priority").lt(lit(6i64))], "-p 3..5"),
+            (vec![col("priority").eq(lit(4i64))], "-p 4..4"),
+            (vec![col("priority").gt(lit(5i64)), col("priority").lt(lit(3i64))], ""),
+            (vec![col("timestamp").gt(ts())], "--since @1704164645"),
+            (vec![col("timestamp").lt(ts())], "--until @1704164646"),
+            (vec![col("timestamp").eq(ts())], "--since @1704164645 --until @1704164646"),
+            (
+                vec![
+                    col("unit").in_list(vec![lit("a"), lit("b")], false),
+                    col("priority").lt_eq(lit(3i64)),
+                    col("timestamp").gt(ts()),
+                ],
+                "-u a -u b -p 0..3 --since @1704164645",
+            ),
+        ];
+        for (filters, expected) in cases {
+            assert_eq!(flags(&filters).join(" "), expected, "{:?}", filters);
+        }
+    }
+
+    #[test]
+    fn other_filters_are_left_to_datafusion() {
+        let filters = [
+            col("unit").in_list(vec![lit("a")], true),
+            col("unit").eq(lit("a")).or(col("priority").eq(lit(3i64))),
+            col("unit").like(lit("a%")),
+            col("priority").not_eq(lit(3i64)),
+            col("message").eq(lit("hi")),
+            col("timestamp").gt(lit("yesterday")),
+        ];
+        for filter in filters {
+            assert!(!JournalFilters::default().add(&filter), "{}", filter);
+        }
+    }
+
+    /// The journalctl command a query would run, with the filters DataFusion pushes down.
+    async fn command_for(sql: &str) -> String {
+        let ctx = ZqueryContext::new().without_builtin_tables().build();
+        let plan = sql_to_df(&ctx, sql).await.unwrap().into_optimized_plan().unwrap();
+        let mut command = None;
+        plan.apply(|node| {
+            if let LogicalPlan::TableScan(scan) = node {
+                let provider = source_as_provider(&scan.source)?;
+                let table = provider.as_any().downcast_ref::<JournalTable>().unwrap();
+                command = Some(table.command(&scan.filters).join(" "));
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })
+        .unwrap();
+        command.unwrap()
+    }
+
+    #[tokio::test]
+    async fn where_clauses_are_pushed_into_the_command() {
+        assert_eq!(
+            command_for(
+                "select message from journal() where unit in ('a', 'b') and priority <= 3 \
+                 and timestamp > timestamp '2024-01-02T03:04:05.5'"
+            )
+            .await,
+            "journalctl -o json -q --no-pager -u a -u b -p 0..3 --since @1704164645"
+        );
+        // Pushed filters would widen the arguments, since journalctl keeps the last one.
+        assert_eq!(
+            command_for(
+                "select message from journal(since => '-1h', unit => 'nginx') \
+                 where unit = 'a' and timestamp > timestamp '2024-01-02T03:04:05.5'"
+            )
+            .await,
+            "journalctl -o json -q --no-pager --since -1h -u nginx"
+        );
+    }
+
+    #[test]
+    fn systemctl_show_has_a_row_per_property_of_each_unit() {
+        let output = "Id=nginx.service\nActiveState=active\nExecStart={ path=/usr/sbin/nginx ; argv[]=nginx }\n\n\
+                      Id=ssh.service\nActiveState=failed\nDescription=\n";
+        assert_eq!(
+            parse_systemctl_show(output),
+            vec![
+                json!({"unit": "nginx.service", "property": "Id", "value": "nginx.service"}),
+                json!({"unit": "nginx.service", "property": "ActiveState", "value": "active"}),
+                json!({"unit": "nginx.service", "property": "ExecStart", "value": "{ path=/usr/sbin/nginx ; argv[]=nginx }"}),
+                json!({"unit": "ssh.service", "property": "Id", "value": "ssh.service"}),
+                json!({"unit": "ssh.service", "property": "ActiveState", "value": "failed"}),
+                json!({"unit": "ssh.service", "property": "Description", "value": ""}),
+            ]
+        );
+        assert_eq!(
+            parse_systemctl_show("ActiveState=active\n"),
+            vec![json!({"unit": null, "property": "ActiveState", "value": "active"})]
+        );
+    }
+}
@@ -0,0 +1,712 @@
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, TimestampNanosecondArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::json::reader::infer_json_schema_from_iterator;
+use datafusion::arrow::json::ReaderBuilder;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, Result};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::TableProvider;
+use datafusion::error::DataFusionError;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion_expr::{Expr, TableType};
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::any::Any;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::process::{Child, Command, Stdio};
+use parking_lot::Mutex;
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+use crate::commandtable::{exited, host_and_args};
+use crate::remote::Remote;
+use crate::stream::time_ordering;
+
+/// Most lines to fold into a single batch when a log is busy.
+const MAX_BATCH_LINES: usize = 1024;
+
+/// How many existing lines `json` tails look at to work out their schema.
+const JSON_INFER_LINES: usize = 100;
+
+/// How much of a local tail's stderr is kept for its error if it fails, since it may
+/// run for days warning about a log that comes and goes.
+const MAX_STDERR_BYTES: u64 = 64 * 1024;
+
+/// Where `received_ts` comes from: the system clock, unless the session's config
+/// carries another, as tests do to close windows on cue.
+#[derive(Debug, Clone, Copy)]
+pub struct ReceivedClock(pub fn() -> i64);
+
+impl Default for ReceivedClock {
+    fn default() -> Self {
+        Self(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as i64)
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// How the lines of a followed log get turned into columns.
+#[derive(Debug, Clone)]
+pub enum LogFormat {
+    /// Every line as-is in a single `line` column.
+    Raw,
+    /// RFC3164 or RFC5424 syslog, detected per line.
+    Syslog,
+    /// nginx/apache combined (or common) access logs.
+    Combined,
+    /// One JSON object per line, with a schema inferred from the end of the file.
+    Json(SchemaRef),
+    /// `journalctl -o json` records.
+    Journal,
+}
+
+impl LogFormat {
+    fn schema(&self) -> SchemaRef {
+        let fields = match self {
+            LogFormat::Raw => vec![Field::new("line", DataType::Utf8, true)],
+            LogFormat::Syslog => vec![
+                Field::new("priority", DataType::Int64, true),
+                Field::new("facility", DataType::Int64, true),
+                Field::new("severity", DataType::Int64, true),
+                Field::new("timestamp", DataType::Utf8, true),
+                Field::new("hostname", DataType::Utf8, true),
+                Field::new("app_name", DataType::Utf8, true),
+                Field::new("proc_id", DataType::Utf8, true),
+                Field::new("msg_id", DataType::Utf8, true),
+                Field::new("message", DataType::Utf8, true),
+                Field::new("line", DataType::Utf8, true),
+            ],
+            LogFormat::Combined => vec![
+                Field::new("remote_addr", DataType::Utf8, true),
+                Field::new("remote_user", DataType::Utf8, true),
+                Field::new("time_local", DataType::Utf8, true),
+                Field::new("method", DataType::Utf8, true),
+                Field::new("path", DataType::Utf8, true),
+                Field::new("protocol", DataType::Utf8, true),
+                Field::new("status", DataType::Int64, true),
+                Field::new("body_bytes", DataType::Int64, true),
+                Field::new("referer", DataType::Utf8, true),
+                Field::new("user_agent", DataType::Utf8, true),
+                Field::new("line", DataType::Utf8, true),
+            ],
+            LogFormat::Json(schema) => return schema.clone(),
+            LogFormat::Journal => journal_fields(),
+        };
+        Arc::new(Schema::new(fields))
+    }
+
+    fn parse(&self, line: &str) -> Value {
+        match self {
+            LogFormat::Raw => json!({ "line": line }),
+            LogFormat::Syslog => parse_syslog(line),
+            LogFormat::Combined => parse_combined(line),
+            LogFormat::Json(_) => serde_json::from_str(line).unwrap_or(Value::Null),
+            LogFormat::Journal => parse_journal(line),
+        }
+    }
+}
+
+/// Columns for a `journalctl -o json` record.
+pub fn journal_fields() -> Vec<Field> {
+    vec![
+        Field::new("timestamp", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+        Field::new("hostname", DataType::Utf8, true),
+        Field::new("unit", DataType::Utf8, true),
+        Field::new("identifier", DataType::Utf8, true),
+        Field::new("pid", DataType::Int64, true),
+        Field::new("priority", DataType::Int64, true),
+        Field::new("message", DataType::Utf8, true),
+    ]
+}
+
+/// Maps one `journalctl -o json` line onto `journal_fields`.
+pub fn parse_journal(line: &str) -> Value {
+    let record: Map<String, Value> = match serde_json::from_str(line) {
+        Ok(Value::Object(record)) => record,
+        _ => return Value::Null,
+    };
+    // journalctl writes every field as a string, and binary messages as byte arrays
+    let text = |key: &str| match record.get(key) {
+        Some(Value::String(s)) => Value::String(s.clone()),
+        Some(Value::Array(bytes)) => Value::String(
+            String::from_utf8_lossy(
+                &bytes.iter().filter_map(|b| b.as_u64()).map(|b| b as u8).collect::<Vec<_>>(),
+            )
+            .to_string(),
+        ),
+        _ => Value::Null,
+    };
+    let number = |key: &str| match record.get(key) {
+        Some(Value::String(s)) => s.parse::<i64>().map(Value::from).unwrap_or(Value::Null),
+        _ => Value::Null,
+    };
+    json!({
+        "timestamp": number("__REALTIME_TIMESTAMP"),
+        "hostname": text("_HOSTNAME"),
+        "unit": text("_SYSTEMD_UNIT"),
+        "identifier": text("SYSLOG_IDENTIFIER"),
+        "pid": number("_PID"),
+        "priority": number("PRIORITY"),
+        "message": text("MESSAGE"),
+    })
+}
+
+fn parse_syslog(line: &str) -> Value {
+    static RFC5424: OnceLock<Regex> = OnceLock::new();
+    static RFC3164: OnceLock<Regex> = OnceLock::new();
+    let rfc5424 = RFC5424.get_or_init(|| {
+        Regex::new(r"^<(\d{1,3})>1 (\S+) (\S+) (\S+) (\S+) (\S+) (?:-|\[.*?\])(?: (.*))?$").unwrap()
+    });
+    let rfc3164 = RFC3164.get_or_init(|| {
+        Regex::new(
+            r"^(?:<(\d{1,3})>)?([A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}|\d{4}-\d{2}-\d{2}T\S+) (\S+) ([^:\[\s]+)(?:\[([^\]]*)\])?: ?(.*)$",
+        )
+        .unwrap()
+    });
+    let nil = |s: &str| if s == "-" { Value::Null } else { Value::String(s.to_string()) };
+
+    let mut row = Map::new();
+    if let Some(caps) = rfc5424.captures(line) {
+        row.insert("timestamp".into(), nil(&caps[2]));
+        row.insert("hostname".into(), nil(&caps[3]));
+        row.insert("app_name".into(), nil(&caps[4]));
+        row.insert("proc_id".into(), nil(&caps[5]));
+        row.insert("msg_id".into(), nil(&caps[6]));
+        row.insert("message".into(), json!(caps.get(7).map(|m| m.as_str())));
+        insert_priority(&mut row, caps.get(1).map(|m| m.as_str()));
+    } else if let Some(caps) = rfc3164.captures(line) {
+        row.insert("timestamp".into(), json!(&caps[2]));
+        row.insert("hostname".into(), json!(&caps[3]));
+        row.insert("app_name".into(), json!(&caps[4]));
+        row.insert("proc_id".into(), json!(caps.get(5).map(|m| m.as_str())));
+        row.insert("message".into(), json!(&caps[6]));
+        insert_priority(&mut row, caps.get(1).map(|m| m.as_str()));
+    }
+    row.insert("line".into(), json!(line));
+    Value::Object(row)
+}
+
+fn insert_priority(row: &mut Map<String, Value>, priority: Option<&str>) {
+    if let Some(priority) = priority.and_then(|p| p.parse::<i64>().ok()) {
+        row.insert("priority".into(), json!(priority));
+        row.insert("facility".into(), json!(priority / 8));
+        row.insert("severity".into(), json!(priority % 8));
+    }
+}
+
+fn parse_combined(line: &str) -> Value {
+    static COMBINED: OnceLock<Regex> = OnceLock::new();
+    let combined = COMBINED.get_or_init(|| {
+        Regex::new(
+            r#"^(\S+) \S+ (\S+) \[([^\]]+)\] "(\S+)(?: (\S+))?(?: (\S+))?" (\d{3}) (\d+|-)(?: "([^"]*)" "([^"]*)")?"#,
+        )
+        .unwrap()
+    });
+    let text = |m: Option<regex::Match>| match m.map(|m| m.as_str()) {
+        None | Some("-") => Value::Null,
+        Some(s) => Value::String(s.to_string()),
+    };
+    let number = |m: Option<regex::Match>| {
+        m.and_then(|m| m.as_str().parse::<i64>().ok()).map(Value::from).unwrap_or(Value::Null)
+    };
+
+    let mut row = Map::new();
+    if let Some(caps) = combined.captures(line) {
+        row.insert("remote_addr".into(), text(caps.get(1)));
+        row.insert("remote_user".into(), text(caps.get(2)));
+        row.insert("time_local".into(), text(caps.get(3)));
+        row.insert("method".into(), text(caps.get(4)));
+        row.insert("path".into(), text(caps.get(5)));
+        row.insert("protocol".into(), text(caps.get(6)));
+        row.insert("status".into(), number(caps.get(7)));
+        row.insert("body_bytes".into(), number(caps.get(8)));
+        row.insert("referer".into(), text(caps.get(9)));
+        row.insert("user_agent".into(), text(caps.get(10)));
+    }
+    row.insert("line".into(), json!(line));
+    Value::Object(row)
+}
+
+/// Quotes an argument for the remote shell.
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Runs a command to completion, locally or on a remote host, and returns its stdout.
+/// A command that exits non-zero is an error with its stderr, the same either way.
+pub(crate) fn read_all(remote: &Remote, host: &str, command: &[String]) -> std::io::Result<String> {
+    if host == "localhost" {
+        let output = Command::new(&command[0]).args(&command[1..]).output()?;
+        if !output.status.success() {
+            return Err(exited(&command[0], output.status, &output.stderr).into());
+        }
+        return String::from_utf8(output.stdout).map_err(std::io::Error::other);
+    }
+    let command_string = command.iter().map(|a| shell_quote(a)).collect::<Vec<_>>().join(" ");
+    Ok(remote.transport.exec(host, &command_string)?)
+}
+
+/// Kills the local `tail -F` when the query that started it goes away. A remote one is
+/// dropped by the thread reading it, which notices the next time its read times out.
+struct ChildGuard(Option<Arc<Mutex<Child>>>);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(child) = &self.0 {
+            let mut child = child.lock();
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// A line of output with when it arrived, or why there won't be any more.
+type FollowedLine = std::result::Result<(i64, String), String>;
+
+/// Starts a never-ending command and forwards each line of its output. If it stops
+/// anyway, a local one's stderr is the error, like a remote one that can't start.
+fn follow_lines(
+    remote: &Remote,
+    host: &str,
+    command: &[String],
+    clock: ReceivedClock,
+) -> std::io::Result<(mpsc::Receiver<FollowedLine>, ChildGuard)> {
+    let (tx, rx) = mpsc::channel(MAX_BATCH_LINES);
+    if host == "localhost" {
+        let mut child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("Failed to open command stdout");
+        let mut stderr = child.stderr.take().expect("Failed to open command stderr");
+        let child = Arc::new(Mutex::new(child));
+        let (name, waited) = (command[0].clone(), child.clone());
+        std::thread::spawn(move || {
+            // Read stderr alongside stdout so a command that warns a lot can't stall on it.
+            let stderr = std::thread::spawn(move || {
+                let mut kept = Vec::new();
+                let _ = (&mut stderr).take(MAX_STDERR_BYTES).read_to_end(&mut kept);
+                let _ = std::io::copy(&mut stderr, &mut std::io::sink());
+                kept
+            });
+            forward_lines(stdout, tx.clone(), clock);
+            let status = waited.lock().wait();
+            let stderr = stderr.join().unwrap_or_default();
+            if let Ok(status) = status {
+                if !status.success() && !tx.is_closed() {
+                    let _ = tx.blocking_send(Err(exited(&name, status, &stderr).to_string()));
+                }
+            }
+        });
+        return Ok((rx, ChildGuard(Some(child))));
+    }
+
+    let (remote, host) = (remote.clone(), host.to_string());
+    let command_string = command.iter().map(|a| shell_quote(a)).collect::<Vec<_>>().join(" ");
+    std::thread::spawn(move || match remote.transport.follow(&host, &command_string) {
+        Ok(output) => forward_lines(output, tx, clock),
+        Err(e) => {
+            let _ = tx.blocking_send(Err(e.to_string()));
+        }
+    });
+    Ok((rx, ChildGuard(None)))
+}
+
+/// Sends each line of `output` until it ends, fails or nobody is listening. Reads that
+/// time out just give it a chance to check for the last of those.
+fn forward_lines(output: impl Read, tx: mpsc::Sender<FollowedLine>, clock: ReceivedClock) {
+    let mut output = BufReader::new(output);
+    let mut line = Vec::new();
+    loop {
+        match output.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                if line.ends_with(b"\n") {
+                    line.pop();
+                }
+                let text = String::from_utf8_lossy(&line).into_owned();
+                line.clear();
+                if tx.blocking_send(Ok((clock.0(), text))).is_err() {
+                    break;
+                }
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted) => {
+                if tx.is_closed() {
+                    break;
+                }
+            }
+            Err(e) => {
+                let _ = tx.blocking_send(Err(e.to_string()));
+                break;
+            }
+        }
+    }
+}
+
+/// Table function for `tail(host('web1'), '/var/log/nginx/access.log', 'combined')`.
+#[derive(Debug)]
+pub struct TailTableFunc {
+    pub remote: Remote,
+}
+
+impl TableFunctionImpl for TailTableFunc {
+    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let (host, args) = host_and_args(exprs);
+        let path = match args.first() {
+            Some(path) => path.clone(),
+            None => return plan_err!("tail expects a path, e.g. tail('/var/log/syslog')"),
+        };
+        let format = match args.get(1).map(|f| f.as_str()).unwrap_or("raw") {
+            "raw" => LogFormat::Raw,
+            "syslog" | "rfc3164" | "rfc5424" => LogFormat::Syslog,
+            "combined" | "common" | "nginx" | "apache" => LogFormat::Combined,
+            "json" | "jsonl" | "ndjson" => {
+                LogFormat::Json(infer_json_lines_schema(&self.remote, &host, &path)?)
+            }
+            other => {
+                return plan_err!(
+                    "Unknown log format '{}', expected raw, syslog, combined or json",
+                    other
+                )
+            }
+        };
+        let command = ["tail", "-F", "-n", "0", &path].map(String::from).to_vec();
+        Ok(Arc::new(TailTable::new(self.remote.clone(), host, command, format)))
+    }
+}
+
+/// Works out the columns of a JSON lines log from what's already in it.
+fn infer_json_lines_schema(remote: &Remote, host: &str, path: &str) -> Result<SchemaRef> {
+    let command = ["tail", "-n", &JSON_INFER_LINES.to_string(), path].map(String::from).to_vec();
+    let existing = read_all(remote, host, &command)
+        .map_err(|e| DataFusionError::Execution(format!("Failed to read {}: {}", path, e)))?;
+    let values = existing
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|value| value.is_object())
+        .map(Ok);
+    let schema = infer_json_schema_from_iterator(values)?;
+    if schema.fields().is_empty() {
+        return plan_err!("Could not infer a schema for {}, it has no JSON lines yet", path);
+    }
+    Ok(Arc::new(schema))
+}
+
+/// Table function for `tail_journal(host('web1'), 'nginx.service')`, following the
+/// systemd journal instead of a file.
+#[derive(Debug)]
+pub struct TailJournalTableFunc {
+    pub remote: Remote,
+}
+
+impl TableFunctionImpl for TailJournalTableFunc {
+    fn call(&self, exprs: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let (host, args) = host_and_args(exprs);
+        let mut command = ["journalctl", "-f", "-n", "0", "-o", "json"].map(String::from).to_vec();
+        if let Some(unit) = args.first() {
+            command.push("-u".to_string());
+            command.push(unit.clone());
+        }
+        Ok(Arc::new(TailTable::new(self.remote.clone(), host, command, LogFormat::Journal)))
+    }
+}
+
+/// An unbounded table over the lines a command prints, tagged with `received_ts`.
+#[derive(Debug)]
+pub struct TailTable {
+    follower: Arc<LineFollower>,
+}
+
+impl TailTable {
+    pub fn new(remote: Remote, host: String, command: Vec<String>, format: LogFormat) -> Self {
+        let mut fields = vec![Field::new(
+            "received_ts",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )];
+        fields.extend(format.schema().fields().iter().map(|f| f.as_ref().clone()));
+        Self {
+            follower: Arc::new(LineFollower {
+                remote,
+                host,
+                command,
+                format,
+                schema: Arc::new(Schema::new(fields)),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for TailTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.follower.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.follower.schema.clone(),
+            vec![self.follower.clone()],
+            projection,
+            time_ordering("received_ts", projection),
+            true,
+            limit,
+        )?))
+    }
+}
+
+#[derive(Debug)]
+struct LineFollower {
+    remote: Remote,
+    host: String,
+    command: Vec<String>,
+    format: LogFormat,
+    schema: SchemaRef,
+}
+
+impl LineFollower {
+    fn to_batch(&self, lines: &[(i64, String)]) -> Result<RecordBatch> {
+        let received_ts: Vec<i64> = lines.iter().map(|(received_ts, _)| *received_ts).collect();
+        let rows: Vec<Value> = lines.iter().map(|(_, line)| self.format.parse(line)).collect();
+
+        let format_schema = self.format.schema();
+        let mut decoder = ReaderBuilder::new(format_schema.clone()).build_decoder()?;
+        decoder.serialize(&rows)?;
+        let batch = decoder
+            .flush()?
+            .unwrap_or_else(|| RecordBatch::new_empty(format_schema));
+
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(TimestampNanosecondArray::from(received_ts))];
+        columns.extend(batch.columns().iter().cloned());
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+}
+
+impl PartitionStream for LineFollower {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let follower = Arc::new(LineFollower {
+            remote: self.remote.clone(),
+            host: self.host.clone(),
+            command: self.command.clone(),
+            format: self.format.clone(),
+            schema: self.schema.clone(),
+        });
+        let command = self.command.join(" ");
+        let failed = move |e: String| DataFusionError::Execution(format!("Failed to follow {}: {}", command, e));
+        let clock = ctx
+            .session_config()
+            .get_extension::<ReceivedClock>()
+            .map(|clock| *clock)
+            .unwrap_or_default();
+        let started =
+            follow_lines(&self.remote, &self.host, &self.command, clock).map_err(|e| failed(e.to_string()));
+
+        let batches = futures::stream::unfold(Some(started), move |state| {
+            let follower = follower.clone();
+            let failed = failed.clone();
+            async move {
+                let (mut rx, guard) = match state? {
+                    Ok(started) => started,
+                    Err(e) => return Some((Err(e), None)),
+                };
+                // Wait for one line, then take whatever else has already arrived. Lines
+                // that came before an error are sent first, and the error after them.
+                let mut lines = match rx.recv().await? {
+                    Ok(line) => vec![line],
+                    Err(e) => return Some((Err(failed(e)), None)),
+                };
+                while lines.len() < MAX_BATCH_LINES {
+                    match rx.try_recv() {
+                        Ok(Ok(line)) => lines.push(line),
+                        Ok(Err(e)) => {
+                            let batch = follower.to_batch(&lines);
+                            return Some((batch, Some(Err(failed(e)))));
+                        }
+                        Err(_) => break,
+                    }
+                }
+                Some((follower.to_batch(&lines), Some(Ok((rx, guard)))))
+            }
+        });
+        Box::pin(RecordBatchStreamAdapter::new(self.schema.clone(), batches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ZqueryContext;
+    use crate::engine::sql_to_df;
+    use crate::error::ZqueryError;
+    use crate::remote::Transport;
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::Int64Type;
+    use futures::StreamExt;
+    use datafusion::prelude::SessionConfig;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+    use std::time::Duration;
+
+    /// What the fake clock says, set by `QuietOutput` as it hands out each chunk of a
+    /// transport given chunks.
+    static NOW: AtomicI64 = AtomicI64::new(0);
+
+    /// Follows by handing back each chunk, at its time on the fake clock, and then
+    /// timing out until it's dropped.
+    #[derive(Debug, Default)]
+    struct QuietTransport {
+        unreachable: bool,
+        chunks: Vec<(i64, &'static [u8])>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    struct QuietOutput {
+        chunks: VecDeque<(i64, &'static [u8])>,
+        sets_clock: bool,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Read for QuietOutput {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if let Some((now, chunk)) = self.chunks.pop_front() {
+                if self.sets_clock {
+                    NOW.store(now, Ordering::SeqCst);
+                }
+                buf[..chunk.len()].copy_from_slice(chunk);
+                return Ok(chunk.len());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            Err(ErrorKind::TimedOut.into())
+        }
+    }
+
+    impl Drop for QuietOutput {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl Transport for QuietTransport {
+        fn exec(&self, host: &str, _command: &str) -> std::result::Result<String, ZqueryError> {
+            Err(ZqueryError::Ssh(format!("Could not connect to {}:22", host)))
+        }
+
+        fn follow(&self, host: &str, _command: &str) -> std::result::Result<Box<dyn Read + Send>, ZqueryError> {
+            if self.unreachable {
+                return Err(ZqueryError::Ssh(format!("Could not connect to {}:22", host)));
+            }
+            let chunks = match self.chunks.is_empty() {
+                true => VecDeque::from([(0, b"first line\n".as_slice())]),
+                false => self.chunks.iter().copied().collect(),
+            };
+            Ok(Box::new(QuietOutput {
+                chunks,
+                sets_clock: !self.chunks.is_empty(),
+                dropped: self.dropped.clone(),
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn a_remote_tail_that_cannot_start_is_an_error() {
+        let transport = QuietTransport { unreachable: true, ..Default::default() };
+        let ctx = ZqueryContext::new().with_transport(transport).build();
+        let sql = "select * from tail(host('web1'), '/var/log/syslog')";
+        let error = sql_to_df(&ctx, sql).await.unwrap().collect().await.unwrap_err();
+        assert!(error.to_string().contains("Could not connect to web1:22"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn a_remote_tail_stops_when_the_query_does() {
+        let transport = QuietTransport::default();
+        let dropped = transport.dropped.clone();
+        let ctx = ZqueryContext::new().with_transport(transport).build();
+        let sql = "select line from tail(host('web1'), '/var/log/syslog')";
+        let mut stream = sql_to_df(&ctx, sql).await.unwrap().execute_stream().await.unwrap();
+        let batch = stream.next().await.unwrap().unwrap();
+        assert_eq!(batch.column(0).as_string::<i32>().value(0), "first line");
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        drop(stream);
+        for _ in 0..100 {
+            if dropped.load(Ordering::SeqCst) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the remote tail was never dropped");
+    }
+
+    #[test]
+    fn a_command_read_in_full_that_fails_is_an_error_with_its_stderr() {
+        let command = ["sh", "-c", "echo partial; echo 'journalctl: it broke' >&2; exit 1"].map(String::from);
+        let error = read_all(&Remote::default(), "localhost", &command).unwrap_err();
+        assert_eq!(error.to_string(), "Command failed: journalctl: it broke");
+    }
+
+    #[test]
+    fn a_local_tail_that_stops_is_an_error_with_its_stderr() {
+        let command = ["sh", "-c", "echo partial; echo 'tail: it broke' >&2; exit 1"].map(String::from);
+        let (mut rx, _guard) = follow_lines(&Remote::default(), "localhost", &command, ReceivedClock::default()).unwrap();
+        assert!(matches!(rx.blocking_recv(), Some(Ok((_, line))) if line == "partial"));
+        assert_eq!(rx.blocking_recv(), Some(Err("Command failed: tail: it broke".to_string())));
+    }
+
+    #[tokio::test]
+    async fn a_windowed_group_by_emits_each_window_as_it_closes() {
+        let line = |status| format!("10.0.0.1 - - [19/Oct/2026:10:00:00 +0000] \"GET / HTTP/1.1\" {} 12\n", status);
+        let first: &'static str = format!("{}{}", line(200), line(200)).leak();
+        let (second, third): (&'static str, &'static str) = (line(404).leak(), line(500).leak());
+        let second_ns = 1_000_000_000;
+        let transport = QuietTransport {
+            // The first window gets three lines in two reads, and a line in the
+            // window after closes it.
+            chunks: vec![(100, first.as_bytes()), (second_ns - 1, second.as_bytes()), (second_ns, third.as_bytes())],
+            ..Default::default()
+        };
+        let clock = ReceivedClock(|| NOW.load(Ordering::SeqCst));
+        let config = SessionConfig::new().with_coalesce_batches(false).with_extension(Arc::new(clock));
+        let ctx = ZqueryContext::new().with_config(config).with_transport(transport).build();
+        let sql = "select tumble(received_ts, '1s') as w, status, count(*) as n from tail(host('web1'), '/var/log/nginx/access.log', 'combined') \
+             group by status, tumble(received_ts, '1s')";
+        let mut stream = sql_to_df(&ctx, sql).await.unwrap().execute_stream().await.unwrap();
+
+        let batch = tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .expect("the first window was never emitted")
+            .unwrap()
+            .unwrap();
+        let (status, n) = (batch.column(1).as_primitive::<Int64Type>(), batch.column(2).as_primitive::<Int64Type>());
+        let mut counts: Vec<(i64, i64)> = (0..batch.num_rows()).map(|i| (status.value(i), n.value(i))).collect();
+        counts.sort();
+        assert_eq!(counts, vec![(200, 2), (404, 1)]);
+    }
+}
@@ -0,0 +1,685 @@
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use datafusion::execution::context::SessionContext;
+use datafusion::physical_plan::{collect, ExecutionPlanProperties};
+use futures::StreamExt;
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{
+    Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState,
+};
+use ratatui::{DefaultTerminal, Frame};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::engine::sql_to_df;
+use crate::error::ZqueryError;
+use crate::remote::Remote;
+use crate::script::substitute;
+use crate::stream::parse_duration;
+
+/// Cells wider than this are cut off in the grid.
+const MAX_COLUMN_WIDTH: usize = 40;
+/// How often panes saved from the editor refresh.
+const DEFAULT_EVERY: Duration = Duration::from_secs(5);
+
+/// A `dashboard.toml` for `zquery tui`. `:host` in a query is replaced with the host
+/// picked with F2, so one dashboard can be pointed at any machine.
+///
+/// ```toml
+/// [[query]]
+/// title = "Top CPU"
+/// sql = "select pid, cpu_percent, command from ps(host(:'host')) order by cpu_percent desc limit 20"
+/// every = "2s"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Dashboard {
+    #[serde(rename = "query", default)]
+    pub queries: Vec<SavedQuery>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavedQuery {
+    pub title: String,
+    pub sql: String,
+    /// How often to re-run the query, e.g. `2s` or `1m`.
+    #[serde(default = "default_every")]
+    pub every: String,
+}
+
+fn default_every() -> String {
+    "5s".to_string()
+}
+
+pub fn load_dashboard(path: &Path) -> std::io::Result<Dashboard> {
+    let text = std::fs::read_to_string(path)?;
+    let dashboard: Dashboard = toml::from_str(&text)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    for query in &dashboard.queries {
+        if parse_duration(&query.every).is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Query {} has an invalid interval '{}'", query.title, query.every),
+            ));
+        }
+    }
+    Ok(dashboard)
+}
+
+/// One value in the grid, with its number kept around so numeric columns sort as numbers.
+struct GridValue {
+    text: String,
+    number: Option<f64>,
+}
+
+/// Query results turned into text once, so drawing and sorting don't touch Arrow.
+struct Grid {
+    columns: Vec<String>,
+    rows: Vec<Vec<GridValue>>,
+}
+
+impl Grid {
+    fn from_batches(batches: &[RecordBatch]) -> datafusion::error::Result<Self> {
+        let columns = match batches.first() {
+            Some(batch) => batch.schema().fields().iter().map(|f| f.name().clone()).collect(),
+            None => Vec::new(),
+        };
+        let options = FormatOptions::default().with_null("");
+        let mut rows = Vec::new();
+        for batch in batches {
+            let formatters = batch
+                .columns()
+                .iter()
+                .map(|c| ArrayFormatter::try_new(c.as_ref(), &options))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            for row in 0..batch.num_rows() {
+                rows.push(
+                    formatters
+                        .iter()
+                        .zip(batch.columns())
+                        .map(|(formatter, column)| {
+                            let text = formatter.value(row).to_string();
+                            let number = if column.data_type().is_numeric() {
+                                text.parse().ok()
+                            } else {
+                                None
+                            };
+                            GridValue { text, number }
+                        })
+                        .collect(),
+                );
+            }
+        }
+        Ok(Self { columns, rows })
+    }
+
+    /// Sorts on a column, numbers as numbers, with empty values last.
+    fn sort(&mut self, column: usize, descending: bool) {
+        self.rows.sort_by(|a, b| {
+            let (a, b) = (&a[column], &b[column]);
+            let ordering = match (a.text.is_empty(), b.text.is_empty()) {
+                (true, false) => return Ordering::Greater,
+                (false, true) => return Ordering::Less,
+                _ => match (a.number, b.number) {
+                    (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                    _ => a.text.cmp(&b.text),
+                },
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+/// A query and its latest results. The first pane shows whatever was run from the
+/// editor; the rest refresh on their own schedule.
+struct Pane {
+    title: String,
+    sql: String,
+    every: Option<Duration>,
+    grid: Option<Grid>,
+    error: Option<String>,
+    running: bool,
+    last_run: Option<Instant>,
+    took: Option<Duration>,
+    /// Bumped on every run so results from an older run that finishes late are dropped.
+    generation: u64,
+    sort: Option<(usize, bool)>,
+    table: TableState,
+    first_column: usize,
+}
+
+impl Pane {
+    fn new(title: String, sql: String, every: Option<Duration>) -> Self {
+        Self {
+            title,
+            sql,
+            every,
+            grid: None,
+            error: None,
+            running: false,
+            last_run: None,
+            took: None,
+            generation: 0,
+            sort: None,
+            table: TableState::default(),
+            first_column: 0,
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        match (self.every, self.last_run) {
+            _ if self.running || self.sql.is_empty() => false,
+            (Some(_), None) => true,
+            (Some(every), Some(last_run)) => last_run.elapsed() >= every,
+            (None, _) => false,
+        }
+    }
+
+    fn apply_sort(&mut self) {
+        if let (Some(grid), Some((column, descending))) = (&mut self.grid, self.sort) {
+            if column < grid.columns.len() {
+                grid.sort(column, descending);
+            }
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        self.grid.as_ref().map_or(0, |g| g.rows.len())
+    }
+
+    fn column_count(&self) -> usize {
+        self.grid.as_ref().map_or(0, |g| g.columns.len())
+    }
+}
+
+struct QueryResult {
+    pane: usize,
+    generation: u64,
+    result: std::result::Result<Grid, String>,
+    took: Duration,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Editor,
+    Pane(usize),
+}
+
+struct App {
+    ctx: SessionContext,
+    editor: String,
+    /// Cursor position in the editor, in chars.
+    cursor: usize,
+    panes: Vec<Pane>,
+    focus: Focus,
+    hosts: Vec<String>,
+    host: String,
+    /// Open while picking a host.
+    host_picker: Option<ListState>,
+    results: mpsc::UnboundedSender<QueryResult>,
+    quit: bool,
+}
+
+impl App {
+    /// Starts a pane's query on a blocking thread and returns straight away. Command
+    /// tables block while they run, so neither the UI task nor the runtime's workers wait
+    /// on a slow host.
+    fn run(&mut self, index: usize) {
+        let vars = HashMap::from([("host".to_string(), self.host.clone())]);
+        let pane = &mut self.panes[index];
+        pane.generation += 1;
+        pane.running = true;
+        pane.last_run = Some(Instant::now());
+        let (ctx, sql, generation) = (self.ctx.clone(), substitute(&pane.sql, &vars), pane.generation);
+        let results = self.results.clone();
+        let runtime = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || {
+            let started = Instant::now();
+            let result = runtime
+                .block_on(run_query(&ctx, &sql))
+                .map_err(|e| ZqueryError::from(e).to_string());
+            let _ = results.send(QueryResult {
+                pane: index,
+                generation,
+                result,
+                took: started.elapsed(),
+            });
+        });
+    }
+
+    fn apply(&mut self, result: QueryResult) {
+        let pane = match self.panes.get_mut(result.pane) {
+            Some(pane) if pane.generation == result.generation => pane,
+            _ => return,
+        };
+        pane.running = false;
+        pane.took = Some(result.took);
+        match result.result {
+            Ok(grid) => {
+                pane.grid = Some(grid);
+                pane.error = None;
+                pane.apply_sort();
+                let rows = pane.row_count();
+                if pane.table.selected().is_none_or(|s| s >= rows) {
+                    pane.table.select(if rows == 0 { None } else { Some(0) });
+                }
+            }
+            Err(e) => pane.error = Some(e),
+        }
+    }
+
+    fn refresh_due(&mut self) {
+        for index in 0..self.panes.len() {
+            if self.panes[index].is_due() {
+                self.run(index);
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl && matches!(key.code, KeyCode::Char('c') | KeyCode::Char('q')) {
+            self.quit = true;
+            return;
+        }
+        if self.host_picker.is_some() {
+            self.handle_host_picker(key);
+            return;
+        }
+        match key.code {
+            KeyCode::Tab => self.focus = self.next_focus(1),
+            KeyCode::BackTab => self.focus = self.next_focus(self.panes.len()),
+            KeyCode::F(2) => {
+                let mut state = ListState::default();
+                state.select(self.hosts.iter().position(|h| *h == self.host));
+                self.host_picker = Some(state);
+            }
+            KeyCode::F(5) => {
+                for index in 0..self.panes.len() {
+                    if !self.panes[index].sql.is_empty() {
+                        self.run(index);
+                    }
+                }
+            }
+            KeyCode::Esc => self.focus = Focus::Editor,
+            _ => match self.focus {
+                Focus::Editor => self.handle_editor(key),
+                Focus::Pane(index) => self.handle_pane(index, key),
+            },
+        }
+    }
+
+    /// Cycles through the editor and then each pane.
+    fn next_focus(&self, step: usize) -> Focus {
+        let count = self.panes.len() + 1;
+        let current = match self.focus {
+            Focus::Editor => 0,
+            Focus::Pane(index) => index + 1,
+        };
+        match (current + step) % count {
+            0 => Focus::Editor,
+            n => Focus::Pane(n - 1),
+        }
+    }
+
+    fn handle_editor(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let byte = |s: &str, chars: usize| s.char_indices().nth(chars).map_or(s.len(), |(i, _)| i);
+        match key.code {
+            KeyCode::Enter => {
+                let sql = self.editor.trim().trim_end_matches(';').to_string();
+                if !sql.is_empty() {
+                    self.panes[0].sql = sql;
+                    self.panes[0].sort = None;
+                    self.run(0);
+                }
+            }
+            // Keeps the query refreshing in a pane of its own.
+            KeyCode::Char('s') if ctrl => {
+                let sql = self.editor.trim().trim_end_matches(';').to_string();
+                if !sql.is_empty() {
+                    let title: String = sql.chars().take(40).collect();
+                    self.panes.push(Pane::new(title, sql, Some(DEFAULT_EVERY)));
+                    self.run(self.panes.len() - 1);
+                }
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.editor.clear();
+                self.cursor = 0;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                let at = byte(&self.editor, self.cursor);
+                self.editor.insert(at, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let at = byte(&self.editor, self.cursor);
+                self.editor.remove(at);
+            }
+            KeyCode::Delete if self.cursor < self.editor.chars().count() => {
+                let at = byte(&self.editor, self.cursor);
+                self.editor.remove(at);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.editor.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.editor.chars().count(),
+            _ => {}
+        }
+    }
+
+    fn handle_pane(&mut self, index: usize, key: KeyEvent) {
+        let pane = &mut self.panes[index];
+        let rows = pane.row_count();
+        let last = rows.saturating_sub(1);
+        let selected = pane.table.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') => pane.table.select(Some((selected + 1).min(last))),
+            KeyCode::Up | KeyCode::Char('k') => pane.table.select(Some(selected.saturating_sub(1))),
+            KeyCode::PageDown => pane.table.select(Some((selected + 20).min(last))),
+            KeyCode::PageUp => pane.table.select(Some(selected.saturating_sub(20))),
+            KeyCode::Home | KeyCode::Char('g') => pane.table.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => pane.table.select(Some(last)),
+            KeyCode::Right | KeyCode::Char('l') => {
+                pane.first_column = (pane.first_column + 1).min(pane.column_count().saturating_sub(1))
+            }
+            KeyCode::Left | KeyCode::Char('h') => pane.first_column = pane.first_column.saturating_sub(1),
+            // `s` moves the sort to the next column, `r` flips its direction.
+            KeyCode::Char('s') => {
+                let columns = pane.column_count();
+                if columns > 0 {
+                    pane.sort = match pane.sort {
+                        Some((column, _)) if column + 1 < columns => Some((column + 1, false)),
+                        Some(_) => None,
+                        None => Some((0, false)),
+                    };
+                    pane.apply_sort();
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some((column, descending)) = pane.sort {
+                    pane.sort = Some((column, !descending));
+                    pane.apply_sort();
+                }
+            }
+            KeyCode::Char('R') => self.run(index),
+            KeyCode::Char('d') if index > 0 => {
+                // Results still on their way for later panes would land in the wrong one.
+                for pane in &mut self.panes[index..] {
+                    pane.generation += 1;
+                    pane.running = false;
+                }
+                self.panes.remove(index);
+                self.focus = Focus::Pane(index - 1);
+            }
+            KeyCode::Char('e') => {
+                self.editor = pane.sql.clone();
+                self.cursor = self.editor.chars().count();
+                self.focus = Focus::Editor;
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_host_picker(&mut self, key: KeyEvent) {
+        let state = match &mut self.host_picker {
+            Some(state) => state,
+            None => return,
+        };
+        let selected = state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                state.select(Some((selected + 1).min(self.hosts.len().saturating_sub(1))))
+            }
+            KeyCode::Up | KeyCode::Char('k') => state.select(Some(selected.saturating_sub(1))),
+            KeyCode::Enter => {
+                if let Some(host) = self.hosts.get(selected) {
+                    self.host = host.clone();
+                }
+                self.host_picker = None;
+                for index in 0..self.panes.len() {
+                    if self.panes[index].sql.contains(":host") {
+                        self.run(index);
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::F(2) => self.host_picker = None,
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [editor, panes, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.draw_editor(frame, editor);
+
+        let count = self.panes.len();
+        let columns = (count as f64).sqrt().ceil() as usize;
+        let rows = count.div_ceil(columns);
+        let row_areas = Layout::vertical(vec![Constraint::Ratio(1, rows as u32); rows]).split(panes);
+        for (row, row_area) in row_areas.iter().enumerate() {
+            let in_row = (count - row * columns).min(columns);
+            let areas = Layout::horizontal(vec![Constraint::Ratio(1, in_row as u32); in_row]).split(*row_area);
+            for (column, area) in areas.iter().enumerate() {
+                let index = row * columns + column;
+                let focused = self.focus == Focus::Pane(index);
+                draw_pane(frame, *area, &mut self.panes[index], focused);
+            }
+        }
+
+        let help = match self.focus {
+            Focus::Editor => "Enter run · Ctrl-S keep as pane · Ctrl-U clear",
+            Focus::Pane(_) => "↑↓ rows · ←→ columns · s sort · r reverse · R refresh · e edit · d close",
+        };
+        let status_line = Line::from(vec![
+            format!(" host: {} ", self.host).black().on_cyan(),
+            format!(" {} · Tab focus · F2 hosts · F5 refresh all · Ctrl-C quit", help).into(),
+        ]);
+        frame.render_widget(Paragraph::new(status_line), status);
+
+        if let Some(state) = &mut self.host_picker {
+            let height = (self.hosts.len() as u16 + 2).min(frame.area().height.saturating_sub(4));
+            let area = centered(frame.area(), 40, height);
+            let items: Vec<ListItem> = self.hosts.iter().map(|h| ListItem::new(h.as_str())).collect();
+            let list = List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(" Host for :host "))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            frame.render_widget(Clear, area);
+            frame.render_stateful_widget(list, area, state);
+        }
+    }
+
+    fn draw_editor(&self, frame: &mut Frame, area: Rect) {
+        let focused = self.focus == Focus::Editor;
+        let width = area.width.saturating_sub(2) as usize;
+        // Scroll sideways so the cursor stays in view.
+        let skip = (self.cursor + 1).saturating_sub(width);
+        let visible: String = self.editor.chars().skip(skip).take(width).collect();
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" SQL ")
+            .border_style(border_style(focused));
+        frame.render_widget(Paragraph::new(visible).block(block), area);
+        if focused {
+            frame.set_cursor_position(Position::new(area.x + 1 + (self.cursor - skip) as u16, area.y + 1));
+        }
+    }
+}
+
+fn border_style(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    }
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    )
+}
+
+fn draw_pane(frame: &mut Frame, area: Rect, pane: &mut Pane, focused: bool) {
+    let mut title = format!(" {} ", if pane.title.is_empty() { "Result" } else { &pane.title });
+    if let Some(every) = pane.every {
+        title.push_str(&format!("· every {:?} ", every));
+    }
+    if pane.grid.is_some() {
+        title.push_str(&format!("· {} rows ", pane.row_count()));
+    }
+    if let Some(took) = pane.took {
+        title.push_str(&format!("· {:.0?} ", took));
+    }
+    if pane.running {
+        title.push_str("· running… ");
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(border_style(focused));
+
+    if let Some(error) = &pane.error {
+        let text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .wrap(ratatui::widgets::Wrap { trim: false })
+            .block(block);
+        frame.render_widget(text, area);
+        return;
+    }
+    let grid = match &pane.grid {
+        Some(grid) => grid,
+        None => {
+            let hint = if pane.sql.is_empty() { "Type a query above and press Enter." } else { "" };
+            frame.render_widget(Paragraph::new(hint).block(block), area);
+            return;
+        }
+    };
+
+    let first = pane.first_column.min(grid.columns.len().saturating_sub(1));
+    let shown = first..grid.columns.len();
+    let widths: Vec<Constraint> = shown
+        .clone()
+        .map(|c| {
+            let widest = grid
+                .rows
+                .iter()
+                .map(|r| r[c].text.chars().count())
+                .chain(std::iter::once(grid.columns[c].chars().count() + 2))
+                .max()
+                .unwrap_or(0);
+            Constraint::Length(widest.min(MAX_COLUMN_WIDTH) as u16)
+        })
+        .collect();
+    let header = Row::new(shown.clone().map(|c| {
+        let arrow = match pane.sort {
+            Some((column, false)) if column == c => " ▲",
+            Some((column, true)) if column == c => " ▼",
+            _ => "",
+        };
+        Cell::from(format!("{}{}", grid.columns[c], arrow))
+    }))
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = grid.rows.iter().map(|row| {
+        Row::new(shown.clone().map(|c| {
+            let style = if row[c].number.is_some() {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            Cell::from(row[c].text.as_str()).style(style)
+        }))
+    });
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .highlight_style(if focused {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        });
+    frame.render_stateful_widget(table, area, &mut pane.table);
+}
+
+async fn run_query(ctx: &SessionContext, sql: &str) -> datafusion::error::Result<Grid> {
+    let df = sql_to_df(ctx, sql).await?;
+    let plan = df.create_physical_plan().await?;
+    if plan.execution_mode().is_unbounded() {
+        return datafusion::common::plan_err!(
+            "stream() queries never finish, so keep a query as a pane to refresh it instead"
+        );
+    }
+    Grid::from_batches(&collect(plan, ctx.task_ctx()).await?)
+}
+
+/// Runs the TUI until ctrl-c. Keys go to the UI as they arrive and results as queries
+/// finish, so the screen keeps responding however long a host takes.
+pub async fn tui(ctx: SessionContext, dashboard: Dashboard) -> std::io::Result<()> {
+    let (results, mut finished) = mpsc::unbounded_channel();
+    let mut panes = vec![Pane::new(String::new(), String::new(), None)];
+    for query in dashboard.queries {
+        panes.push(Pane::new(query.title, query.sql, parse_duration(&query.every)));
+    }
+    let mut hosts = vec!["localhost".to_string()];
+    hosts.extend(Remote::of(&ctx.state()).inventory.hosts().unwrap_or_default());
+    let mut app = App {
+        ctx,
+        editor: String::new(),
+        cursor: 0,
+        panes,
+        focus: Focus::Editor,
+        hosts,
+        host: "localhost".to_string(),
+        host_picker: None,
+        results,
+        quit: false,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, &mut finished).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    finished: &mut mpsc::UnboundedReceiver<QueryResult>,
+) -> std::io::Result<()> {
+    let mut events = EventStream::new();
+    let mut ticker = tokio::time::interval(Duration::from_millis(250));
+    while !app.quit {
+        terminal.draw(|frame| app.draw(frame))?;
+        tokio::select! {
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => app.handle_key(key),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+                None => break,
+            },
+            Some(result) = finished.recv() => app.apply(result),
+            _ = ticker.tick() => app.refresh_due(),
+        }
+    }
+    Ok(())
+}
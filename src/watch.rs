@@ -0,0 +1,331 @@
+use datafusion::arrow::json::writer::{JsonArray, WriterBuilder};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::execution::context::SessionContext;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::engine::sql_to_df;
+use crate::stream::parse_duration;
+
+/// A `rules.toml` file for `zquery watch`.
+///
+/// ```toml
+/// [[rule]]
+/// name = "disk-full"
+/// sql = "select host, mounted_on, capacity_percent from df(hosts('prod-*')) where capacity_percent > 90"
+/// every = "5m"
+/// actions = [
+///     { type = "stdout" },
+///     { type = "log", path = "/var/log/zquery-alerts.log" },
+///     { type = "command", command = "/usr/local/bin/page-oncall" },
+///     { type = "webhook", url = "http://alerts.internal/hooks/zquery" },
+/// ]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct RulesFile {
+    #[serde(rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+/// A query that fires when it starts returning rows and resolves when it stops.
+#[derive(Debug, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub sql: String,
+    /// How often to run the query, e.g. `30s` or `5m`.
+    #[serde(default = "default_every")]
+    pub every: String,
+    #[serde(default = "default_actions")]
+    pub actions: Vec<Action>,
+}
+
+fn default_every() -> String {
+    "1m".to_string()
+}
+
+fn default_actions() -> Vec<Action> {
+    vec![Action::Stdout]
+}
+
+/// What to do when a rule changes state.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Action {
+    /// Print the alert as a line of JSON.
+    Stdout,
+    /// Append the alert as a line of JSON to a file.
+    Log { path: PathBuf },
+    /// Run a command through `sh -c` with the rows as JSON on stdin, and the rule name
+    /// and state in `ZQUERY_RULE` and `ZQUERY_STATE`.
+    Command { command: String },
+    /// POST the alert as JSON to a URL.
+    Webhook { url: String },
+}
+
+/// Whether a rule's query is currently returning rows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleState {
+    Ok,
+    Firing,
+}
+
+impl RuleState {
+    fn name(&self) -> &'static str {
+        match self {
+            RuleState::Ok => "resolved",
+            RuleState::Firing => "firing",
+        }
+    }
+
+    /// The state a check's rows put the rule in, if that's a change. Only transitions
+    /// alert, so a disk that stays full pages once rather than on every check.
+    fn after(self, rows: &[Value]) -> Option<RuleState> {
+        let next = if rows.is_empty() { RuleState::Ok } else { RuleState::Firing };
+        (next != self).then_some(next)
+    }
+}
+
+pub fn load_rules(path: &Path) -> std::io::Result<RulesFile> {
+    let text = std::fs::read_to_string(path)?;
+    let rules: RulesFile = toml::from_str(&text)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    for rule in &rules.rules {
+        if parse_duration(&rule.every).is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Rule {} has an invalid schedule '{}'", rule.name, rule.every),
+            ));
+        }
+    }
+    Ok(rules)
+}
+
+/// Runs every rule on its own schedule until the process is stopped.
+pub async fn watch(ctx: SessionContext, rules: RulesFile) {
+    let mut tasks = Vec::new();
+    for rule in rules.rules {
+        let ctx = ctx.clone();
+        tasks.push(tokio::spawn(async move { watch_rule(ctx, rule).await }));
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+async fn watch_rule(ctx: SessionContext, rule: Rule) {
+    let every = parse_duration(&rule.every).unwrap_or(Duration::from_secs(60));
+    let mut ticker = tokio::time::interval(every);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut state = RuleState::Ok;
+
+    loop {
+        ticker.tick().await;
+        let rows = match evaluate(&ctx, &rule.sql).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                eprintln!("Rule {} failed: {}", rule.name, e);
+                continue;
+            }
+        };
+        if let Some(next) = state.after(&rows) {
+            state = next;
+            fire(&rule, state, rows).await;
+        }
+    }
+}
+
+async fn evaluate(ctx: &SessionContext, sql: &str) -> datafusion::error::Result<Vec<Value>> {
+    let batches = sql_to_df(ctx, sql).await?.collect().await?;
+    batches_to_json(&batches)
+}
+
+/// Turns batches into JSON objects, keeping numbers as numbers and writing nulls out so
+/// every row has every column.
+pub fn batches_to_json(batches: &[RecordBatch]) -> datafusion::error::Result<Vec<Value>> {
+    let mut writer = WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, JsonArray>(Vec::new());
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    let bytes = writer.into_inner();
+    if bytes.is_empty() {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_slice(&bytes) {
+        Ok(Value::Array(rows)) => Ok(rows),
+        Ok(_) => Ok(Vec::new()),
+        Err(e) => Err(datafusion::error::DataFusionError::External(Box::new(e))),
+    }
+}
+
+async fn fire(rule: &Rule, state: RuleState, rows: Vec<Value>) {
+    let alert = json!({
+        "time": chrono::Utc::now().to_rfc3339(),
+        "rule": rule.name,
+        "state": state.name(),
+        "rows": rows,
+    });
+    for action in &rule.actions {
+        let result = match action {
+            Action::Stdout => {
+                println!("{}", alert);
+                Ok(())
+            }
+            Action::Log { path } => append_line(path, &alert.to_string()),
+            Action::Command { command } => {
+                let command = command.clone();
+                let rule_name = rule.name.clone();
+                let rows = alert["rows"].to_string();
+                tokio::task::spawn_blocking(move || {
+                    run_command(&command, &rule_name, state.name(), &rows)
+                })
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+            }
+            Action::Webhook { url } => {
+                let url = url.clone();
+                let body = alert.to_string();
+                tokio::task::spawn_blocking(move || post(&url, &body))
+                    .await
+                    .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Rule {} action {:?} failed: {}", rule.name, action, e);
+        }
+    }
+}
+
+fn append_line(path: &Path, line: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+fn run_command(command: &str, rule: &str, state: &str, rows: &str) -> std::io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("ZQUERY_RULE", rule)
+        .env("ZQUERY_STATE", state)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().expect("Failed to open command stdin").write_all(rows.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!("exited with {}", status)));
+    }
+    Ok(())
+}
+
+fn post(url: &str, body: &str) -> std::io::Result<()> {
+    ureq::post(url)
+        .set("Content-Type", "application/json")
+        .timeout(Duration::from_secs(10))
+        .send_string(body)
+        .map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    #[test]
+    fn a_rule_alerts_only_when_it_changes_state() {
+        let full = vec![json!({ "mounted_on": "/", "capacity_percent": 95 })];
+        let fuller = vec![json!({ "mounted_on": "/", "capacity_percent": 99 })];
+        let checks = [vec![], full.clone(), full, fuller, vec![], vec![], vec![json!({})]];
+        let mut state = RuleState::Ok;
+        let mut alerts = Vec::new();
+        for rows in &checks {
+            let alert = state.after(rows);
+            if let Some(next) = alert {
+                state = next;
+            }
+            alerts.push(alert);
+        }
+        assert_eq!(
+            alerts,
+            [None, Some(RuleState::Firing), None, None, Some(RuleState::Ok), None, Some(RuleState::Firing)]
+        );
+    }
+
+    #[test]
+    fn null_columns_are_written_out() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("host", DataType::Utf8, true),
+            Field::new("load", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![Some("web1"), None])),
+                Arc::new(Int64Array::from(vec![None, Some(3)])),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            batches_to_json(&[batch]).unwrap(),
+            vec![json!({ "host": "web1", "load": null }), json!({ "host": null, "load": 3 })]
+        );
+    }
+
+    /// Accepts one request and returns its headers and body.
+    fn receive_one(listener: TcpListener) -> (Vec<String>, String) {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(socket.try_clone().unwrap());
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            headers.push(line.trim().to_lowercase());
+        }
+        let length = headers
+            .iter()
+            .find_map(|h| h.strip_prefix("content-length: "))
+            .map_or(0, |n| n.parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+        (headers, String::from_utf8(body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn webhooks_post_the_alert_as_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/zquery", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || receive_one(listener));
+        let rule = Rule {
+            name: "disk-full".to_string(),
+            sql: "select 1".to_string(),
+            every: default_every(),
+            actions: vec![Action::Webhook { url }],
+        };
+        let rows = vec![json!({ "host": "web1", "mounted_on": "/", "capacity_percent": 95 })];
+        fire(&rule, RuleState::Firing, rows.clone()).await;
+
+        let (headers, body) = server.join().unwrap();
+        assert!(headers[0].starts_with("post /hooks/zquery "), "{:?}", headers);
+        assert!(headers.contains(&"content-type: application/json".to_string()), "{:?}", headers);
+        let alert: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(alert["rule"], json!("disk-full"));
+        assert_eq!(alert["state"], json!("firing"));
+        assert_eq!(alert["rows"], json!(rows));
+        assert!(alert["time"].as_str().is_some_and(|t| chrono::DateTime::parse_from_rfc3339(t).is_ok()));
+    }
+}